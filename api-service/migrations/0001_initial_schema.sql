@@ -1,11 +1,15 @@
 -- Coffee Counter Database Schema
 -- Created: 2024-12-27
 
+-- Databases created before migrations existed were initialised from
+-- database/init/01-init.sql; every statement here is idempotent so those
+-- databases adopt this migration without changes.
+
 -- Enable UUID extension
 CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
 
 -- Users table for authentication
-CREATE TABLE users (
+CREATE TABLE IF NOT EXISTS users (
     id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
     email VARCHAR(255) UNIQUE NOT NULL,
     password_hash VARCHAR(255) NOT NULL,
@@ -18,7 +22,7 @@ CREATE TABLE users (
 );
 
 -- Categories table
-CREATE TABLE categories (
+CREATE TABLE IF NOT EXISTS categories (
     id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
     user_id UUID REFERENCES users(id) ON DELETE CASCADE,
     name VARCHAR(100) NOT NULL,
@@ -29,12 +33,12 @@ CREATE TABLE categories (
     is_active BOOLEAN DEFAULT true,
     created_at TIMESTAMPTZ DEFAULT NOW(),
     updated_at TIMESTAMPTZ DEFAULT NOW(),
-    
+
     UNIQUE(user_id, name)
 );
 
 -- Counter data table
-CREATE TABLE counter_data (
+CREATE TABLE IF NOT EXISTS counter_data (
     id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
     user_id UUID REFERENCES users(id) ON DELETE CASCADE,
     category_id UUID REFERENCES categories(id) ON DELETE CASCADE,
@@ -44,12 +48,12 @@ CREATE TABLE counter_data (
     notes TEXT,
     created_at TIMESTAMPTZ DEFAULT NOW(),
     updated_at TIMESTAMPTZ DEFAULT NOW(),
-    
+
     UNIQUE(user_id, category_id, date)
 );
 
 -- User sessions table (for refresh tokens)
-CREATE TABLE user_sessions (
+CREATE TABLE IF NOT EXISTS user_sessions (
     id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
     user_id UUID REFERENCES users(id) ON DELETE CASCADE,
     refresh_token VARCHAR(500) NOT NULL,
@@ -61,16 +65,16 @@ CREATE TABLE user_sessions (
 );
 
 -- Indexes for performance
-CREATE INDEX idx_users_email ON users(email);
-CREATE INDEX idx_users_active ON users(is_active);
-CREATE INDEX idx_categories_user ON categories(user_id);
-CREATE INDEX idx_categories_active ON categories(user_id, is_active);
-CREATE INDEX idx_counter_data_user_date ON counter_data(user_id, date);
-CREATE INDEX idx_counter_data_category ON counter_data(category_id);
-CREATE INDEX idx_counter_data_user_category_date ON counter_data(user_id, category_id, date);
-CREATE INDEX idx_sessions_user ON user_sessions(user_id);
-CREATE INDEX idx_sessions_token ON user_sessions(refresh_token);
-CREATE INDEX idx_sessions_expires ON user_sessions(expires_at);
+CREATE INDEX IF NOT EXISTS idx_users_email ON users(email);
+CREATE INDEX IF NOT EXISTS idx_users_active ON users(is_active);
+CREATE INDEX IF NOT EXISTS idx_categories_user ON categories(user_id);
+CREATE INDEX IF NOT EXISTS idx_categories_active ON categories(user_id, is_active);
+CREATE INDEX IF NOT EXISTS idx_counter_data_user_date ON counter_data(user_id, date);
+CREATE INDEX IF NOT EXISTS idx_counter_data_category ON counter_data(category_id);
+CREATE INDEX IF NOT EXISTS idx_counter_data_user_category_date ON counter_data(user_id, category_id, date);
+CREATE INDEX IF NOT EXISTS idx_sessions_user ON user_sessions(user_id);
+CREATE INDEX IF NOT EXISTS idx_sessions_token ON user_sessions(refresh_token);
+CREATE INDEX IF NOT EXISTS idx_sessions_expires ON user_sessions(expires_at);
 
 -- Function to update updated_at timestamp
 CREATE OR REPLACE FUNCTION update_updated_at_column()
@@ -82,13 +86,13 @@ END;
 $$ language 'plpgsql';
 
 -- Triggers for updated_at
-CREATE TRIGGER update_users_updated_at BEFORE UPDATE ON users
+CREATE OR REPLACE TRIGGER update_users_updated_at BEFORE UPDATE ON users
     FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
 
-CREATE TRIGGER update_categories_updated_at BEFORE UPDATE ON categories
+CREATE OR REPLACE TRIGGER update_categories_updated_at BEFORE UPDATE ON categories
     FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
 
-CREATE TRIGGER update_counter_data_updated_at BEFORE UPDATE ON counter_data
+CREATE OR REPLACE TRIGGER update_counter_data_updated_at BEFORE UPDATE ON counter_data
     FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
 
 -- Insert default categories for new users (function)
@@ -101,16 +105,3 @@ BEGIN
     (p_user_id, 'Protein', 'zap', '#E74C3C', 'g', 30.00);
 END;
 $$ LANGUAGE plpgsql;
-
--- Sample data for testing
-INSERT INTO users (email, password_hash, display_name, email_verified) VALUES
-('test@example.com', '$2b$12$LQv3c1yqBWVHxkd0LHAkCOYz6TtxMQJqhN8/LewdBPj/RK.s5uO.G', 'Test User', true);
-
--- Get the test user ID and create default categories
-DO $$
-DECLARE
-    test_user_id UUID;
-BEGIN
-    SELECT id INTO test_user_id FROM users WHERE email = 'test@example.com';
-    PERFORM create_default_categories(test_user_id);
-END $$;
@@ -0,0 +1,14 @@
+-- Soft delete for categories
+-- Created: 2026-10-19
+
+-- Deleting a category archives it; counter history is only removed once the
+-- archived category is purged after the trash retention period.
+ALTER TABLE categories ADD COLUMN deleted_at TIMESTAMPTZ;
+
+-- Archived categories no longer reserve their name
+ALTER TABLE categories DROP CONSTRAINT categories_user_id_name_key;
+CREATE UNIQUE INDEX idx_categories_user_name_live ON categories(user_id, name)
+    WHERE deleted_at IS NULL;
+
+CREATE INDEX idx_categories_deleted ON categories(deleted_at)
+    WHERE deleted_at IS NOT NULL;
@@ -0,0 +1,26 @@
+-- User-defined category ordering and grouping
+-- Created: 2026-10-19
+
+ALTER TABLE categories ADD COLUMN sort_order INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE categories ADD COLUMN group_name VARCHAR(50);
+
+-- Keep the previous creation-time order for existing categories
+UPDATE categories c SET sort_order = ranked.position
+FROM (
+    SELECT id, ROW_NUMBER() OVER (PARTITION BY user_id ORDER BY created_at, id) - 1 AS position
+    FROM categories
+) ranked
+WHERE c.id = ranked.id;
+
+CREATE INDEX idx_categories_user_order ON categories(user_id, sort_order);
+
+-- Default categories start out in a fixed order
+CREATE OR REPLACE FUNCTION create_default_categories(p_user_id UUID)
+RETURNS VOID AS $$
+BEGIN
+    INSERT INTO categories (user_id, name, icon, color, unit, default_amount, sort_order) VALUES
+    (p_user_id, 'Coffee', 'coffee', '#8B4513', 'ml', 250.00, 0),
+    (p_user_id, 'Water', 'droplets', '#4A90E2', 'ml', 500.00, 1),
+    (p_user_id, 'Protein', 'zap', '#E74C3C', 'g', 30.00, 2);
+END;
+$$ LANGUAGE plpgsql;
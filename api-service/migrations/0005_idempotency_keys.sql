@@ -0,0 +1,18 @@
+-- Responses to counter writes sent with an Idempotency-Key, replayed to retries
+-- Created: 2026-10-19
+
+-- A row is claimed when a keyed request starts and gets its response in the
+-- same transaction as the write, so a concurrent retry waits on the primary
+-- key and then replays. Rows past the TTL are replaced on reuse. Keys are
+-- scoped by the operation they were sent to, so a header key on
+-- POST /counters and a batch operation id never share a namespace.
+CREATE TABLE idempotency_keys (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    operation VARCHAR(50) NOT NULL,
+    idempotency_key VARCHAR(255) NOT NULL,
+    request_hash VARCHAR(64) NOT NULL,
+    response JSONB,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    PRIMARY KEY (user_id, operation, idempotency_key)
+);
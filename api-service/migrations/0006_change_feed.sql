@@ -0,0 +1,85 @@
+-- Change sequence and tombstones for the offline sync feed
+-- Created: 2026-10-19
+
+-- Every insert or update of a category or counter entry takes the next
+-- number, and so does every delete, which leaves a tombstone behind. The
+-- numbers come from one sequence so they never tie, and adding the column
+-- numbers the existing rows.
+CREATE SEQUENCE change_seq;
+
+ALTER TABLE categories ADD COLUMN change_seq BIGINT NOT NULL DEFAULT nextval('change_seq');
+ALTER TABLE counter_data ADD COLUMN change_seq BIGINT NOT NULL DEFAULT nextval('change_seq');
+
+CREATE INDEX idx_categories_user_change_seq ON categories(user_id, change_seq);
+CREATE INDEX idx_counter_data_user_change_seq ON counter_data(user_id, change_seq);
+
+-- No foreign key on user_id: removing a user deletes their rows, and the
+-- tombstones that leaves must not refer to a user that is going away.
+CREATE TABLE sync_tombstones (
+    change_seq BIGINT PRIMARY KEY,
+    user_id UUID NOT NULL,
+    entity_type VARCHAR(50) NOT NULL,
+    entity_id UUID NOT NULL,
+    category_id UUID,
+    date DATE,
+    deleted_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_sync_tombstones_user_change_seq ON sync_tombstones(user_id, change_seq);
+
+-- Sequence numbers are handed out in call order but become visible in commit
+-- order, so a reader could see 8 committed before 7 and move its cursor past
+-- 7 for good. Holding a per-user lock from a transaction's first change until
+-- it commits makes each user's changes commit in sequence order. The
+-- application takes it at the start of its write transactions, before any
+-- row locks, so that writers queue here rather than deadlock.
+CREATE OR REPLACE FUNCTION lock_change_seq(p_user_id UUID)
+RETURNS VOID AS $$
+BEGIN
+    PERFORM pg_advisory_xact_lock(hashtext('change_seq'), hashtext(p_user_id::text));
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION next_change_seq(p_user_id UUID)
+RETURNS BIGINT AS $$
+BEGIN
+    PERFORM lock_change_seq(p_user_id);
+    RETURN nextval('change_seq');
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION stamp_change_seq()
+RETURNS TRIGGER AS $$
+BEGIN
+    NEW.change_seq = next_change_seq(NEW.user_id);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION bury_category()
+RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO sync_tombstones (change_seq, user_id, entity_type, entity_id)
+    VALUES (next_change_seq(OLD.user_id), OLD.user_id, 'category', OLD.id);
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION bury_counter_data()
+RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO sync_tombstones (change_seq, user_id, entity_type, entity_id, category_id, date)
+    VALUES (next_change_seq(OLD.user_id), OLD.user_id, 'counter_data', OLD.id, OLD.category_id, OLD.date);
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER stamp_categories_change_seq BEFORE INSERT OR UPDATE ON categories
+    FOR EACH ROW EXECUTE FUNCTION stamp_change_seq();
+CREATE TRIGGER stamp_counter_data_change_seq BEFORE INSERT OR UPDATE ON counter_data
+    FOR EACH ROW EXECUTE FUNCTION stamp_change_seq();
+
+CREATE TRIGGER bury_categories AFTER DELETE ON categories
+    FOR EACH ROW EXECUTE FUNCTION bury_category();
+CREATE TRIGGER bury_counter_data AFTER DELETE ON counter_data
+    FOR EACH ROW EXECUTE FUNCTION bury_counter_data();
@@ -0,0 +1,46 @@
+-- Coffee Counter schema for the SQLite backend (`--features sqlite`).
+-- Mirrors the Postgres migrations one level up, with SQLite types:
+-- UUIDs are 16-byte BLOBs, timestamps RFC 3339 TEXT written by the
+-- application, booleans INTEGER and counter amounts a JSON array.
+-- Users live in the auth service, so there is no users table here.
+-- Later migrations share their Postgres counterpart's number and name; the
+-- category soft delete (0003) and ordering (0004) columns are part of this
+-- table from the start, so those numbers are unused.
+
+CREATE TABLE categories (
+    id BLOB PRIMARY KEY NOT NULL,
+    user_id BLOB NOT NULL,
+    name TEXT NOT NULL,
+    icon TEXT NOT NULL DEFAULT 'coffee',
+    color TEXT NOT NULL DEFAULT '#8B4513',
+    unit TEXT NOT NULL DEFAULT 'ml',
+    default_amount REAL NOT NULL DEFAULT 250.0,
+    is_active INTEGER NOT NULL DEFAULT 1,
+    sort_order INTEGER NOT NULL DEFAULT 0,
+    group_name TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    deleted_at TEXT
+);
+
+-- Names only need to be unique among categories that are not in the trash
+CREATE UNIQUE INDEX idx_categories_user_name_live ON categories(user_id, name)
+    WHERE deleted_at IS NULL;
+CREATE INDEX idx_categories_user_order ON categories(user_id, sort_order);
+
+CREATE TABLE counter_data (
+    id BLOB PRIMARY KEY NOT NULL,
+    user_id BLOB NOT NULL,
+    category_id BLOB NOT NULL REFERENCES categories(id) ON DELETE CASCADE,
+    date TEXT NOT NULL,
+    count INTEGER NOT NULL DEFAULT 0,
+    amounts TEXT NOT NULL DEFAULT '[]',
+    notes TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+
+    UNIQUE(user_id, category_id, date)
+);
+
+CREATE INDEX idx_counter_data_user_date ON counter_data(user_id, date);
+CREATE INDEX idx_counter_data_category ON counter_data(category_id);
@@ -0,0 +1,27 @@
+-- Audit trail for data-changing operations; see the Postgres migration of
+-- the same name. Entries are written by the application in the transaction
+-- of the change, with before_data and after_data as JSON TEXT.
+
+CREATE TABLE audit_log (
+    id BLOB PRIMARY KEY NOT NULL,
+    user_id BLOB NOT NULL,
+    actor_id BLOB NOT NULL,
+    action TEXT NOT NULL,
+    entity_type TEXT NOT NULL,
+    entity_id BLOB,
+    session_id TEXT,
+    request_id TEXT,
+    before_data TEXT,
+    after_data TEXT,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX idx_audit_log_user_created ON audit_log(user_id, created_at DESC);
+CREATE INDEX idx_audit_log_entity ON audit_log(entity_type, entity_id);
+CREATE INDEX idx_audit_log_created ON audit_log(created_at);
+
+-- Entries may be purged by retention, but never rewritten
+CREATE TRIGGER audit_log_append_only BEFORE UPDATE ON audit_log
+BEGIN
+    SELECT RAISE(ABORT, 'audit_log is append-only');
+END;
@@ -0,0 +1,13 @@
+-- Responses to counter writes sent with an Idempotency-Key; see the Postgres
+-- migration of the same name. The response is JSON TEXT.
+
+CREATE TABLE idempotency_keys (
+    user_id BLOB NOT NULL,
+    operation TEXT NOT NULL,
+    idempotency_key TEXT NOT NULL,
+    request_hash TEXT NOT NULL,
+    response TEXT,
+    created_at TEXT NOT NULL,
+
+    PRIMARY KEY (user_id, operation, idempotency_key)
+);
@@ -0,0 +1,74 @@
+-- Change sequence and tombstones for the offline sync feed; see the Postgres
+-- migration of the same name. Writers are already serialised by SQLite, so
+-- a single-row counter is all the sequence needs.
+
+CREATE TABLE change_sequence (value INTEGER NOT NULL);
+
+ALTER TABLE categories ADD COLUMN change_seq INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE counter_data ADD COLUMN change_seq INTEGER NOT NULL DEFAULT 0;
+
+-- Number the existing rows without ties
+UPDATE categories SET change_seq = rowid;
+UPDATE counter_data SET change_seq = rowid + (SELECT COALESCE(MAX(rowid), 0) FROM categories);
+INSERT INTO change_sequence (value)
+    SELECT COALESCE(MAX(change_seq), 0) FROM (
+        SELECT change_seq FROM categories UNION ALL SELECT change_seq FROM counter_data
+    );
+
+CREATE INDEX idx_categories_user_change_seq ON categories(user_id, change_seq);
+CREATE INDEX idx_counter_data_user_change_seq ON counter_data(user_id, change_seq);
+
+CREATE TABLE sync_tombstones (
+    change_seq INTEGER PRIMARY KEY NOT NULL,
+    user_id BLOB NOT NULL,
+    entity_type TEXT NOT NULL,
+    entity_id BLOB NOT NULL,
+    category_id BLOB,
+    date TEXT,
+    deleted_at TEXT NOT NULL
+);
+
+CREATE INDEX idx_sync_tombstones_user_change_seq ON sync_tombstones(user_id, change_seq);
+
+-- The update triggers skip the triggers' own stamping updates
+CREATE TRIGGER stamp_categories_insert AFTER INSERT ON categories
+BEGIN
+    UPDATE change_sequence SET value = value + 1;
+    UPDATE categories SET change_seq = (SELECT value FROM change_sequence) WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER stamp_categories_update AFTER UPDATE ON categories
+    WHEN NEW.change_seq = OLD.change_seq
+BEGIN
+    UPDATE change_sequence SET value = value + 1;
+    UPDATE categories SET change_seq = (SELECT value FROM change_sequence) WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER stamp_counter_data_insert AFTER INSERT ON counter_data
+BEGIN
+    UPDATE change_sequence SET value = value + 1;
+    UPDATE counter_data SET change_seq = (SELECT value FROM change_sequence) WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER stamp_counter_data_update AFTER UPDATE ON counter_data
+    WHEN NEW.change_seq = OLD.change_seq
+BEGIN
+    UPDATE change_sequence SET value = value + 1;
+    UPDATE counter_data SET change_seq = (SELECT value FROM change_sequence) WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER bury_categories AFTER DELETE ON categories
+BEGIN
+    UPDATE change_sequence SET value = value + 1;
+    INSERT INTO sync_tombstones (change_seq, user_id, entity_type, entity_id, deleted_at)
+    VALUES ((SELECT value FROM change_sequence), OLD.user_id, 'category', OLD.id,
+        strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+END;
+
+CREATE TRIGGER bury_counter_data AFTER DELETE ON counter_data
+BEGIN
+    UPDATE change_sequence SET value = value + 1;
+    INSERT INTO sync_tombstones (change_seq, user_id, entity_type, entity_id, category_id, date, deleted_at)
+    VALUES ((SELECT value FROM change_sequence), OLD.user_id, 'counter_data', OLD.id,
+        OLD.category_id, OLD.date, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+END;
@@ -0,0 +1,238 @@
+use axum::{
+    extract::Extension,
+    http::StatusCode,
+    middleware,
+    response::Json,
+    routing::get,
+    Router,
+};
+use serde_json::json;
+use sqlx::{postgres::PgPoolOptions, PgPool};
+use std::sync::Arc;
+use tower::ServiceBuilder;
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tower_http::trace::TraceLayer;
+use tracing::{info, warn};
+
+use crate::config::{Config, StorageConfig};
+use crate::db::{
+    prepare_schema, DynAuditRepository, DynCategoryRepository, DynCounterRepository,
+    DynSyncRepository, DynTrashRepository, MemoryRepository, PostgresRepository, StorageBackend,
+};
+#[cfg(feature = "sqlite")]
+use crate::db::SqliteRepository;
+use crate::handlers::health::{self, Readiness};
+use crate::handlers::{audit, categories, counters, docs, metrics, sync};
+use crate::utils::cors::cors_layer;
+use crate::utils::live::LiveUpdates;
+use crate::utils::metrics::{track_requests, Metrics};
+use crate::utils::rate_limit::{rate_limit, RateLimiter};
+use crate::utils::request_id::echo_request_id;
+use crate::utils::telemetry;
+use crate::utils::version::{deprecated, ApiVersion, Deprecation};
+
+/// The repositories the routes are served from, plus the Postgres pool or
+/// SQLite repository when there is one, for readiness checks. The audit log
+/// and trash purge exist with a database only.
+#[derive(Clone)]
+pub struct Storage {
+    pub pool: Option<PgPool>,
+    #[cfg(feature = "sqlite")]
+    pub sqlite: Option<Arc<SqliteRepository>>,
+    pub categories: DynCategoryRepository,
+    pub counters: DynCounterRepository,
+    pub sync: DynSyncRepository,
+    pub audit: Option<DynAuditRepository>,
+    pub trash: Option<DynTrashRepository>,
+}
+
+impl Storage {
+    /// Opens the configured backend and brings its schema up to date (or
+    /// verifies it, depending on the migrations mode).
+    pub async fn connect(config: &StorageConfig) -> anyhow::Result<Self> {
+        let mode = config.migrations;
+
+        match config.storage_backend().map_err(anyhow::Error::msg)? {
+            StorageBackend::Postgres { url } => {
+                let pool = PgPoolOptions::new()
+                    .max_connections(config.max_connections)
+                    .min_connections(config.min_connections)
+                    .acquire_timeout(config.acquire_timeout())
+                    .connect(&url)
+                    .await?;
+
+                info!("Database connected");
+
+                prepare_schema(&pool, mode).await?;
+                info!("Database schema is up to date ({:?} mode)", mode);
+
+                Ok(Self::postgres(pool))
+            }
+            #[cfg(feature = "sqlite")]
+            StorageBackend::Sqlite { url } => {
+                let repository = Arc::new(SqliteRepository::connect(&url).await?);
+
+                info!("SQLite database opened");
+
+                repository.prepare_schema(mode).await?;
+                info!("Database schema is up to date ({:?} mode)", mode);
+
+                Ok(Self::sqlite(repository))
+            }
+            StorageBackend::Memory => {
+                warn!("Using in-memory storage: data is lost on restart and the audit log is disabled");
+
+                Ok(Self::memory())
+            }
+        }
+    }
+
+    /// Storage for a pool whose schema is already prepared.
+    pub fn postgres(pool: PgPool) -> Self {
+        let repository = Arc::new(PostgresRepository::new(pool.clone()));
+
+        Self {
+            pool: Some(pool),
+            #[cfg(feature = "sqlite")]
+            sqlite: None,
+            categories: repository.clone(),
+            counters: repository.clone(),
+            sync: repository.clone(),
+            audit: Some(repository.clone()),
+            trash: Some(repository),
+        }
+    }
+
+    /// Storage for a SQLite database whose schema is already prepared.
+    #[cfg(feature = "sqlite")]
+    pub fn sqlite(repository: Arc<SqliteRepository>) -> Self {
+        Self {
+            pool: None,
+            sqlite: Some(repository.clone()),
+            categories: repository.clone(),
+            counters: repository.clone(),
+            sync: repository.clone(),
+            audit: Some(repository.clone()),
+            trash: Some(repository),
+        }
+    }
+
+    pub fn memory() -> Self {
+        let repository = Arc::new(MemoryRepository::new());
+
+        Self {
+            pool: None,
+            #[cfg(feature = "sqlite")]
+            sqlite: None,
+            categories: repository.clone(),
+            counters: repository.clone(),
+            sync: repository,
+            audit: None,
+            trash: None,
+        }
+    }
+}
+
+/// Builds the complete application: routes, middleware and the 404 fallback.
+/// Background tasks are not started here; see `main`, which also clears
+/// `readiness` on shutdown.
+pub fn app(config: &Config, storage: Storage, readiness: Readiness) -> Router {
+    let metrics = Metrics::new(storage.pool.clone(), config.storage.max_connections);
+    let live = LiveUpdates::new(storage.pool.clone());
+
+    // API routes, relative to the version prefix
+    let mut api = Router::new()
+        .nest("/categories", categories::routes())
+        .nest("/counters", counters::routes())
+        .nest("/sync", sync::routes());
+
+    // In-memory storage keeps no audit log
+    if storage.audit.is_some() && config.features.audit_api {
+        api = api
+            .nest("/audit", audit::routes())
+            .nest("/admin/audit", audit::admin_routes());
+    }
+
+    let mut app = Router::new()
+        // Health checks
+        .route("/health", get(health::readiness))
+        .route("/health/live", get(health::liveness))
+        .route("/health/ready", get(health::readiness))
+
+        // Each version fixes the response shape of handlers that have several
+        .nest("/api/v1", api.clone().layer(Extension(ApiVersion::V1)))
+        .nest("/api/v2", api.clone().layer(Extension(ApiVersion::V2)));
+
+    // The original unversioned paths, which negotiate the shape on Accept
+    if config.api.legacy_routes {
+        let deprecation = Deprecation::new(config.api.legacy_sunset);
+        app = app.nest("/api", api.layer(middleware::from_fn_with_state(deprecation, deprecated)));
+    }
+
+    if config.features.metrics {
+        app = app.route("/metrics", get(metrics::metrics));
+    }
+
+    if let Some(pool) = storage.pool {
+        app = app.layer(Extension(pool));
+    }
+    #[cfg(feature = "sqlite")]
+    if let Some(sqlite) = storage.sqlite {
+        app = app.layer(Extension(sqlite));
+    }
+    if let Some(audit_log) = storage.audit {
+        app = app.layer(Extension(audit_log));
+    }
+
+    // Inside the extension layers below, which it needs to authenticate
+    if config.rate_limit.enabled {
+        let limiter = RateLimiter::from_config(&config.rate_limit);
+        app = app.layer(middleware::from_fn_with_state(limiter, rate_limit));
+    }
+
+    // API documentation, after the rate limiter so it can be read without
+    // a token
+    app = app
+        .route("/api/openapi.json", get(docs::openapi_json))
+        .route("/api/docs", get(docs::viewer));
+
+    // 404 handler, after the rate limiter so unknown routes aren't
+    // authenticated but before the layers below so they still get request
+    // IDs, CORS headers and metrics
+    app = app.fallback(|| async {
+        (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "success": false,
+                "message": "Route not found"
+            }))
+        )
+    });
+
+    // Outside the rate limiter so rejected requests are counted too
+    app = app.layer(middleware::from_fn_with_state(metrics.clone(), track_requests));
+
+    app
+        // Middleware
+        .layer(
+            ServiceBuilder::new()
+                .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+                .layer(PropagateRequestIdLayer::x_request_id())
+                .layer(
+                    TraceLayer::new_for_http()
+                        .make_span_with(telemetry::make_span)
+                        .on_response(telemetry::on_response),
+                )
+                .layer(middleware::from_fn(echo_request_id))
+                .layer(cors_layer(&config.cors))
+                .layer(Extension(storage.categories))
+                .layer(Extension(storage.counters))
+                .layer(Extension(storage.sync))
+                .layer(Extension(config.idempotency.settings()))
+                .layer(Extension(live))
+                .layer(Extension(config.auth.auth_service().with_metrics(metrics.clone())))
+                .layer(Extension(metrics))
+                .layer(Extension(readiness))
+                .layer(Extension(config.auth.admin_users()))
+        )
+}
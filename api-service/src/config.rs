@@ -0,0 +1,908 @@
+use axum::http::{HeaderName, Method};
+use chrono::NaiveDate;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::db::{MigrationMode, StorageBackend};
+use crate::utils::audit::AuditSettings;
+use crate::utils::auth::{AdminUsers, AuthService};
+use crate::utils::cors::OriginPattern;
+use crate::utils::idempotency::IdempotencySettings;
+use crate::utils::trash::TrashSettings;
+
+/// Every problem found while loading the configuration, so they can all be
+/// fixed in one go rather than one restart at a time.
+#[derive(Debug, thiserror::Error)]
+#[error("invalid configuration:\n  - {}", .0.join("\n  - "))]
+pub struct ConfigError(pub Vec<String>);
+
+/// Service configuration. Built from defaults, then an optional TOML file,
+/// then environment variables, each overriding the one before.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub api: ApiConfig,
+    pub log: LogConfig,
+    pub telemetry: TelemetryConfig,
+    pub storage: StorageConfig,
+    pub auth: AuthConfig,
+    pub cors: CorsConfig,
+    pub audit: AuditConfig,
+    pub trash: TrashConfig,
+    pub idempotency: IdempotencyConfig,
+    pub rate_limit: RateLimitConfig,
+    pub features: FeaturesConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: IpAddr,
+    pub port: u16,
+    /// On SIGTERM, how long to keep serving while reporting not-ready so
+    /// load balancers can stop sending traffic first.
+    pub shutdown_drain_secs: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            port: 8080,
+            shutdown_drain_secs: 5,
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn shutdown_drain(&self) -> Duration {
+        Duration::from_secs(self.shutdown_drain_secs)
+    }
+}
+
+/// The unversioned `/api/...` routes, kept as deprecated aliases of `/api/v1`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiConfig {
+    pub legacy_routes: bool,
+    /// Announced in the aliases' `Sunset` header as the day they go away.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub legacy_sunset: Option<NaiveDate>,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            legacy_routes: true,
+            legacy_sunset: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines.
+    #[default]
+    Text,
+    /// One JSON object per line, span fields included, for the log pipeline.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => Err(format!(
+                "invalid log format '{}', expected 'text' or 'json'",
+                other
+            )),
+        }
+    }
+}
+
+/// Levels are still chosen with `RUST_LOG`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    pub format: LogFormat,
+}
+
+/// OpenTelemetry trace export, off unless a collector is configured.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TelemetryConfig {
+    /// Base URL of an OTLP/HTTP collector, e.g. `http://localhost:4318`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub otlp_endpoint: Option<String>,
+    pub service_name: String,
+    /// Share of traces started here that are exported. Requests arriving
+    /// with a `traceparent` follow the caller's decision instead.
+    pub sample_ratio: f64,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            otlp_endpoint: None,
+            service_name: "coffee-counter-api".to_string(),
+            sample_ratio: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageKind {
+    /// Postgres or SQLite, chosen by the scheme of `database_url`.
+    #[default]
+    Database,
+    /// Process-local storage for demos; nothing survives a restart.
+    Memory,
+}
+
+impl FromStr for StorageKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "database" => Ok(StorageKind::Database),
+            "memory" => Ok(StorageKind::Memory),
+            other => Err(format!(
+                "invalid storage backend '{}', expected 'database' or 'memory'",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    pub backend: StorageKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub database_url: Option<String>,
+    /// Postgres pool bounds; SQLite always uses a single connection.
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub acquire_timeout_secs: u64,
+    pub migrations: MigrationMode,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            backend: StorageKind::Database,
+            database_url: None,
+            max_connections: 20,
+            min_connections: 0,
+            acquire_timeout_secs: 30,
+            migrations: MigrationMode::Run,
+        }
+    }
+}
+
+impl StorageConfig {
+    pub fn storage_backend(&self) -> Result<StorageBackend, String> {
+        match self.backend {
+            StorageKind::Memory => Ok(StorageBackend::Memory),
+            StorageKind::Database => {
+                let url = self.database_url.as_deref().ok_or_else(|| {
+                    "storage.database_url (DATABASE_URL) must be set for database storage"
+                        .to_string()
+                })?;
+                StorageBackend::from_database_url(url)
+            }
+        }
+    }
+
+    pub fn acquire_timeout(&self) -> Duration {
+        Duration::from_secs(self.acquire_timeout_secs)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuthMode {
+    /// Bearer tokens are verified by the auth service.
+    #[default]
+    Service,
+    /// No tokens: every request acts as `single_user_id`. Meant for
+    /// self-hosted SQLite or in-memory installs with one user.
+    SingleUser,
+}
+
+impl FromStr for AuthMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "service" => Ok(AuthMode::Service),
+            "single-user" => Ok(AuthMode::SingleUser),
+            other => Err(format!(
+                "invalid auth mode '{}', expected 'service' or 'single-user'",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    pub mode: AuthMode,
+    pub service_url: String,
+    pub timeout_ms: u64,
+    pub single_user_id: Uuid,
+    /// Email addresses allowed to use the admin endpoints.
+    pub admin_emails: Vec<String>,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            mode: AuthMode::Service,
+            service_url: "http://auth-service:3001".to_string(),
+            timeout_ms: 5000,
+            single_user_id: Uuid::from_u128(1),
+            admin_emails: Vec::new(),
+        }
+    }
+}
+
+impl AuthConfig {
+    pub fn auth_service(&self) -> AuthService {
+        match self.mode {
+            AuthMode::Service => {
+                AuthService::with_timeout(&self.service_url, Duration::from_millis(self.timeout_ms))
+            }
+            AuthMode::SingleUser => AuthService::single_user(self.single_user_id),
+        }
+    }
+
+    pub fn admin_users(&self) -> AdminUsers {
+        AdminUsers::new(&self.admin_emails)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    /// Origins allowed to call the API from a browser: exact origins or
+    /// `https://*.example.com` patterns. Empty allows none; `*` allows any.
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    /// Let browsers send cookies and HTTP auth; not combinable with `*`.
+    pub allow_credentials: bool,
+    /// How long browsers may cache a preflight response.
+    pub max_age_secs: u64,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: Vec::new(),
+            allowed_methods: ["GET", "POST", "PUT", "PATCH", "DELETE", "OPTIONS"]
+                .map(String::from)
+                .to_vec(),
+            allowed_headers: [
+                "Content-Type",
+                "Authorization",
+                "If-None-Match",
+                "If-Match",
+                "Idempotency-Key",
+            ]
+            .map(String::from)
+            .to_vec(),
+            allow_credentials: false,
+            max_age_secs: 3600,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuditConfig {
+    /// Entries older than this many days are purged; 0 keeps them forever.
+    pub retention_days: i64,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            retention_days: 365,
+        }
+    }
+}
+
+impl AuditConfig {
+    pub fn settings(&self) -> AuditSettings {
+        AuditSettings::new(self.retention_days)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrashConfig {
+    /// Archived categories older than this many days are purged; 0 keeps them forever.
+    pub retention_days: i64,
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        Self { retention_days: 30 }
+    }
+}
+
+impl TrashConfig {
+    pub fn settings(&self) -> TrashSettings {
+        TrashSettings::new(self.retention_days)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IdempotencyConfig {
+    /// How long a counter write sent with an `Idempotency-Key` is replayed
+    /// to retries with the same key.
+    pub ttl_hours: u64,
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        Self { ttl_hours: 24 }
+    }
+}
+
+impl IdempotencyConfig {
+    pub fn settings(&self) -> IdempotencySettings {
+        IdempotencySettings::new(self.ttl_hours)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    /// Tried in order; the first rule matching a request decides its limit,
+    /// and requests matching none are not limited. Each rule is counted
+    /// separately per user.
+    pub rules: Vec<RateLimitRule>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            rules: vec![
+                RateLimitRule::new("counter-writes", &["POST"], "/api/counters", 120),
+                RateLimitRule::new("writes", &["POST", "PUT", "PATCH", "DELETE"], "/api", 60),
+                RateLimitRule::new("reads", &[], "/api", 300),
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitRule {
+    pub name: String,
+    /// Empty matches every method.
+    #[serde(default)]
+    pub methods: Vec<String>,
+    /// Matches this path and everything below it. Write it without a version:
+    /// `/api/counters` also covers `/api/v1/counters`.
+    pub path_prefix: String,
+    pub requests: u32,
+    #[serde(default = "RateLimitRule::default_window_secs")]
+    pub window_secs: u64,
+}
+
+impl RateLimitRule {
+    fn new(name: &str, methods: &[&str], path_prefix: &str, requests: u32) -> Self {
+        Self {
+            name: name.to_string(),
+            methods: methods.iter().map(|method| method.to_string()).collect(),
+            path_prefix: path_prefix.to_string(),
+            requests,
+            window_secs: Self::default_window_secs(),
+        }
+    }
+
+    fn default_window_secs() -> u64 {
+        60
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeaturesConfig {
+    /// Serve `/api/audit` and `/api/admin/audit` (Postgres only).
+    pub audit_api: bool,
+    /// Run audit retention and trash purge in this process. Turn off on all
+    /// but one replica.
+    pub background_jobs: bool,
+    /// Serve Prometheus metrics at `/metrics`.
+    pub metrics: bool,
+}
+
+impl Default for FeaturesConfig {
+    fn default() -> Self {
+        Self {
+            audit_api: true,
+            background_jobs: true,
+            metrics: true,
+        }
+    }
+}
+
+impl Config {
+    /// Loads the configuration from `path` (if given) and the process environment.
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let mut errors = Vec::new();
+
+        let file = path.and_then(|path| match std::fs::read_to_string(path) {
+            Ok(contents) => Some((path.display().to_string(), contents)),
+            Err(e) => {
+                errors.push(format!("{}: {}", path.display(), e));
+                None
+            }
+        });
+
+        let file = file
+            .as_ref()
+            .map(|(name, contents)| (name.as_str(), contents.as_str()));
+        match Self::from_sources(file, |key| std::env::var(key).ok()) {
+            Ok(config) if errors.is_empty() => Ok(config),
+            Ok(_) => Err(ConfigError(errors)),
+            Err(ConfigError(more)) => {
+                errors.extend(more);
+                Err(ConfigError(errors))
+            }
+        }
+    }
+
+    /// Builds the configuration from a TOML document (`(name, contents)`) and
+    /// an environment lookup, then validates the result.
+    pub fn from_sources(
+        file: Option<(&str, &str)>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, ConfigError> {
+        let mut errors = Vec::new();
+
+        let mut config = match file {
+            Some((name, contents)) => toml::from_str(contents).unwrap_or_else(|e| {
+                errors.push(format!("{}: {}", name, e));
+                Config::default()
+            }),
+            None => Config::default(),
+        };
+
+        let mut env = EnvOverlay {
+            lookup: env,
+            errors: &mut errors,
+        };
+        env.set("HOST", &mut config.server.host);
+        env.set("PORT", &mut config.server.port);
+        env.set("SHUTDOWN_DRAIN_SECS", &mut config.server.shutdown_drain_secs);
+        env.set("API_LEGACY_ROUTES", &mut config.api.legacy_routes);
+        env.set_optional("API_LEGACY_SUNSET", &mut config.api.legacy_sunset);
+        env.set("LOG_FORMAT", &mut config.log.format);
+        env.set_optional(
+            "OTEL_EXPORTER_OTLP_ENDPOINT",
+            &mut config.telemetry.otlp_endpoint,
+        );
+        env.set("OTEL_SERVICE_NAME", &mut config.telemetry.service_name);
+        env.set("OTEL_TRACES_SAMPLER_ARG", &mut config.telemetry.sample_ratio);
+        env.set("STORAGE_BACKEND", &mut config.storage.backend);
+        env.set_optional("DATABASE_URL", &mut config.storage.database_url);
+        env.set("DB_POOL_MAX_SIZE", &mut config.storage.max_connections);
+        env.set("DB_POOL_MIN_IDLE", &mut config.storage.min_connections);
+        env.set("DB_POOL_TIMEOUT", &mut config.storage.acquire_timeout_secs);
+        env.set("MIGRATIONS_MODE", &mut config.storage.migrations);
+        env.set("AUTH_MODE", &mut config.auth.mode);
+        env.set("AUTH_SERVICE_URL", &mut config.auth.service_url);
+        env.set("AUTH_SERVICE_TIMEOUT", &mut config.auth.timeout_ms);
+        env.set("AUTH_SINGLE_USER_ID", &mut config.auth.single_user_id);
+        env.set_list("ADMIN_EMAILS", &mut config.auth.admin_emails);
+        env.set_list("CORS_ALLOWED_ORIGINS", &mut config.cors.allowed_origins);
+        env.set_list("CORS_ALLOWED_METHODS", &mut config.cors.allowed_methods);
+        env.set_list("CORS_ALLOWED_HEADERS", &mut config.cors.allowed_headers);
+        env.set("CORS_ALLOW_CREDENTIALS", &mut config.cors.allow_credentials);
+        env.set("CORS_MAX_AGE", &mut config.cors.max_age_secs);
+        env.set("AUDIT_RETENTION_DAYS", &mut config.audit.retention_days);
+        env.set(
+            "CATEGORY_TRASH_RETENTION_DAYS",
+            &mut config.trash.retention_days,
+        );
+        env.set("IDEMPOTENCY_TTL_HOURS", &mut config.idempotency.ttl_hours);
+        env.set("RATE_LIMIT_ENABLED", &mut config.rate_limit.enabled);
+        env.set("FEATURE_AUDIT_API", &mut config.features.audit_api);
+        env.set(
+            "FEATURE_BACKGROUND_JOBS",
+            &mut config.features.background_jobs,
+        );
+        env.set("FEATURE_METRICS", &mut config.features.metrics);
+
+        errors.extend(config.validate());
+
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigError(errors))
+        }
+    }
+
+    fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        let backend = self.storage.storage_backend();
+        if let Err(e) = &backend {
+            errors.push(e.clone());
+        }
+        if self.storage.max_connections == 0 {
+            errors.push("storage.max_connections must be at least 1".to_string());
+        }
+        if self.storage.min_connections > self.storage.max_connections {
+            errors.push(format!(
+                "storage.min_connections ({}) must not exceed storage.max_connections ({})",
+                self.storage.min_connections, self.storage.max_connections
+            ));
+        }
+        if self.storage.acquire_timeout_secs == 0 {
+            errors.push("storage.acquire_timeout_secs must be at least 1".to_string());
+        }
+
+        if let Some(endpoint) = &self.telemetry.otlp_endpoint {
+            match Url::parse(endpoint) {
+                Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+                _ => errors.push(format!(
+                    "telemetry.otlp_endpoint '{}' is not an http(s) URL",
+                    endpoint
+                )),
+            }
+        }
+        if self.telemetry.service_name.trim().is_empty() {
+            errors.push("telemetry.service_name must not be empty".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.telemetry.sample_ratio) {
+            errors.push(format!(
+                "telemetry.sample_ratio must be between 0 and 1, got {}",
+                self.telemetry.sample_ratio
+            ));
+        }
+
+        match self.auth.mode {
+            AuthMode::Service => {
+                match Url::parse(&self.auth.service_url) {
+                    Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+                    _ => errors.push(format!(
+                        "auth.service_url '{}' is not an http(s) URL",
+                        self.auth.service_url
+                    )),
+                }
+                if self.auth.timeout_ms == 0 {
+                    errors.push("auth.timeout_ms must be at least 1".to_string());
+                }
+            }
+            AuthMode::SingleUser => {
+                if matches!(backend, Ok(StorageBackend::Postgres { .. })) {
+                    errors.push(
+                        "auth.mode 'single-user' is only supported with SQLite or in-memory storage"
+                            .to_string(),
+                    );
+                }
+            }
+        }
+
+        for origin in &self.cors.allowed_origins {
+            if origin == "*" {
+                if self.cors.allow_credentials {
+                    errors.push(
+                        "cors.allow_credentials cannot be combined with allowed origin '*'"
+                            .to_string(),
+                    );
+                }
+            } else if let Err(e) = origin.parse::<OriginPattern>() {
+                errors.push(format!("cors.allowed_origins: {}", e));
+            }
+        }
+        for method in &self.cors.allowed_methods {
+            if Method::from_str(method).is_err() {
+                errors.push(format!("cors.allowed_methods: invalid method '{}'", method));
+            }
+        }
+        for header in &self.cors.allowed_headers {
+            if HeaderName::from_str(header).is_err() {
+                errors.push(format!("cors.allowed_headers: invalid header '{}'", header));
+            }
+        }
+
+        for rule in &self.rate_limit.rules {
+            let name = &rule.name;
+            if rule.requests == 0 || rule.window_secs == 0 {
+                errors.push(format!(
+                    "rate_limit rule '{}': requests and window_secs must be at least 1",
+                    name
+                ));
+            }
+            if !rule.path_prefix.starts_with('/') {
+                errors.push(format!(
+                    "rate_limit rule '{}': path_prefix must start with '/'",
+                    name
+                ));
+            }
+            for method in &rule.methods {
+                if Method::from_str(&method.to_ascii_uppercase()).is_err() {
+                    errors.push(format!("rate_limit rule '{}': invalid method '{}'", name, method));
+                }
+            }
+        }
+
+        if self.audit.retention_days < 0 {
+            errors.push("audit.retention_days must not be negative".to_string());
+        }
+        if self.trash.retention_days < 0 {
+            errors.push("trash.retention_days must not be negative".to_string());
+        }
+        if self.idempotency.ttl_hours == 0 {
+            errors.push("idempotency.ttl_hours must be at least 1".to_string());
+        }
+
+        errors
+    }
+
+    /// A copy safe to print or log: credentials in the database URL are masked.
+    pub fn redacted(&self) -> Self {
+        let mut config = self.clone();
+        config.storage.database_url = config.storage.database_url.map(|url| redact_url(&url));
+        config
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).expect("configuration is always representable as TOML")
+    }
+}
+
+fn redact_url(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(mut parsed) if parsed.password().is_some() => {
+            let _ = parsed.set_password(Some("REDACTED"));
+            parsed.to_string()
+        }
+        Ok(_) => url.to_string(),
+        // Can't tell which part is the secret, so hide all of it
+        Err(_) => "REDACTED".to_string(),
+    }
+}
+
+/// Applies environment variables on top of the file configuration, collecting
+/// parse failures instead of stopping at the first. Empty values count as unset.
+struct EnvOverlay<'a, F> {
+    lookup: F,
+    errors: &'a mut Vec<String>,
+}
+
+impl<F: Fn(&str) -> Option<String>> EnvOverlay<'_, F> {
+    fn get(&self, key: &str) -> Option<String> {
+        (self.lookup)(key).filter(|value| !value.trim().is_empty())
+    }
+
+    fn set<T>(&mut self, key: &str, target: &mut T)
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        if let Some(value) = self.get(key) {
+            match value.trim().parse() {
+                Ok(parsed) => *target = parsed,
+                Err(e) => self
+                    .errors
+                    .push(format!("{}: invalid value '{}': {}", key, value, e)),
+            }
+        }
+    }
+
+    fn set_optional<T>(&mut self, key: &str, target: &mut Option<T>)
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        if let Some(value) = self.get(key) {
+            match value.trim().parse() {
+                Ok(parsed) => *target = Some(parsed),
+                Err(e) => self
+                    .errors
+                    .push(format!("{}: invalid value '{}': {}", key, value, e)),
+            }
+        }
+    }
+
+    fn set_list(&mut self, key: &str, target: &mut Vec<String>) {
+        if let Some(value) = self.get(key) {
+            *target = value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(String::from)
+                .collect();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        move |key| vars.get(key).cloned()
+    }
+
+    #[test]
+    fn test_environment_overrides_file() {
+        let file = r#"
+            [server]
+            port = 9000
+
+            [storage]
+            database_url = "postgresql://coffee:secret@db/coffee"
+            max_connections = 5
+
+            [cors]
+            allowed_origins = ["https://coffee.example.com"]
+
+            [log]
+            format = "text"
+        "#;
+
+        let config = Config::from_sources(
+            Some(("api.toml", file)),
+            env(&[
+                ("PORT", "9100"),
+                ("ADMIN_EMAILS", "a@example.com, b@example.com"),
+                ("LOG_FORMAT", "JSON"),
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(config.server.port, 9100);
+        assert_eq!(config.log.format, LogFormat::Json);
+        assert_eq!(config.storage.max_connections, 5);
+        assert_eq!(config.auth.admin_emails, ["a@example.com", "b@example.com"]);
+        assert_eq!(config.cors.allowed_origins, ["https://coffee.example.com"]);
+        assert_eq!(config.audit.retention_days, 365);
+    }
+
+    #[test]
+    fn test_all_errors_are_reported() {
+        let error = Config::from_sources(
+            Some((
+                "api.toml",
+                "[storage]\nmax_connections = 2\nmin_connections = 4\n",
+            )),
+            env(&[
+                ("PORT", "eighty"),
+                ("MIGRATIONS_MODE", "sometimes"),
+                ("AUTH_SERVICE_URL", "auth-service"),
+            ]),
+        )
+        .unwrap_err();
+
+        let messages = error.0.join("\n");
+        assert_eq!(error.0.len(), 5, "{}", messages);
+        assert!(messages.contains("PORT: invalid value 'eighty'"));
+        assert!(messages.contains("MIGRATIONS_MODE"));
+        assert!(messages.contains("DATABASE_URL"));
+        assert!(messages.contains("min_connections"));
+        assert!(messages.contains("auth.service_url"));
+    }
+
+    #[test]
+    fn test_unknown_file_keys_are_rejected() {
+        let error = Config::from_sources(
+            Some(("api.toml", "[server]\nprot = 8080\n")),
+            env(&[("STORAGE_BACKEND", "memory")]),
+        )
+        .unwrap_err();
+
+        assert_eq!(error.0.len(), 1);
+        assert!(error.0[0].starts_with("api.toml: unknown field `prot`"));
+    }
+
+    #[test]
+    fn test_single_user_mode_requires_local_storage() {
+        let postgres = env(&[
+            ("AUTH_MODE", "single-user"),
+            ("DATABASE_URL", "postgresql://coffee@db/coffee"),
+        ]);
+        assert!(Config::from_sources(None, postgres).is_err());
+
+        let memory = env(&[("AUTH_MODE", "single-user"), ("STORAGE_BACKEND", "memory")]);
+        assert_eq!(
+            Config::from_sources(None, memory).unwrap().auth.mode,
+            AuthMode::SingleUser
+        );
+    }
+
+    #[test]
+    fn test_cors_origins_are_validated() {
+        let error = Config::from_sources(
+            None,
+            env(&[
+                ("STORAGE_BACKEND", "memory"),
+                ("CORS_ALLOWED_ORIGINS", "*, https://*.example.com, example.com"),
+                ("CORS_ALLOW_CREDENTIALS", "true"),
+            ]),
+        )
+        .unwrap_err();
+
+        assert_eq!(error.0.len(), 2, "{:?}", error.0);
+        assert!(error.0[0].contains("allow_credentials"));
+        assert!(error.0[1].contains("'example.com'"));
+    }
+
+    #[test]
+    fn test_rate_limit_rules_from_file() {
+        let file = r#"
+            [storage]
+            backend = "memory"
+
+            [[rate_limit.rules]]
+            name = "exports"
+            methods = ["GET"]
+            path_prefix = "/api/export"
+            requests = 5
+        "#;
+
+        let config =
+            Config::from_sources(Some(("test.toml", file)), env(&[("RATE_LIMIT_ENABLED", "false")]))
+                .unwrap();
+        assert!(!config.rate_limit.enabled);
+        assert_eq!(config.rate_limit.rules.len(), 1);
+        assert_eq!(config.rate_limit.rules[0].window_secs, 60);
+
+        let broken = format!(
+            "{}\n[[rate_limit.rules]]\nname = \"broken\"\nmethods = [\"FETCH IT\"]\npath_prefix = \"api\"\nrequests = 0\n",
+            file
+        );
+        let error = Config::from_sources(Some(("test.toml", &broken)), env(&[])).unwrap_err();
+        assert_eq!(error.0.len(), 3, "{:?}", error.0);
+        assert!(error.0.iter().all(|message| message.contains("'broken'")));
+    }
+
+    #[test]
+    fn test_redacted_dump_hides_database_password() {
+        let config = Config::from_sources(
+            None,
+            env(&[("DATABASE_URL", "postgresql://coffee:hunter2@db:5432/coffee")]),
+        )
+        .unwrap();
+
+        let dump = config.redacted().to_toml();
+        assert!(!dump.contains("hunter2"));
+        assert!(dump.contains("postgresql://coffee:REDACTED@db:5432/coffee"));
+
+        // The dump is itself a valid configuration file
+        let reloaded: Config = toml::from_str(&dump).unwrap();
+        assert_eq!(
+            reloaded.storage.max_connections,
+            config.storage.max_connections
+        );
+    }
+}
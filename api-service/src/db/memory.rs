@@ -0,0 +1,973 @@
+use axum::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use uuid::Uuid;
+
+use super::repository::{
+    counter_entry, ensure_unique_positions, group_by_date, join_notes, merge_conversion_factor,
+    page_changes, round_amount, Added, CategoryRepository, CounterRepository, Fingerprint,
+    IdempotencyKey, Precondition, RepositoryError, RepositoryResult, SyncRepository,
+};
+use crate::models::{
+    AddCounterRequest, Category, CategoryCounterData, CategoryPosition, CounterData,
+    CounterDataResponseV2, CreateCategoryRequest, MergeCategoryRequest, MergeCategoryResult,
+    SetCounterRequest, SyncChanges, SyncTombstone, UpdateCategoryRequest,
+};
+use crate::utils::audit::AuditContext;
+use crate::utils::auth::AuthenticatedUser;
+
+#[derive(Default)]
+struct MemoryState {
+    categories: Vec<Category>,
+    counters: Vec<CounterData>,
+    idempotency_keys: HashMap<(Uuid, &'static str, String), StoredResult>,
+    /// The last number of the change sequence.
+    change_seq: i64,
+    /// Sequence number of each category's and entry's latest change, by id.
+    changes: HashMap<Uuid, i64>,
+    tombstones: Vec<(i64, Uuid, SyncTombstone)>,
+}
+
+/// The result of a write made with an idempotency key.
+struct StoredResult {
+    request_hash: String,
+    entry: CategoryCounterData,
+    created_at: DateTime<Utc>,
+}
+
+impl MemoryState {
+    fn category(&self, user_id: Uuid, id: Uuid, archived: bool) -> RepositoryResult<&Category> {
+        self.categories
+            .iter()
+            .find(|c| c.id == id && c.user_id == user_id && c.deleted_at.is_some() == archived)
+            .ok_or_else(RepositoryError::category_not_found)
+    }
+
+    fn category_mut(
+        &mut self,
+        user_id: Uuid,
+        id: Uuid,
+        archived: bool,
+    ) -> RepositoryResult<&mut Category> {
+        self.categories
+            .iter_mut()
+            .find(|c| c.id == id && c.user_id == user_id && c.deleted_at.is_some() == archived)
+            .ok_or_else(RepositoryError::category_not_found)
+    }
+
+    /// Mirrors the unique index on live category names.
+    fn ensure_name_available(&self, user_id: Uuid, name: &str, except: Option<Uuid>) -> RepositoryResult<()> {
+        let taken = self.categories.iter().any(|c| {
+            c.user_id == user_id && c.deleted_at.is_none() && c.name == name && Some(c.id) != except
+        });
+
+        if taken {
+            Err(RepositoryError::duplicate_name())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The user's live, active category: the only kind that can be counted.
+    fn active_category(&self, user_id: Uuid, id: Uuid) -> RepositoryResult<Category> {
+        self.category(user_id, id, false)
+            .ok()
+            .filter(|c| c.is_active)
+            .cloned()
+            .ok_or_else(RepositoryError::category_not_found)
+    }
+
+    /// The result stored under `key` by an earlier write, if any. Forgets the
+    /// user's expired keys first.
+    fn replay(
+        &mut self,
+        user_id: Uuid,
+        key: &IdempotencyKey,
+    ) -> RepositoryResult<Option<CategoryCounterData>> {
+        self.idempotency_keys.retain(|(owner, _, _), stored| {
+            *owner != user_id || stored.created_at >= key.expired_before
+        });
+
+        match self.idempotency_keys.get(&(user_id, key.operation, key.key.clone())) {
+            Some(stored) if stored.request_hash != key.request_hash => {
+                Err(RepositoryError::IdempotencyKeyReused)
+            }
+            Some(stored) => Ok(Some(stored.entry.clone())),
+            None => Ok(None),
+        }
+    }
+
+    fn remember(&mut self, user_id: Uuid, key: &IdempotencyKey, entry: &CategoryCounterData) {
+        self.idempotency_keys.insert(
+            (user_id, key.operation, key.key.clone()),
+            StoredResult {
+                request_hash: key.request_hash.clone(),
+                entry: entry.clone(),
+                created_at: Utc::now(),
+            },
+        );
+    }
+
+    /// Records a change to the category or entry `id`.
+    fn stamp(&mut self, id: Uuid) {
+        self.change_seq += 1;
+        self.changes.insert(id, self.change_seq);
+    }
+
+    /// Records that a counter entry was deleted.
+    fn bury(&mut self, row: &CounterData) {
+        self.change_seq += 1;
+        self.changes.remove(&row.id);
+        self.tombstones.push((
+            self.change_seq,
+            row.user_id,
+            SyncTombstone {
+                entity_type: "counter_data".to_string(),
+                entity_id: row.id,
+                category_id: Some(row.category_id),
+                date: Some(row.date),
+                deleted_at: Utc::now(),
+            },
+        ));
+    }
+
+    fn live_categories(&self, user_id: Uuid) -> Vec<&Category> {
+        let mut categories: Vec<&Category> = self
+            .categories
+            .iter()
+            .filter(|c| c.user_id == user_id && c.deleted_at.is_none())
+            .collect();
+        categories.sort_by_key(|c| (c.sort_order, c.created_at));
+        categories
+    }
+}
+
+/// Process-local storage for tests and the database-free demo mode. Data is
+/// lost on restart and changes are not written to the audit log.
+#[derive(Clone, Default)]
+pub struct MemoryRepository {
+    state: Arc<RwLock<MemoryState>>,
+}
+
+impl MemoryRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, MemoryState> {
+        self.state.read().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, MemoryState> {
+        self.state.write().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[async_trait]
+impl CategoryRepository for MemoryRepository {
+    async fn list(&self, user_id: Uuid, active_only: bool) -> RepositoryResult<Vec<Category>> {
+        Ok(self
+            .read()
+            .live_categories(user_id)
+            .into_iter()
+            .filter(|c| c.is_active || !active_only)
+            .cloned()
+            .collect())
+    }
+
+    async fn get(&self, user_id: Uuid, id: Uuid) -> RepositoryResult<Category> {
+        self.read().category(user_id, id, false).cloned()
+    }
+
+    async fn create(
+        &self,
+        user: &AuthenticatedUser,
+        _context: &AuditContext,
+        request: &CreateCategoryRequest,
+    ) -> RepositoryResult<Category> {
+        let mut state = self.write();
+        state.ensure_name_available(user.user_id, &request.name, None)?;
+
+        let sort_order = state
+            .categories
+            .iter()
+            .filter(|c| c.user_id == user.user_id)
+            .map(|c| c.sort_order + 1)
+            .max()
+            .unwrap_or(0);
+        let now = Utc::now();
+
+        let category = Category {
+            id: Uuid::new_v4(),
+            user_id: user.user_id,
+            name: request.name.clone(),
+            icon: request.icon.clone(),
+            color: request.color.clone(),
+            unit: request.unit.clone(),
+            default_amount: request.default_amount,
+            is_active: true,
+            sort_order,
+            group_name: request.group_name.clone().filter(|group| !group.is_empty()),
+            created_at: now,
+            updated_at: now,
+            deleted_at: None,
+        };
+        state.categories.push(category.clone());
+        state.stamp(category.id);
+
+        Ok(category)
+    }
+
+    async fn update(
+        &self,
+        user: &AuthenticatedUser,
+        _context: &AuditContext,
+        id: Uuid,
+        request: &UpdateCategoryRequest,
+        precondition: &Precondition,
+    ) -> RepositoryResult<Category> {
+        let mut state = self.write();
+        precondition.check(state.category(user.user_id, id, false)?)?;
+        if let Some(name) = &request.name {
+            state.ensure_name_available(user.user_id, name, Some(id))?;
+        }
+
+        let category = state.category_mut(user.user_id, id, false)?;
+        if let Some(name) = &request.name {
+            category.name = name.clone();
+        }
+        if let Some(icon) = &request.icon {
+            category.icon = icon.clone();
+        }
+        if let Some(color) = &request.color {
+            category.color = color.clone();
+        }
+        if let Some(unit) = &request.unit {
+            category.unit = unit.clone();
+        }
+        if let Some(default_amount) = request.default_amount {
+            category.default_amount = default_amount;
+        }
+        if let Some(is_active) = request.is_active {
+            category.is_active = is_active;
+        }
+        if let Some(group_name) = &request.group_name {
+            category.group_name = Some(group_name.clone()).filter(|group| !group.is_empty());
+        }
+        category.updated_at = Utc::now();
+        let category = category.clone();
+        state.stamp(id);
+
+        Ok(category)
+    }
+
+    async fn archive(
+        &self,
+        user: &AuthenticatedUser,
+        _context: &AuditContext,
+        id: Uuid,
+        precondition: &Precondition,
+    ) -> RepositoryResult<Category> {
+        let mut state = self.write();
+        let category = state.category_mut(user.user_id, id, false)?;
+        precondition.check(category)?;
+        category.deleted_at = Some(Utc::now());
+        category.updated_at = Utc::now();
+        let category = category.clone();
+        state.stamp(id);
+
+        Ok(category)
+    }
+
+    async fn reorder(
+        &self,
+        user: &AuthenticatedUser,
+        _context: &AuditContext,
+        positions: &[CategoryPosition],
+    ) -> RepositoryResult<Vec<Category>> {
+        ensure_unique_positions(positions)?;
+
+        let mut state = self.write();
+        let live: Vec<Uuid> = state
+            .live_categories(user.user_id)
+            .into_iter()
+            .map(|c| c.id)
+            .collect();
+
+        if let Some(unknown) = positions.iter().find(|item| !live.contains(&item.id)) {
+            return Err(RepositoryError::NotFound(format!(
+                "Category {} not found",
+                unknown.id
+            )));
+        }
+
+        let remaining = live
+            .iter()
+            .filter(|id| !positions.iter().any(|item| item.id == **id));
+        let layout: Vec<(Uuid, Option<Option<String>>)> = positions
+            .iter()
+            .map(|item| (item.id, Some(item.group_name.clone())))
+            .chain(remaining.map(|id| (*id, None)))
+            .collect();
+
+        let now = Utc::now();
+        for (position, (id, group_name)) in layout.iter().enumerate() {
+            let category = state.category_mut(user.user_id, *id, false)?;
+            category.sort_order = position as i32;
+            if let Some(group_name) = group_name {
+                category.group_name = group_name.clone();
+            }
+            category.updated_at = now;
+            state.stamp(*id);
+        }
+
+        Ok(state
+            .live_categories(user.user_id)
+            .into_iter()
+            .cloned()
+            .collect())
+    }
+
+    async fn list_trash(&self, user_id: Uuid) -> RepositoryResult<Vec<Category>> {
+        let mut categories: Vec<Category> = self
+            .read()
+            .categories
+            .iter()
+            .filter(|c| c.user_id == user_id && c.deleted_at.is_some())
+            .cloned()
+            .collect();
+        categories.sort_by_key(|c| std::cmp::Reverse(c.deleted_at));
+
+        Ok(categories)
+    }
+
+    async fn restore(
+        &self,
+        user: &AuthenticatedUser,
+        _context: &AuditContext,
+        id: Uuid,
+    ) -> RepositoryResult<Category> {
+        let mut state = self.write();
+        let name = state.category(user.user_id, id, true)?.name.clone();
+        state.ensure_name_available(user.user_id, &name, Some(id))?;
+
+        let category = state.category_mut(user.user_id, id, true)?;
+        category.deleted_at = None;
+        category.updated_at = Utc::now();
+        let category = category.clone();
+        state.stamp(id);
+
+        Ok(category)
+    }
+
+    async fn merge(
+        &self,
+        user: &AuthenticatedUser,
+        _context: &AuditContext,
+        source_id: Uuid,
+        request: &MergeCategoryRequest,
+    ) -> RepositoryResult<MergeCategoryResult> {
+        if request.target_id == source_id {
+            return Err(RepositoryError::InvalidInput(
+                "Cannot merge a category into itself".to_string(),
+            ));
+        }
+
+        let mut state = self.write();
+        let source = state.category(user.user_id, source_id, false)?.clone();
+        let target = state.category(user.user_id, request.target_id, false)?.clone();
+        let conversion_factor = merge_conversion_factor(&source, &target, request.conversion_factor)?;
+
+        let now = Utc::now();
+        let mut moved_days = 0;
+        let mut combined_days = 0;
+        let (from_source, mut kept): (Vec<CounterData>, Vec<CounterData>) =
+            std::mem::take(&mut state.counters)
+                .into_iter()
+                .partition(|row| row.user_id == user.user_id && row.category_id == source.id);
+
+        for mut row in from_source {
+            let amounts = row
+                .amounts
+                .iter()
+                .map(|amount| round_amount(amount * conversion_factor));
+
+            match kept
+                .iter_mut()
+                .find(|t| t.user_id == user.user_id && t.category_id == target.id && t.date == row.date)
+            {
+                Some(existing) => {
+                    existing.count += row.count;
+                    existing.amounts.extend(amounts);
+                    existing.notes = join_notes(existing.notes.take(), row.notes.clone());
+                    existing.updated_at = now;
+                    state.stamp(existing.id);
+                    state.bury(&row);
+                    combined_days += 1;
+                }
+                None => {
+                    row.amounts = amounts.collect();
+                    row.category_id = target.id;
+                    row.updated_at = now;
+                    state.stamp(row.id);
+                    kept.push(row);
+                    moved_days += 1;
+                }
+            }
+        }
+        state.counters = kept;
+
+        let archived = state.category_mut(user.user_id, source.id, false)?;
+        archived.deleted_at = Some(now);
+        archived.updated_at = now;
+        let archived = archived.clone();
+        state.stamp(archived.id);
+
+        Ok(MergeCategoryResult {
+            source: archived,
+            target,
+            conversion_factor,
+            moved_days,
+            combined_days,
+        })
+    }
+
+    async fn fingerprint(&self, user_id: Uuid) -> RepositoryResult<Fingerprint> {
+        let state = self.read();
+        let categories = state.categories.iter().filter(|c| c.user_id == user_id);
+
+        Ok(categories.fold(Fingerprint::default(), |fingerprint, category| {
+            fingerprint.combine(Fingerprint {
+                rows: 1,
+                last_updated: Some(category.updated_at),
+                counts: 0,
+            })
+        }))
+    }
+}
+
+#[async_trait]
+impl CounterRepository for MemoryRepository {
+    async fn day(&self, user_id: Uuid, date: NaiveDate) -> RepositoryResult<CounterDataResponseV2> {
+        let state = self.read();
+
+        let categories = state
+            .live_categories(user_id)
+            .into_iter()
+            .filter(|c| c.is_active)
+            .map(|category| {
+                match state
+                    .counters
+                    .iter()
+                    .find(|row| row.user_id == user_id && row.category_id == category.id && row.date == date)
+                {
+                    Some(row) => counter_entry(
+                        category,
+                        row.count,
+                        row.amounts.clone(),
+                        Some(row.updated_at),
+                    ),
+                    None => counter_entry(category, 0, Vec::new(), None),
+                }
+            })
+            .collect();
+
+        Ok(CounterDataResponseV2 { date, categories })
+    }
+
+    async fn range(
+        &self,
+        user_id: Uuid,
+        start: NaiveDate,
+        end: NaiveDate,
+        include_archived: bool,
+    ) -> RepositoryResult<Vec<CounterDataResponseV2>> {
+        let state = self.read();
+
+        let mut rows: Vec<(&CounterData, &Category)> = state
+            .counters
+            .iter()
+            .filter(|row| row.user_id == user_id && row.date >= start && row.date <= end)
+            .filter_map(|row| {
+                state
+                    .categories
+                    .iter()
+                    .find(|c| c.id == row.category_id)
+                    .filter(|c| c.is_active && (c.deleted_at.is_none() || include_archived))
+                    .map(|category| (row, category))
+            })
+            .collect();
+        rows.sort_by_key(|(row, category)| (row.date, category.sort_order, category.created_at));
+
+        Ok(group_by_date(rows.into_iter().map(|(row, category)| {
+            (
+                row.date,
+                counter_entry(category, row.count, row.amounts.clone(), Some(row.updated_at)),
+            )
+        })))
+    }
+
+    async fn add(
+        &self,
+        user: &AuthenticatedUser,
+        _context: &AuditContext,
+        date: NaiveDate,
+        request: &AddCounterRequest,
+        idempotency: Option<&IdempotencyKey>,
+    ) -> RepositoryResult<Added> {
+        let mut state = self.write();
+
+        if let Some(key) = idempotency {
+            if let Some(entry) = state.replay(user.user_id, key)? {
+                return Ok(Added::replayed(entry));
+            }
+        }
+        let category = state.active_category(user.user_id, request.category_id)?;
+
+        let amount = round_amount(request.amount);
+        let now = Utc::now();
+
+        let existing = state.counters.iter_mut().find(|row| {
+            row.user_id == user.user_id && row.category_id == category.id && row.date == date
+        });
+        let row = match existing {
+            Some(row) => {
+                row.count += 1;
+                row.amounts.push(amount);
+                row.notes = join_notes(row.notes.take(), request.notes.clone());
+                row.updated_at = now;
+                row.clone()
+            }
+            None => {
+                let row = CounterData {
+                    id: Uuid::new_v4(),
+                    user_id: user.user_id,
+                    category_id: category.id,
+                    date,
+                    count: 1,
+                    amounts: vec![amount],
+                    notes: request.notes.clone(),
+                    created_at: now,
+                    updated_at: now,
+                };
+                state.counters.push(row.clone());
+                row
+            }
+        };
+
+        state.stamp(row.id);
+
+        let entry = counter_entry(&category, row.count, row.amounts, Some(row.updated_at));
+        if let Some(key) = idempotency {
+            state.remember(user.user_id, key, &entry);
+        }
+
+        Ok(Added::new(entry))
+    }
+
+    async fn set(
+        &self,
+        user: &AuthenticatedUser,
+        _context: &AuditContext,
+        date: NaiveDate,
+        request: &SetCounterRequest,
+        precondition: &Precondition,
+        idempotency: Option<&IdempotencyKey>,
+    ) -> RepositoryResult<Added> {
+        let mut state = self.write();
+
+        if let Some(key) = idempotency {
+            if let Some(entry) = state.replay(user.user_id, key)? {
+                return Ok(Added::replayed(entry));
+            }
+        }
+        let category = state.active_category(user.user_id, request.category_id)?;
+
+        let position = state.counters.iter().position(|row| {
+            row.user_id == user.user_id && row.category_id == category.id && row.date == date
+        });
+        let current = match position {
+            Some(i) => {
+                let row = &state.counters[i];
+                counter_entry(&category, row.count, row.amounts.clone(), Some(row.updated_at))
+            }
+            None => counter_entry(&category, 0, Vec::new(), None),
+        };
+        precondition.check_entry(&current)?;
+
+        let amounts: Vec<f64> = request.amounts.iter().copied().map(round_amount).collect();
+        let now = Utc::now();
+
+        let entry = match position {
+            _ if amounts.is_empty() => {
+                if let Some(i) = position {
+                    let row = state.counters.remove(i);
+                    state.bury(&row);
+                }
+                counter_entry(&category, 0, Vec::new(), None)
+            }
+            Some(i) => {
+                let row = &mut state.counters[i];
+                row.count = amounts.len() as i32;
+                row.amounts = amounts.clone();
+                if request.notes.is_some() {
+                    row.notes = request.notes.clone();
+                }
+                row.updated_at = now;
+                let (id, count) = (row.id, row.count);
+                state.stamp(id);
+                counter_entry(&category, count, amounts, Some(now))
+            }
+            None => {
+                let id = Uuid::new_v4();
+                state.stamp(id);
+                state.counters.push(CounterData {
+                    id,
+                    user_id: user.user_id,
+                    category_id: category.id,
+                    date,
+                    count: amounts.len() as i32,
+                    amounts: amounts.clone(),
+                    notes: request.notes.clone(),
+                    created_at: now,
+                    updated_at: now,
+                });
+                counter_entry(&category, amounts.len() as i32, amounts, Some(now))
+            }
+        };
+        if let Some(key) = idempotency {
+            state.remember(user.user_id, key, &entry);
+        }
+
+        Ok(Added::new(entry))
+    }
+
+    async fn fingerprint_range(
+        &self,
+        user_id: Uuid,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> RepositoryResult<Fingerprint> {
+        let categories = self.fingerprint(user_id).await?;
+        let state = self.read();
+        let rows = state
+            .counters
+            .iter()
+            .filter(|row| row.user_id == user_id && row.date >= start && row.date <= end);
+
+        Ok(rows.fold(categories, |fingerprint, row| {
+            fingerprint.combine(Fingerprint {
+                rows: 1,
+                last_updated: Some(row.updated_at),
+                counts: row.count.into(),
+            })
+        }))
+    }
+}
+
+#[async_trait]
+impl SyncRepository for MemoryRepository {
+    async fn changes(&self, user_id: Uuid, since: i64, limit: usize) -> RepositoryResult<SyncChanges> {
+        let state = self.read();
+        let changed = |id: &Uuid| state.changes.get(id).copied().filter(|seq| *seq > since);
+
+        fn oldest_first<T>(mut rows: Vec<(i64, T)>, limit: usize) -> Vec<(i64, T)> {
+            rows.sort_by_key(|(seq, _)| *seq);
+            rows.truncate(limit + 1);
+            rows
+        }
+
+        let categories = state
+            .categories
+            .iter()
+            .filter(|c| c.user_id == user_id)
+            .filter_map(|c| Some((changed(&c.id)?, c.clone())))
+            .collect();
+        let counters = state
+            .counters
+            .iter()
+            .filter(|row| row.user_id == user_id)
+            .filter_map(|row| Some((changed(&row.id)?, row.clone())))
+            .collect();
+        let deleted = state
+            .tombstones
+            .iter()
+            .filter(|(seq, owner, _)| *owner == user_id && *seq > since)
+            .map(|(seq, _, tombstone)| (*seq, tombstone.clone()))
+            .collect();
+
+        Ok(page_changes(
+            since,
+            limit,
+            oldest_first(categories, limit),
+            oldest_first(counters, limit),
+            oldest_first(deleted, limit),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::auth::UserInfo;
+
+    fn user() -> AuthenticatedUser {
+        let user_id = Uuid::new_v4();
+        AuthenticatedUser {
+            user_id,
+            user: UserInfo {
+                id: user_id,
+                email: "test@example.com".to_string(),
+                display_name: None,
+                is_active: true,
+            },
+        }
+    }
+
+    fn context() -> AuditContext {
+        AuditContext {
+            request_id: Uuid::new_v4().to_string(),
+            session_id: None,
+        }
+    }
+
+    fn create_request(name: &str, unit: &str) -> CreateCategoryRequest {
+        CreateCategoryRequest {
+            name: name.to_string(),
+            icon: "coffee".to_string(),
+            color: "#8B4513".to_string(),
+            unit: unit.to_string(),
+            default_amount: 250.0,
+            group_name: None,
+        }
+    }
+
+    fn add_request(category_id: Uuid, amount: f64) -> AddCounterRequest {
+        AddCounterRequest {
+            category_id,
+            amount,
+            notes: None,
+        }
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 1, day).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_categories_are_scoped_to_their_owner() {
+        let repo = MemoryRepository::new();
+        let (alice, bob) = (user(), user());
+
+        let coffee = repo.create(&alice, &context(), &create_request("Coffee", "ml")).await.unwrap();
+        repo.create(&bob, &context(), &create_request("Coffee", "ml")).await.unwrap();
+
+        assert!(matches!(
+            repo.get(bob.user_id, coffee.id).await,
+            Err(RepositoryError::NotFound(_))
+        ));
+        assert!(matches!(
+            repo.create(&alice, &context(), &create_request("Coffee", "ml")).await,
+            Err(RepositoryError::Conflict(_))
+        ));
+        assert!(matches!(
+            repo.add(&bob, &context(), date(1), &add_request(coffee.id, 100.0), None).await,
+            Err(RepositoryError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_add_upserts_the_days_entry() {
+        let repo = MemoryRepository::new();
+        let alice = user();
+        let coffee = repo.create(&alice, &context(), &create_request("Coffee", "ml")).await.unwrap();
+        let water = repo.create(&alice, &context(), &create_request("Water", "ml")).await.unwrap();
+
+        repo.add(&alice, &context(), date(1), &add_request(coffee.id, 250.0), None).await.unwrap();
+        let entry = repo
+            .add(&alice, &context(), date(1), &add_request(coffee.id, 100.0), None)
+            .await
+            .unwrap()
+            .entry;
+        assert_eq!(entry.count, 2);
+        assert_eq!(entry.total_amount, 350.0);
+
+        let day = repo.day(alice.user_id, date(1)).await.unwrap();
+        assert_eq!(day.categories.len(), 2);
+        assert_eq!(day.categories[1].category_id, water.id);
+        assert_eq!(day.categories[1].count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_merge_combines_and_moves_days() {
+        let repo = MemoryRepository::new();
+        let alice = user();
+        let litres = repo.create(&alice, &context(), &create_request("Big coffee", "l")).await.unwrap();
+        let millilitres = repo.create(&alice, &context(), &create_request("Coffee", "ml")).await.unwrap();
+
+        repo.add(&alice, &context(), date(1), &add_request(litres.id, 0.5), None).await.unwrap();
+        repo.add(&alice, &context(), date(2), &add_request(litres.id, 0.25), None).await.unwrap();
+        repo.add(&alice, &context(), date(1), &add_request(millilitres.id, 100.0), None).await.unwrap();
+
+        let request = MergeCategoryRequest {
+            target_id: millilitres.id,
+            conversion_factor: None,
+        };
+        let result = repo.merge(&alice, &context(), litres.id, &request).await.unwrap();
+        assert_eq!((result.combined_days, result.moved_days), (1, 1));
+        assert!(result.source.deleted_at.is_some());
+
+        let days = repo.range(alice.user_id, date(1), date(31), false).await.unwrap();
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].categories[0].amounts, vec![100.0, 500.0]);
+        assert_eq!(days[1].categories[0].amounts, vec![250.0]);
+    }
+
+    #[tokio::test]
+    async fn test_trash_hides_history_until_restored() {
+        let repo = MemoryRepository::new();
+        let alice = user();
+        let coffee = repo.create(&alice, &context(), &create_request("Coffee", "ml")).await.unwrap();
+        repo.add(&alice, &context(), date(1), &add_request(coffee.id, 250.0), None).await.unwrap();
+
+        repo.archive(&alice, &context(), coffee.id, &Precondition::Any).await.unwrap();
+        assert!(repo.range(alice.user_id, date(1), date(1), false).await.unwrap().is_empty());
+        assert_eq!(repo.range(alice.user_id, date(1), date(1), true).await.unwrap().len(), 1);
+
+        repo.create(&alice, &context(), &create_request("Coffee", "ml")).await.unwrap();
+        assert!(matches!(
+            repo.restore(&alice, &context(), coffee.id).await,
+            Err(RepositoryError::Conflict(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_idempotent_add_replays_the_first_result() {
+        let repo = MemoryRepository::new();
+        let alice = user();
+        let coffee = repo.create(&alice, &context(), &create_request("Coffee", "ml")).await.unwrap();
+        let key = |request_hash: &str, expired_before| IdempotencyKey {
+            operation: "counters.add",
+            key: "tap-1".to_string(),
+            request_hash: request_hash.to_string(),
+            expired_before,
+        };
+        let fresh = Utc::now() - chrono::Duration::hours(24);
+
+        let first = repo
+            .add(&alice, &context(), date(1), &add_request(coffee.id, 250.0), Some(&key("a", fresh)))
+            .await
+            .unwrap();
+        let retry = repo
+            .add(&alice, &context(), date(1), &add_request(coffee.id, 250.0), Some(&key("a", fresh)))
+            .await
+            .unwrap();
+        assert!(!first.replayed && retry.replayed);
+        assert_eq!(retry.entry.count, 1);
+        assert_eq!(repo.day(alice.user_id, date(1)).await.unwrap().categories[0].count, 1);
+
+        assert!(matches!(
+            repo.add(&alice, &context(), date(1), &add_request(coffee.id, 100.0), Some(&key("b", fresh)))
+                .await,
+            Err(RepositoryError::IdempotencyKeyReused)
+        ));
+
+        // Keys are per user, and forgotten once expired
+        let bob = user();
+        let tea = repo.create(&bob, &context(), &create_request("Tea", "ml")).await.unwrap();
+        let bobs = repo
+            .add(&bob, &context(), date(1), &add_request(tea.id, 250.0), Some(&key("a", fresh)))
+            .await
+            .unwrap();
+        assert!(!bobs.replayed);
+        assert_eq!(bobs.entry.category_id, tea.id);
+        let expired = repo
+            .add(&alice, &context(), date(1), &add_request(coffee.id, 250.0), Some(&key("a", Utc::now())))
+            .await
+            .unwrap();
+        assert!(!expired.replayed);
+        assert_eq!(expired.entry.count, 2);
+    }
+
+    #[tokio::test]
+    async fn test_set_replaces_or_deletes_the_days_entry() {
+        let repo = MemoryRepository::new();
+        let alice = user();
+        let coffee = repo.create(&alice, &context(), &create_request("Coffee", "ml")).await.unwrap();
+        let set_request = |amounts: Vec<f64>| SetCounterRequest {
+            category_id: coffee.id,
+            amounts,
+            notes: None,
+        };
+
+        let added = repo
+            .add(&alice, &context(), date(1), &add_request(coffee.id, 250.0), None)
+            .await
+            .unwrap()
+            .entry;
+        let read = Precondition::UpdatedAt(vec![added.updated_at.unwrap()]);
+
+        let edited = repo
+            .set(&alice, &context(), date(1), &set_request(vec![100.0, 150.0]), &read, None)
+            .await
+            .unwrap()
+            .entry;
+        assert_eq!((edited.count, edited.total_amount), (2, 250.0));
+
+        // The version the client read is gone now
+        match repo.set(&alice, &context(), date(1), &set_request(Vec::new()), &read, None).await {
+            Err(RepositoryError::EntryChanged(current)) => assert_eq!(current.count, 2),
+            other => panic!("expected a changed entry, got {:?}", other),
+        }
+
+        let deleted = repo
+            .set(&alice, &context(), date(1), &set_request(Vec::new()), &Precondition::Any, None)
+            .await
+            .unwrap()
+            .entry;
+        assert_eq!((deleted.count, deleted.updated_at), (0, None));
+        assert!(repo.range(alice.user_id, date(1), date(1), false).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_changes_follow_the_cursor() {
+        let repo = MemoryRepository::new();
+        let alice = user();
+        let coffee = repo.create(&alice, &context(), &create_request("Coffee", "ml")).await.unwrap();
+        repo.add(&alice, &context(), date(1), &add_request(coffee.id, 250.0), None)
+            .await
+            .unwrap();
+        repo.create(&user(), &context(), &create_request("Tea", "ml")).await.unwrap();
+
+        let all = repo.changes(alice.user_id, 0, 10).await.unwrap();
+        assert_eq!((all.categories.len(), all.counters.len()), (1, 1));
+        assert!(!all.has_more);
+
+        let first = repo.changes(alice.user_id, 0, 1).await.unwrap();
+        assert_eq!(first.categories[0].id, coffee.id);
+        assert!(first.counters.is_empty() && first.has_more);
+
+        // Deleting the entry leaves a tombstone; archiving is an update
+        let set_request = SetCounterRequest {
+            category_id: coffee.id,
+            amounts: Vec::new(),
+            notes: None,
+        };
+        repo.set(&alice, &context(), date(1), &set_request, &Precondition::Any, None)
+            .await
+            .unwrap();
+        repo.archive(&alice, &context(), coffee.id, &Precondition::Any).await.unwrap();
+
+        let later = repo.changes(alice.user_id, all.cursor, 10).await.unwrap();
+        assert!(later.counters.is_empty());
+        assert_eq!(later.deleted.len(), 1);
+        assert_eq!(later.deleted[0].entity_type, "counter_data");
+        assert_eq!(later.deleted[0].date, Some(date(1)));
+        assert!(later.categories[0].deleted_at.is_some());
+
+        let idle = repo.changes(alice.user_id, later.cursor, 10).await.unwrap();
+        assert_eq!(idle.cursor, later.cursor);
+        assert!(idle.categories.is_empty() && idle.deleted.is_empty());
+    }
+}
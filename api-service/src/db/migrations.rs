@@ -0,0 +1,202 @@
+use serde::{Deserialize, Serialize};
+use sqlx::migrate::{AppliedMigration, Migrate, MigrateError, Migrator};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Schema migrations from `migrations/`, embedded at compile time.
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// What to do about the database schema before serving requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MigrationMode {
+    /// Apply any pending migrations.
+    #[default]
+    Run,
+    /// Only verify that the schema matches this build; never modify it.
+    Check,
+}
+
+impl FromStr for MigrationMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "run" => Ok(MigrationMode::Run),
+            "check" => Ok(MigrationMode::Check),
+            other => Err(format!(
+                "invalid migration mode '{}', expected 'run' or 'check'",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SchemaError {
+    #[error("migration failed: {0}")]
+    Migrate(#[from] MigrateError),
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+    #[error("schema does not match this build: {}", .0.join("; "))]
+    Mismatch(Vec<String>),
+}
+
+/// Brings the schema up to date, or only verifies it in check mode.
+pub async fn prepare_schema(pool: &PgPool, mode: MigrationMode) -> Result<(), SchemaError> {
+    match mode {
+        MigrationMode::Run => {
+            MIGRATOR.run(pool).await?;
+            Ok(())
+        }
+        MigrationMode::Check => check_schema(pool).await,
+    }
+}
+
+/// Compares applied migrations against the embedded ones without creating or
+/// changing anything in the database.
+pub async fn check_schema(pool: &PgPool) -> Result<(), SchemaError> {
+    let mut conn = pool.acquire().await?;
+
+    let has_table: bool = sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+        .fetch_one(&mut conn)
+        .await?;
+
+    verify_applied(&mut *conn, has_table, &MIGRATOR).await
+}
+
+/// Shared by every backend once it knows whether sqlx's bookkeeping table
+/// exists; asking `Migrate` directly would create it.
+pub(crate) async fn verify_applied<C>(
+    conn: &mut C,
+    has_table: bool,
+    migrator: &Migrator,
+) -> Result<(), SchemaError>
+where
+    C: Migrate + ?Sized,
+{
+    let applied = if has_table {
+        if let Some(version) = conn.dirty_version().await? {
+            return Err(SchemaError::Mismatch(vec![format!(
+                "migration {} was left partially applied",
+                version
+            )]));
+        }
+        conn.list_applied_migrations().await?
+    } else {
+        Vec::new()
+    };
+
+    let problems = schema_mismatches(migrator, &applied);
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(SchemaError::Mismatch(problems))
+    }
+}
+
+/// Describes every difference between the embedded and applied migrations.
+pub fn schema_mismatches(migrator: &Migrator, applied: &[AppliedMigration]) -> Vec<String> {
+    let applied_by_version: HashMap<i64, &AppliedMigration> =
+        applied.iter().map(|m| (m.version, m)).collect();
+
+    let mut problems = Vec::new();
+
+    for migration in migrator.iter() {
+        if migration.migration_type.is_down_migration() {
+            continue;
+        }
+
+        match applied_by_version.get(&migration.version) {
+            None => problems.push(format!(
+                "migration {} ({}) is not applied",
+                migration.version, migration.description
+            )),
+            Some(applied) if applied.checksum != migration.checksum => problems.push(format!(
+                "migration {} ({}) was modified after being applied",
+                migration.version, migration.description
+            )),
+            Some(_) => {}
+        }
+    }
+
+    for applied in applied {
+        if !migrator.iter().any(|m| m.version == applied.version) {
+            problems.push(format!(
+                "migration {} is applied but unknown to this build",
+                applied.version
+            ));
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+
+    fn applied(version: i64) -> AppliedMigration {
+        let migration = MIGRATOR
+            .iter()
+            .find(|m| m.version == version)
+            .expect("embedded migration");
+
+        AppliedMigration {
+            version,
+            checksum: migration.checksum.clone(),
+        }
+    }
+
+    fn all_applied() -> Vec<AppliedMigration> {
+        MIGRATOR.iter().map(|m| applied(m.version)).collect()
+    }
+
+    #[test]
+    fn test_migrations_are_embedded_in_order() {
+        let versions: Vec<i64> = MIGRATOR.iter().map(|m| m.version).collect();
+
+        assert!(!versions.is_empty());
+        assert!(versions.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn test_fully_migrated_schema_matches() {
+        assert!(schema_mismatches(&MIGRATOR, &all_applied()).is_empty());
+    }
+
+    #[test]
+    fn test_detects_pending_migration() {
+        let mut migrations = all_applied();
+        let pending = migrations.pop().unwrap();
+
+        let problems = schema_mismatches(&MIGRATOR, &migrations);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains(&pending.version.to_string()));
+        assert!(problems[0].contains("not applied"));
+    }
+
+    #[test]
+    fn test_detects_modified_and_unknown_migrations() {
+        let mut migrations = all_applied();
+        migrations[0].checksum = Cow::Owned(vec![0; 48]);
+        migrations.push(AppliedMigration {
+            version: 99_999,
+            checksum: Cow::Owned(vec![0; 48]),
+        });
+
+        let problems = schema_mismatches(&MIGRATOR, &migrations);
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].contains("modified"));
+        assert!(problems[1].contains("unknown to this build"));
+    }
+
+    #[test]
+    fn test_migration_mode_parsing() {
+        assert_eq!("run".parse(), Ok(MigrationMode::Run));
+        assert_eq!(" CHECK ".parse(), Ok(MigrationMode::Check));
+        assert!("skip".parse::<MigrationMode>().is_err());
+    }
+}
@@ -1 +1,75 @@
-// Database utilities and migrations will go here
+pub mod memory;
+pub mod migrations;
+pub mod postgres;
+pub mod repository;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+pub use memory::MemoryRepository;
+pub use migrations::{prepare_schema, MigrationMode, MIGRATOR};
+pub use postgres::PostgresRepository;
+pub use repository::{
+    Added, AuditFilter, AuditRepository, CategoryRepository, CounterRepository,
+    DynAuditRepository, DynCategoryRepository, DynCounterRepository, DynSyncRepository,
+    DynTrashRepository, Fingerprint, IdempotencyKey, Precondition, RepositoryError,
+    RepositoryResult, SyncRepository, TrashRepository,
+};
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteRepository;
+
+/// Where categories and counters are stored, chosen at startup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageBackend {
+    Postgres { url: String },
+    /// Single-file storage, available when built with `--features sqlite`.
+    #[cfg(feature = "sqlite")]
+    Sqlite { url: String },
+    /// Process-local storage for demos; nothing survives a restart.
+    Memory,
+}
+
+impl StorageBackend {
+    /// The URL's scheme picks the database.
+    pub fn from_database_url(url: &str) -> Result<Self, String> {
+        let scheme = url.split(':').next().unwrap_or_default().to_ascii_lowercase();
+
+        match scheme.as_str() {
+            "postgres" | "postgresql" => Ok(StorageBackend::Postgres {
+                url: url.to_string(),
+            }),
+            #[cfg(feature = "sqlite")]
+            "sqlite" => Ok(StorageBackend::Sqlite {
+                url: url.to_string(),
+            }),
+            #[cfg(not(feature = "sqlite"))]
+            "sqlite" => Err("SQLite support is not compiled in; rebuild with `--features sqlite`".to_string()),
+            _ => Err(format!("unsupported DATABASE_URL scheme '{}'", scheme)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_database_url_scheme_selects_backend() {
+        assert!(matches!(
+            StorageBackend::from_database_url("postgresql://coffee@localhost/coffee"),
+            Ok(StorageBackend::Postgres { .. })
+        ));
+        assert!(matches!(
+            StorageBackend::from_database_url("postgres://coffee@localhost/coffee"),
+            Ok(StorageBackend::Postgres { .. })
+        ));
+        assert!(StorageBackend::from_database_url("mysql://localhost/coffee").is_err());
+
+        #[cfg(feature = "sqlite")]
+        assert!(matches!(
+            StorageBackend::from_database_url("sqlite://coffee.db"),
+            Ok(StorageBackend::Sqlite { .. })
+        ));
+        #[cfg(not(feature = "sqlite"))]
+        assert!(StorageBackend::from_database_url("sqlite://coffee.db").is_err());
+    }
+}
@@ -0,0 +1,1079 @@
+use axum::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde_json::Value;
+use sqlx::types::Json;
+use sqlx::{FromRow, PgPool, Postgres, Transaction};
+use tracing::Instrument;
+use uuid::Uuid;
+
+use super::repository::{
+    counter_entry, ensure_unique_positions, group_by_date, merge_conversion_factor,
+    page_changes, Added, AuditFilter, AuditRepository, CategoryRepository, CounterRepository,
+    Fingerprint, IdempotencyKey, Precondition, RepositoryError, RepositoryResult, SyncRepository,
+    TrashRepository,
+};
+use crate::models::{
+    AddCounterRequest, AuditLogEntry, Category, CategoryCounterData, CategoryPosition, CounterData,
+    CounterDataResponseV2, CreateCategoryRequest, MergeCategoryRequest, MergeCategoryResult,
+    SetCounterRequest, SyncChanges, SyncTombstone, UpdateCategoryRequest,
+};
+use crate::utils::audit::{self, AuditContext, AuditEvent};
+use crate::utils::auth::AuthenticatedUser;
+use crate::utils::telemetry::query_span;
+use crate::utils::trash;
+
+const CATEGORY_COLUMNS: &str = "id, user_id, name, icon, color, unit, \
+    default_amount::float8 AS default_amount, is_active, sort_order, group_name, \
+    created_at, updated_at, deleted_at";
+
+/// `CATEGORY_COLUMNS` for queries that join categories as `c`.
+const JOINED_CATEGORY_COLUMNS: &str = "c.id, c.user_id, c.name, c.icon, c.color, c.unit, \
+    c.default_amount::float8 AS default_amount, c.is_active, c.sort_order, c.group_name, \
+    c.created_at, c.updated_at, c.deleted_at";
+
+const COUNTER_DATA_COLUMNS: &str = "id, user_id, category_id, date, count, \
+    amounts::float8[] AS amounts, notes, created_at, updated_at";
+
+/// A category joined with its counters on one day.
+#[derive(sqlx::FromRow)]
+struct CounterRow {
+    #[sqlx(flatten)]
+    category: Category,
+    date: Option<NaiveDate>,
+    count: Option<i32>,
+    amounts: Option<Vec<f64>>,
+    entry_updated_at: Option<DateTime<Utc>>,
+}
+
+impl CounterRow {
+    fn into_entry(self) -> CategoryCounterData {
+        counter_entry(
+            &self.category,
+            self.count.unwrap_or(0),
+            self.amounts.unwrap_or_default(),
+            self.entry_updated_at,
+        )
+    }
+}
+
+/// A row read from the change feed with its sequence number.
+#[derive(FromRow)]
+struct Sequenced<T> {
+    change_seq: i64,
+    #[sqlx(flatten)]
+    row: T,
+}
+
+impl<T> Sequenced<T> {
+    fn into_pair(self) -> (i64, T) {
+        (self.change_seq, self.row)
+    }
+}
+
+#[derive(Clone)]
+pub struct PostgresRepository {
+    pool: PgPool,
+}
+
+impl PostgresRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+/// Starts a transaction that changes the user's data. Takes the lock that
+/// orders the user's change sequence (see the `0006_change_feed` migration)
+/// before any row locks, so concurrent writers queue on it instead of
+/// deadlocking part way through.
+async fn begin_change(pool: &PgPool, user_id: Uuid) -> RepositoryResult<Transaction<'static, Postgres>> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("SELECT lock_change_seq($1)")
+        .bind(user_id)
+        .execute(&mut tx)
+        .instrument(query_span("SELECT", "change_seq"))
+        .await?;
+
+    Ok(tx)
+}
+
+fn unique_violation_as_conflict(e: sqlx::Error) -> RepositoryError {
+    match &e {
+        sqlx::Error::Database(db) if db.code().as_deref() == Some("23505") => {
+            RepositoryError::duplicate_name()
+        }
+        _ => RepositoryError::Database(e),
+    }
+}
+
+async fn record(
+    tx: &mut Transaction<'_, Postgres>,
+    user: &AuthenticatedUser,
+    context: &AuditContext,
+    event: AuditEvent,
+) -> RepositoryResult<()> {
+    audit::record(tx, user, context, event)
+        .await
+        .map_err(RepositoryError::Audit)
+}
+
+/// Locks one of the user's categories for the rest of the transaction.
+async fn lock_category(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    id: Uuid,
+    archived: bool,
+) -> RepositoryResult<Category> {
+    sqlx::query_as::<_, Category>(&format!(
+        "SELECT {} FROM categories WHERE id = $1 AND user_id = $2 AND (deleted_at IS NOT NULL) = $3 FOR UPDATE",
+        CATEGORY_COLUMNS
+    ))
+    .bind(id)
+    .bind(user_id)
+    .bind(archived)
+    .fetch_optional(tx)
+    .instrument(query_span("SELECT", "categories"))
+    .await?
+    .ok_or_else(RepositoryError::category_not_found)
+}
+
+/// One of the user's live, active categories, which are the only ones that
+/// can be counted, kept from being archived for the rest of the transaction.
+async fn share_active_category(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    id: Uuid,
+) -> RepositoryResult<Category> {
+    sqlx::query_as::<_, Category>(&format!(
+        "SELECT {} FROM categories WHERE id = $1 AND user_id = $2 AND is_active = true AND deleted_at IS NULL FOR SHARE",
+        CATEGORY_COLUMNS
+    ))
+    .bind(id)
+    .bind(user_id)
+    .fetch_optional(tx)
+    .instrument(query_span("SELECT", "categories"))
+    .await?
+    .ok_or_else(RepositoryError::category_not_found)
+}
+
+/// Locks a category's entry on `date`, if it has one.
+async fn lock_entry(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    category_id: Uuid,
+    date: NaiveDate,
+) -> RepositoryResult<Option<CounterData>> {
+    let entry = sqlx::query_as::<_, CounterData>(&format!(
+        "SELECT {} FROM counter_data WHERE user_id = $1 AND category_id = $2 AND date = $3 FOR UPDATE",
+        COUNTER_DATA_COLUMNS
+    ))
+    .bind(user_id)
+    .bind(category_id)
+    .bind(date)
+    .fetch_optional(tx)
+    .instrument(query_span("SELECT", "counter_data"))
+    .await?;
+
+    Ok(entry)
+}
+
+/// Claims `key` for this request, or returns the result stored by the earlier
+/// request that claimed it. A concurrent request with the same key waits here
+/// until that one commits or rolls back.
+async fn claim_idempotency_key(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    key: &IdempotencyKey,
+) -> RepositoryResult<Option<CategoryCounterData>> {
+    sqlx::query("DELETE FROM idempotency_keys WHERE user_id = $1 AND created_at < $2")
+        .bind(user_id)
+        .bind(key.expired_before)
+        .execute(&mut *tx)
+        .instrument(query_span("DELETE", "idempotency_keys"))
+        .await?;
+
+    let claimed = sqlx::query(
+        "INSERT INTO idempotency_keys (user_id, operation, idempotency_key, request_hash) VALUES ($1, $2, $3, $4) ON CONFLICT DO NOTHING",
+    )
+    .bind(user_id)
+    .bind(key.operation)
+    .bind(&key.key)
+    .bind(&key.request_hash)
+    .execute(&mut *tx)
+    .instrument(query_span("INSERT", "idempotency_keys"))
+    .await?
+    .rows_affected()
+        == 1;
+    if claimed {
+        return Ok(None);
+    }
+
+    let (request_hash, response): (String, Option<Json<CategoryCounterData>>) = sqlx::query_as(
+        "SELECT request_hash, response FROM idempotency_keys WHERE user_id = $1 AND operation = $2 AND idempotency_key = $3",
+    )
+    .bind(user_id)
+    .bind(key.operation)
+    .bind(&key.key)
+    .fetch_one(&mut *tx)
+    .instrument(query_span("SELECT", "idempotency_keys"))
+    .await?;
+
+    if request_hash != key.request_hash {
+        return Err(RepositoryError::IdempotencyKeyReused);
+    }
+    // The claiming request stores its result before committing
+    response
+        .map(|response| Some(response.0))
+        .ok_or_else(|| RepositoryError::Conflict("Idempotency key is still in use".to_string()))
+}
+
+async fn store_idempotent_result(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    key: &IdempotencyKey,
+    entry: &CategoryCounterData,
+) -> RepositoryResult<()> {
+    sqlx::query(
+        "UPDATE idempotency_keys SET response = $4 WHERE user_id = $1 AND operation = $2 AND idempotency_key = $3",
+    )
+    .bind(user_id)
+    .bind(key.operation)
+    .bind(&key.key)
+    .bind(Json(entry))
+    .execute(tx)
+    .instrument(query_span("UPDATE", "idempotency_keys"))
+    .await?;
+
+    Ok(())
+}
+
+#[async_trait]
+impl CategoryRepository for PostgresRepository {
+    async fn list(&self, user_id: Uuid, active_only: bool) -> RepositoryResult<Vec<Category>> {
+        let categories = sqlx::query_as::<_, Category>(&format!(
+            "SELECT {} FROM categories WHERE user_id = $1 AND deleted_at IS NULL AND (is_active = true OR NOT $2) ORDER BY sort_order ASC, created_at ASC",
+            CATEGORY_COLUMNS
+        ))
+        .bind(user_id)
+        .bind(active_only)
+        .fetch_all(&self.pool)
+        .instrument(query_span("SELECT", "categories"))
+        .await?;
+
+        Ok(categories)
+    }
+
+    async fn get(&self, user_id: Uuid, id: Uuid) -> RepositoryResult<Category> {
+        sqlx::query_as::<_, Category>(&format!(
+            "SELECT {} FROM categories WHERE id = $1 AND user_id = $2 AND deleted_at IS NULL",
+            CATEGORY_COLUMNS
+        ))
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .instrument(query_span("SELECT", "categories"))
+        .await?
+        .ok_or_else(RepositoryError::category_not_found)
+    }
+
+    async fn create(
+        &self,
+        user: &AuthenticatedUser,
+        context: &AuditContext,
+        request: &CreateCategoryRequest,
+    ) -> RepositoryResult<Category> {
+        let mut tx = begin_change(&self.pool, user.user_id).await?;
+
+        let category = sqlx::query_as::<_, Category>(&format!(
+            r#"
+            INSERT INTO categories (user_id, name, icon, color, unit, default_amount, group_name, sort_order)
+            VALUES ($1, $2, $3, $4, $5, $6, NULLIF($7, ''), (
+                SELECT COALESCE(MAX(sort_order) + 1, 0) FROM categories WHERE user_id = $1
+            ))
+            RETURNING {}
+            "#,
+            CATEGORY_COLUMNS
+        ))
+        .bind(user.user_id)
+        .bind(&request.name)
+        .bind(&request.icon)
+        .bind(&request.color)
+        .bind(&request.unit)
+        .bind(request.default_amount)
+        .bind(request.group_name.as_deref())
+        .fetch_one(&mut tx)
+        .instrument(query_span("INSERT", "categories"))
+        .await
+        .map_err(unique_violation_as_conflict)?;
+
+        record(
+            &mut tx,
+            user,
+            context,
+            AuditEvent {
+                action: "category.create",
+                entity_type: "category",
+                entity_id: Some(category.id),
+                before: None,
+                after: serde_json::to_value(&category).ok(),
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(category)
+    }
+
+    async fn update(
+        &self,
+        user: &AuthenticatedUser,
+        context: &AuditContext,
+        id: Uuid,
+        request: &UpdateCategoryRequest,
+        precondition: &Precondition,
+    ) -> RepositoryResult<Category> {
+        let mut tx = begin_change(&self.pool, user.user_id).await?;
+
+        let before = lock_category(&mut tx, user.user_id, id, false).await?;
+        precondition.check(&before)?;
+
+        let category = sqlx::query_as::<_, Category>(&format!(
+            r#"
+            UPDATE categories SET
+                name = COALESCE($3, name),
+                icon = COALESCE($4, icon),
+                color = COALESCE($5, color),
+                unit = COALESCE($6, unit),
+                default_amount = COALESCE($7, default_amount),
+                is_active = COALESCE($8, is_active),
+                group_name = CASE WHEN $9::text IS NULL THEN group_name ELSE NULLIF($9, '') END
+            WHERE id = $1 AND user_id = $2
+            RETURNING {}
+            "#,
+            CATEGORY_COLUMNS
+        ))
+        .bind(id)
+        .bind(user.user_id)
+        .bind(request.name.as_deref())
+        .bind(request.icon.as_deref())
+        .bind(request.color.as_deref())
+        .bind(request.unit.as_deref())
+        .bind(request.default_amount)
+        .bind(request.is_active)
+        .bind(request.group_name.as_deref())
+        .fetch_one(&mut tx)
+        .instrument(query_span("UPDATE", "categories"))
+        .await
+        .map_err(unique_violation_as_conflict)?;
+
+        record(
+            &mut tx,
+            user,
+            context,
+            AuditEvent {
+                action: "category.update",
+                entity_type: "category",
+                entity_id: Some(category.id),
+                before: serde_json::to_value(&before).ok(),
+                after: serde_json::to_value(&category).ok(),
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(category)
+    }
+
+    async fn archive(
+        &self,
+        user: &AuthenticatedUser,
+        context: &AuditContext,
+        id: Uuid,
+        precondition: &Precondition,
+    ) -> RepositoryResult<Category> {
+        let mut tx = begin_change(&self.pool, user.user_id).await?;
+
+        let before = lock_category(&mut tx, user.user_id, id, false).await?;
+        precondition.check(&before)?;
+
+        let category = sqlx::query_as::<_, Category>(&format!(
+            "UPDATE categories SET deleted_at = NOW() WHERE id = $1 AND user_id = $2 RETURNING {}",
+            CATEGORY_COLUMNS
+        ))
+        .bind(id)
+        .bind(user.user_id)
+        .fetch_one(&mut tx)
+        .instrument(query_span("UPDATE", "categories"))
+        .await?;
+
+        record(
+            &mut tx,
+            user,
+            context,
+            AuditEvent {
+                action: "category.archive",
+                entity_type: "category",
+                entity_id: Some(category.id),
+                before: serde_json::to_value(&before).ok(),
+                after: serde_json::to_value(&category).ok(),
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(category)
+    }
+
+    async fn reorder(
+        &self,
+        user: &AuthenticatedUser,
+        context: &AuditContext,
+        positions: &[CategoryPosition],
+    ) -> RepositoryResult<Vec<Category>> {
+        ensure_unique_positions(positions)?;
+
+        let ids: Vec<Uuid> = positions.iter().map(|item| item.id).collect();
+        let groups: Vec<Option<String>> =
+            positions.iter().map(|item| item.group_name.clone()).collect();
+
+        let mut tx = begin_change(&self.pool, user.user_id).await?;
+
+        let before = sqlx::query_as::<_, Category>(&format!(
+            "SELECT {} FROM categories WHERE user_id = $1 AND deleted_at IS NULL ORDER BY sort_order ASC, created_at ASC FOR UPDATE",
+            CATEGORY_COLUMNS
+        ))
+        .bind(user.user_id)
+        .fetch_all(&mut tx)
+        .instrument(query_span("SELECT", "categories"))
+        .await?;
+
+        if let Some(unknown) = ids.iter().find(|id| !before.iter().any(|c| c.id == **id)) {
+            return Err(RepositoryError::NotFound(format!(
+                "Category {} not found",
+                unknown
+            )));
+        }
+
+        let categories = sqlx::query_as::<_, Category>(&format!(
+            r#"
+            WITH layout AS (
+                SELECT id, position - 1 AS position, group_name
+                FROM unnest($2::uuid[], $3::text[]) WITH ORDINALITY AS l(id, group_name, position)
+            ),
+            remaining AS (
+                SELECT c.id,
+                       cardinality($2::uuid[]) + ROW_NUMBER() OVER (ORDER BY c.sort_order, c.created_at) - 1 AS position,
+                       c.group_name
+                FROM categories c
+                WHERE c.user_id = $1 AND c.deleted_at IS NULL AND c.id <> ALL($2::uuid[])
+            ),
+            updated AS (
+                UPDATE categories c SET sort_order = p.position, group_name = p.group_name
+                FROM (SELECT * FROM layout UNION ALL SELECT * FROM remaining) p
+                WHERE c.id = p.id AND c.user_id = $1
+                RETURNING c.*
+            )
+            SELECT {} FROM updated ORDER BY sort_order ASC
+            "#,
+            CATEGORY_COLUMNS
+        ))
+        .bind(user.user_id)
+        .bind(&ids)
+        .bind(&groups)
+        .fetch_all(&mut tx)
+        .instrument(query_span("UPDATE", "categories"))
+        .await?;
+
+        let layout = |categories: &[Category]| {
+            categories
+                .iter()
+                .map(|c| serde_json::json!({ "id": c.id, "sort_order": c.sort_order, "group_name": c.group_name }))
+                .collect::<Vec<_>>()
+        };
+
+        record(
+            &mut tx,
+            user,
+            context,
+            AuditEvent {
+                action: "category.reorder",
+                entity_type: "category",
+                entity_id: None,
+                before: Some(Value::from(layout(&before))),
+                after: Some(Value::from(layout(&categories))),
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(categories)
+    }
+
+    async fn list_trash(&self, user_id: Uuid) -> RepositoryResult<Vec<Category>> {
+        let categories = sqlx::query_as::<_, Category>(&format!(
+            "SELECT {} FROM categories WHERE user_id = $1 AND deleted_at IS NOT NULL ORDER BY deleted_at DESC",
+            CATEGORY_COLUMNS
+        ))
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .instrument(query_span("SELECT", "categories"))
+        .await?;
+
+        Ok(categories)
+    }
+
+    async fn restore(
+        &self,
+        user: &AuthenticatedUser,
+        context: &AuditContext,
+        id: Uuid,
+    ) -> RepositoryResult<Category> {
+        let mut tx = begin_change(&self.pool, user.user_id).await?;
+
+        let before = lock_category(&mut tx, user.user_id, id, true).await?;
+
+        // A live category may have taken the name in the meantime
+        let category = sqlx::query_as::<_, Category>(&format!(
+            "UPDATE categories SET deleted_at = NULL WHERE id = $1 AND user_id = $2 RETURNING {}",
+            CATEGORY_COLUMNS
+        ))
+        .bind(id)
+        .bind(user.user_id)
+        .fetch_one(&mut tx)
+        .instrument(query_span("UPDATE", "categories"))
+        .await
+        .map_err(unique_violation_as_conflict)?;
+
+        record(
+            &mut tx,
+            user,
+            context,
+            AuditEvent {
+                action: "category.restore",
+                entity_type: "category",
+                entity_id: Some(category.id),
+                before: serde_json::to_value(&before).ok(),
+                after: serde_json::to_value(&category).ok(),
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(category)
+    }
+
+    async fn merge(
+        &self,
+        user: &AuthenticatedUser,
+        context: &AuditContext,
+        source_id: Uuid,
+        request: &MergeCategoryRequest,
+    ) -> RepositoryResult<MergeCategoryResult> {
+        if request.target_id == source_id {
+            return Err(RepositoryError::InvalidInput(
+                "Cannot merge a category into itself".to_string(),
+            ));
+        }
+
+        let mut tx = begin_change(&self.pool, user.user_id).await?;
+
+        // Lock both rows in a stable order so concurrent merges cannot deadlock
+        let locked = sqlx::query_as::<_, Category>(&format!(
+            "SELECT {} FROM categories WHERE id = ANY($1) AND user_id = $2 AND deleted_at IS NULL ORDER BY id FOR UPDATE",
+            CATEGORY_COLUMNS
+        ))
+        .bind(vec![source_id, request.target_id])
+        .bind(user.user_id)
+        .fetch_all(&mut tx)
+        .instrument(query_span("SELECT", "categories"))
+        .await?;
+
+        let mut source = None;
+        let mut target = None;
+        for category in locked {
+            if category.id == source_id {
+                source = Some(category);
+            } else {
+                target = Some(category);
+            }
+        }
+        let (source, target) = source
+            .zip(target)
+            .ok_or_else(RepositoryError::category_not_found)?;
+
+        let conversion_factor = merge_conversion_factor(&source, &target, request.conversion_factor)?;
+
+        // Days present in both categories are folded into the target row
+        let combined = sqlx::query(
+            r#"
+            UPDATE counter_data t SET
+                count = t.count + s.count,
+                amounts = t.amounts || ARRAY(
+                    SELECT a * $4 FROM unnest(s.amounts) WITH ORDINALITY AS u(a, i) ORDER BY i
+                )::DECIMAL(10,2)[],
+                notes = CASE
+                    WHEN s.notes IS NULL THEN t.notes
+                    WHEN t.notes IS NULL THEN s.notes
+                    ELSE t.notes || E'\n' || s.notes
+                END
+            FROM counter_data s
+            WHERE s.user_id = $1 AND s.category_id = $2
+                AND t.user_id = $1 AND t.category_id = $3 AND t.date = s.date
+            "#,
+        )
+        .bind(user.user_id)
+        .bind(source.id)
+        .bind(target.id)
+        .bind(conversion_factor)
+        .execute(&mut tx)
+        .instrument(query_span("UPDATE", "counter_data"))
+        .await?;
+
+        sqlx::query(
+            r#"
+            DELETE FROM counter_data s
+            WHERE s.user_id = $1 AND s.category_id = $2
+                AND EXISTS (
+                    SELECT 1 FROM counter_data t
+                    WHERE t.user_id = $1 AND t.category_id = $3 AND t.date = s.date
+                )
+            "#,
+        )
+        .bind(user.user_id)
+        .bind(source.id)
+        .bind(target.id)
+        .execute(&mut tx)
+        .instrument(query_span("DELETE", "counter_data"))
+        .await?;
+
+        let moved = sqlx::query(
+            r#"
+            UPDATE counter_data SET
+                category_id = $3,
+                amounts = ARRAY(
+                    SELECT a * $4 FROM unnest(amounts) WITH ORDINALITY AS u(a, i) ORDER BY i
+                )::DECIMAL(10,2)[]
+            WHERE user_id = $1 AND category_id = $2
+            "#,
+        )
+        .bind(user.user_id)
+        .bind(source.id)
+        .bind(target.id)
+        .bind(conversion_factor)
+        .execute(&mut tx)
+        .instrument(query_span("UPDATE", "counter_data"))
+        .await?;
+
+        let archived = sqlx::query_as::<_, Category>(&format!(
+            "UPDATE categories SET deleted_at = NOW() WHERE id = $1 AND user_id = $2 RETURNING {}",
+            CATEGORY_COLUMNS
+        ))
+        .bind(source.id)
+        .bind(user.user_id)
+        .fetch_one(&mut tx)
+        .instrument(query_span("UPDATE", "categories"))
+        .await?;
+
+        let result = MergeCategoryResult {
+            source: archived,
+            target,
+            conversion_factor,
+            moved_days: moved.rows_affected(),
+            combined_days: combined.rows_affected(),
+        };
+
+        record(
+            &mut tx,
+            user,
+            context,
+            AuditEvent {
+                action: "category.merge",
+                entity_type: "category",
+                entity_id: Some(source.id),
+                before: serde_json::to_value(&source).ok(),
+                after: serde_json::to_value(&result).ok(),
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(result)
+    }
+
+    async fn fingerprint(&self, user_id: Uuid) -> RepositoryResult<Fingerprint> {
+        let fingerprint = sqlx::query_as::<_, Fingerprint>(
+            "SELECT COUNT(*) AS rows, MAX(updated_at) AS last_updated, 0::int8 AS counts FROM categories WHERE user_id = $1",
+        )
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .instrument(query_span("SELECT", "categories"))
+        .await?;
+
+        Ok(fingerprint)
+    }
+}
+
+#[async_trait]
+impl CounterRepository for PostgresRepository {
+    async fn day(&self, user_id: Uuid, date: NaiveDate) -> RepositoryResult<CounterDataResponseV2> {
+        let rows = sqlx::query_as::<_, CounterRow>(&format!(
+            r#"
+            SELECT {}, cd.date, cd.count, cd.amounts::float8[] AS amounts, cd.updated_at AS entry_updated_at
+            FROM categories c
+            LEFT JOIN counter_data cd ON c.id = cd.category_id AND cd.date = $1 AND cd.user_id = $2
+            WHERE c.user_id = $2 AND c.is_active = true AND c.deleted_at IS NULL
+            ORDER BY c.sort_order ASC, c.created_at ASC
+            "#,
+            JOINED_CATEGORY_COLUMNS
+        ))
+        .bind(date)
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .instrument(query_span("SELECT", "categories"))
+        .await?;
+
+        Ok(CounterDataResponseV2 {
+            date,
+            categories: rows.into_iter().map(CounterRow::into_entry).collect(),
+        })
+    }
+
+    async fn range(
+        &self,
+        user_id: Uuid,
+        start: NaiveDate,
+        end: NaiveDate,
+        include_archived: bool,
+    ) -> RepositoryResult<Vec<CounterDataResponseV2>> {
+        let rows = sqlx::query_as::<_, CounterRow>(&format!(
+            r#"
+            SELECT {}, cd.date, cd.count, cd.amounts::float8[] AS amounts, cd.updated_at AS entry_updated_at
+            FROM counter_data cd
+            JOIN categories c ON c.id = cd.category_id
+            WHERE cd.user_id = $1 AND cd.date BETWEEN $2 AND $3 AND c.is_active = true
+                AND (c.deleted_at IS NULL OR $4)
+            ORDER BY cd.date ASC, c.sort_order ASC, c.created_at ASC
+            "#,
+            JOINED_CATEGORY_COLUMNS
+        ))
+        .bind(user_id)
+        .bind(start)
+        .bind(end)
+        .bind(include_archived)
+        .fetch_all(&self.pool)
+        .instrument(query_span("SELECT", "counter_data"))
+        .await?;
+
+        // Rows arrive grouped by date and in display order within each day
+        Ok(group_by_date(rows.into_iter().filter_map(|row| {
+            let date = row.date?;
+            Some((date, row.into_entry()))
+        })))
+    }
+
+    async fn add(
+        &self,
+        user: &AuthenticatedUser,
+        context: &AuditContext,
+        date: NaiveDate,
+        request: &AddCounterRequest,
+        idempotency: Option<&IdempotencyKey>,
+    ) -> RepositoryResult<Added> {
+        let mut tx = begin_change(&self.pool, user.user_id).await?;
+
+        if let Some(key) = idempotency {
+            if let Some(entry) = claim_idempotency_key(&mut tx, user.user_id, key).await? {
+                return Ok(Added::replayed(entry));
+            }
+        }
+
+        let category = share_active_category(&mut tx, user.user_id, request.category_id).await?;
+        let before = lock_entry(&mut tx, user.user_id, request.category_id, date).await?;
+
+        let result = sqlx::query_as::<_, CounterData>(&format!(
+            r#"
+            INSERT INTO counter_data (user_id, category_id, date, count, amounts, notes)
+            VALUES ($1, $2, $3, 1, ARRAY[$4], $5)
+            ON CONFLICT (user_id, category_id, date)
+            DO UPDATE SET
+                count = counter_data.count + 1,
+                amounts = array_append(counter_data.amounts, $4),
+                notes = CASE
+                    WHEN $5 IS NOT NULL THEN
+                        CASE
+                            WHEN counter_data.notes IS NULL THEN $5
+                            ELSE counter_data.notes || E'\n' || $5
+                        END
+                    ELSE counter_data.notes
+                END,
+                updated_at = NOW()
+            RETURNING {}
+            "#,
+            COUNTER_DATA_COLUMNS
+        ))
+        .bind(user.user_id)
+        .bind(request.category_id)
+        .bind(date)
+        .bind(request.amount)
+        .bind(request.notes.as_deref())
+        .fetch_one(&mut tx)
+        .instrument(query_span("INSERT", "counter_data"))
+        .await?;
+
+        record(
+            &mut tx,
+            user,
+            context,
+            AuditEvent {
+                action: "counter.add",
+                entity_type: "counter_data",
+                entity_id: Some(result.id),
+                before: before.and_then(|row| serde_json::to_value(row).ok()),
+                after: serde_json::to_value(&result).ok(),
+            },
+        )
+        .await?;
+
+        let entry = counter_entry(
+            &category,
+            result.count,
+            result.amounts,
+            Some(result.updated_at),
+        );
+        if let Some(key) = idempotency {
+            store_idempotent_result(&mut tx, user.user_id, key, &entry).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(Added::new(entry))
+    }
+
+    async fn set(
+        &self,
+        user: &AuthenticatedUser,
+        context: &AuditContext,
+        date: NaiveDate,
+        request: &SetCounterRequest,
+        precondition: &Precondition,
+        idempotency: Option<&IdempotencyKey>,
+    ) -> RepositoryResult<Added> {
+        let mut tx = begin_change(&self.pool, user.user_id).await?;
+
+        if let Some(key) = idempotency {
+            if let Some(entry) = claim_idempotency_key(&mut tx, user.user_id, key).await? {
+                return Ok(Added::replayed(entry));
+            }
+        }
+
+        let category = share_active_category(&mut tx, user.user_id, request.category_id).await?;
+        let before = lock_entry(&mut tx, user.user_id, request.category_id, date).await?;
+        precondition.check_entry(&match &before {
+            Some(row) => counter_entry(&category, row.count, row.amounts.clone(), Some(row.updated_at)),
+            None => counter_entry(&category, 0, Vec::new(), None),
+        })?;
+
+        let (entry, event) = if request.amounts.is_empty() {
+            if let Some(row) = &before {
+                sqlx::query("DELETE FROM counter_data WHERE id = $1")
+                    .bind(row.id)
+                    .execute(&mut tx)
+                    .instrument(query_span("DELETE", "counter_data"))
+                    .await?;
+            }
+
+            let event = before.map(|row| AuditEvent {
+                action: "counter.delete",
+                entity_type: "counter_data",
+                entity_id: Some(row.id),
+                before: serde_json::to_value(row).ok(),
+                after: None,
+            });
+            (counter_entry(&category, 0, Vec::new(), None), event)
+        } else {
+            let result = sqlx::query_as::<_, CounterData>(&format!(
+                r#"
+                INSERT INTO counter_data (user_id, category_id, date, count, amounts, notes)
+                VALUES ($1, $2, $3, $4, $5::DECIMAL(10,2)[], $6)
+                ON CONFLICT (user_id, category_id, date)
+                DO UPDATE SET
+                    count = EXCLUDED.count,
+                    amounts = EXCLUDED.amounts,
+                    notes = COALESCE(EXCLUDED.notes, counter_data.notes),
+                    updated_at = NOW()
+                RETURNING {}
+                "#,
+                COUNTER_DATA_COLUMNS
+            ))
+            .bind(user.user_id)
+            .bind(request.category_id)
+            .bind(date)
+            .bind(request.amounts.len() as i32)
+            .bind(&request.amounts)
+            .bind(request.notes.as_deref())
+            .fetch_one(&mut tx)
+            .instrument(query_span("INSERT", "counter_data"))
+            .await?;
+
+            let event = AuditEvent {
+                action: "counter.set",
+                entity_type: "counter_data",
+                entity_id: Some(result.id),
+                before: before.and_then(|row| serde_json::to_value(row).ok()),
+                after: serde_json::to_value(&result).ok(),
+            };
+            let entry = counter_entry(
+                &category,
+                result.count,
+                result.amounts,
+                Some(result.updated_at),
+            );
+            (entry, Some(event))
+        };
+
+        // Deleting a day with nothing logged changes nothing worth recording
+        if let Some(event) = event {
+            record(&mut tx, user, context, event).await?;
+        }
+        if let Some(key) = idempotency {
+            store_idempotent_result(&mut tx, user.user_id, key, &entry).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(Added::new(entry))
+    }
+
+    async fn fingerprint_range(
+        &self,
+        user_id: Uuid,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> RepositoryResult<Fingerprint> {
+        let fingerprint = sqlx::query_as::<_, Fingerprint>(
+            r#"
+            SELECT c.rows + cd.rows AS rows, GREATEST(c.last_updated, cd.last_updated) AS last_updated, cd.counts
+            FROM (
+                SELECT COUNT(*) AS rows, MAX(updated_at) AS last_updated
+                FROM categories WHERE user_id = $1
+            ) c
+            CROSS JOIN (
+                SELECT COUNT(*) AS rows, MAX(updated_at) AS last_updated, COALESCE(SUM(count), 0)::int8 AS counts
+                FROM counter_data WHERE user_id = $1 AND date BETWEEN $2 AND $3
+            ) cd
+            "#,
+        )
+        .bind(user_id)
+        .bind(start)
+        .bind(end)
+        .fetch_one(&self.pool)
+        .instrument(query_span("SELECT", "counter_data"))
+        .await?;
+
+        Ok(fingerprint)
+    }
+}
+
+#[async_trait]
+impl SyncRepository for PostgresRepository {
+    async fn changes(&self, user_id: Uuid, since: i64, limit: usize) -> RepositoryResult<SyncChanges> {
+        // Read all three from one snapshot, so that a change committed in
+        // between can't be passed over by the cursor
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+            .execute(&mut tx)
+            .await?;
+        let fetch = limit as i64 + 1;
+
+        let categories = sqlx::query_as::<_, Sequenced<Category>>(&format!(
+            "SELECT change_seq, {} FROM categories WHERE user_id = $1 AND change_seq > $2 ORDER BY change_seq LIMIT $3",
+            CATEGORY_COLUMNS
+        ))
+        .bind(user_id)
+        .bind(since)
+        .bind(fetch)
+        .fetch_all(&mut tx)
+        .instrument(query_span("SELECT", "categories"))
+        .await?;
+
+        let counters = sqlx::query_as::<_, Sequenced<CounterData>>(&format!(
+            "SELECT change_seq, {} FROM counter_data WHERE user_id = $1 AND change_seq > $2 ORDER BY change_seq LIMIT $3",
+            COUNTER_DATA_COLUMNS
+        ))
+        .bind(user_id)
+        .bind(since)
+        .bind(fetch)
+        .fetch_all(&mut tx)
+        .instrument(query_span("SELECT", "counter_data"))
+        .await?;
+
+        let deleted = sqlx::query_as::<_, Sequenced<SyncTombstone>>(
+            r#"
+            SELECT change_seq, entity_type, entity_id, category_id, date, deleted_at
+            FROM sync_tombstones WHERE user_id = $1 AND change_seq > $2 ORDER BY change_seq LIMIT $3
+            "#,
+        )
+        .bind(user_id)
+        .bind(since)
+        .bind(fetch)
+        .fetch_all(&mut tx)
+        .instrument(query_span("SELECT", "sync_tombstones"))
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(page_changes(
+            since,
+            limit,
+            categories.into_iter().map(Sequenced::into_pair).collect(),
+            counters.into_iter().map(Sequenced::into_pair).collect(),
+            deleted.into_iter().map(Sequenced::into_pair).collect(),
+        ))
+    }
+}
+
+#[async_trait]
+impl AuditRepository for PostgresRepository {
+    async fn entries(&self, filter: &AuditFilter) -> RepositoryResult<Vec<AuditLogEntry>> {
+        let entries = sqlx::query_as::<_, AuditLogEntry>(
+            r#"
+            SELECT id, user_id, actor_id, action, entity_type, entity_id,
+                   session_id, request_id, before_data, after_data, created_at
+            FROM audit_log
+            WHERE ($1::uuid IS NULL OR user_id = $1)
+              AND ($2::text IS NULL OR entity_type = $2)
+              AND ($3::uuid IS NULL OR entity_id = $3)
+              AND ($4::timestamptz IS NULL OR created_at < $4)
+            ORDER BY created_at DESC
+            LIMIT $5
+            "#,
+        )
+        .bind(filter.user_id)
+        .bind(filter.entity_type.as_deref())
+        .bind(filter.entity_id)
+        .bind(filter.before)
+        .bind(filter.limit)
+        .fetch_all(&self.pool)
+        .instrument(query_span("SELECT", "audit_log"))
+        .await?;
+
+        Ok(entries)
+    }
+
+    async fn purge_entries(&self, retention_days: i64) -> RepositoryResult<u64> {
+        Ok(audit::purge_expired(&self.pool, retention_days).await?)
+    }
+}
+
+#[async_trait]
+impl TrashRepository for PostgresRepository {
+    async fn purge_trash(&self, retention_days: i64) -> RepositoryResult<u64> {
+        Ok(trash::purge_expired(&self.pool, retention_days).await?)
+    }
+}
@@ -0,0 +1,571 @@
+use axum::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use std::collections::HashSet;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::models::{
+    AddCounterRequest, AuditLogEntry, Category, CategoryCounterData, CategoryPosition,
+    CounterDataResponseV2, CounterData, CreateCategoryRequest, MergeCategoryRequest, MergeCategoryResult,
+    SetCounterRequest, SyncChanges, SyncTombstone, UpdateCategoryRequest,
+};
+use crate::utils::audit::AuditContext;
+use crate::utils::auth::AuthenticatedUser;
+use crate::utils::units;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RepositoryError {
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error("cannot convert '{from}' to '{to}'")]
+    IncompatibleUnits { from: String, to: String },
+    /// An idempotency key was sent again with a different request.
+    #[error("idempotency key was already used for a different request")]
+    IdempotencyKeyReused,
+    /// A conditional write found the category changed; carries its current state.
+    #[error("category has changed since it was read")]
+    PreconditionFailed(Box<Category>),
+    /// A conditional counter write found the entry changed; carries its
+    /// current state.
+    #[error("counter entry has changed since it was read")]
+    EntryChanged(Box<CategoryCounterData>),
+    #[error("failed to record change: {0}")]
+    Audit(sqlx::Error),
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl RepositoryError {
+    pub fn category_not_found() -> Self {
+        RepositoryError::NotFound("Category not found".to_string())
+    }
+
+    pub fn duplicate_name() -> Self {
+        RepositoryError::Conflict("A category with this name already exists".to_string())
+    }
+}
+
+pub type RepositoryResult<T> = Result<T, RepositoryError>;
+
+/// Versions of a category or counter entry, by `updated_at`, that a
+/// conditional write (`If-Match`) may replace.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Precondition {
+    /// No `If-Match`, or `If-Match: *`.
+    #[default]
+    Any,
+    UpdatedAt(Vec<DateTime<Utc>>),
+}
+
+impl Precondition {
+    /// Whether a row last changed at `updated_at` (`None` if there is no
+    /// row) may be replaced.
+    pub fn allows(&self, updated_at: Option<DateTime<Utc>>) -> bool {
+        match self {
+            Precondition::Any => true,
+            Precondition::UpdatedAt(versions) => {
+                updated_at.is_some_and(|updated_at| versions.contains(&updated_at))
+            }
+        }
+    }
+
+    /// Fails with the current state when the category has changed since the
+    /// client read it.
+    pub fn check(&self, current: &Category) -> RepositoryResult<()> {
+        if self.allows(Some(current.updated_at)) {
+            Ok(())
+        } else {
+            Err(RepositoryError::PreconditionFailed(Box::new(current.clone())))
+        }
+    }
+
+    /// Like `check`, for a category's entry on one day.
+    pub fn check_entry(&self, current: &CategoryCounterData) -> RepositoryResult<()> {
+        if self.allows(current.updated_at) {
+            Ok(())
+        } else {
+            Err(RepositoryError::EntryChanged(Box::new(current.clone())))
+        }
+    }
+}
+
+/// A client-chosen `Idempotency-Key` making a write safe to retry: the first
+/// request with a key is applied and its result stored, later ones with the
+/// same key get that result back.
+#[derive(Debug, Clone)]
+pub struct IdempotencyKey {
+    /// The endpoint the key was sent to; each has its own keys.
+    pub operation: &'static str,
+    pub key: String,
+    /// Digest of the request, to refuse reusing the key for a different one.
+    pub request_hash: String,
+    /// Uses of the key before this are forgotten.
+    pub expired_before: DateTime<Utc>,
+}
+
+/// The entry a counter write produced, or replayed from the earlier request
+/// with the same idempotency key.
+#[derive(Debug)]
+pub struct Added {
+    pub entry: CategoryCounterData,
+    pub replayed: bool,
+}
+
+impl Added {
+    pub fn new(entry: CategoryCounterData) -> Self {
+        Added {
+            entry,
+            replayed: false,
+        }
+    }
+
+    pub fn replayed(entry: CategoryCounterData) -> Self {
+        Added {
+            entry,
+            replayed: true,
+        }
+    }
+}
+
+/// A cheap summary of the rows behind a read that changes whenever they do:
+/// how many there are, when the latest one was touched and the sum of their
+/// counts. Lets conditional requests be answered without loading the rows.
+#[derive(Debug, Clone, Default, PartialEq, Eq, sqlx::FromRow)]
+pub struct Fingerprint {
+    pub rows: i64,
+    pub last_updated: Option<DateTime<Utc>>,
+    pub counts: i64,
+}
+
+impl Fingerprint {
+    pub fn combine(self, other: Fingerprint) -> Fingerprint {
+        Fingerprint {
+            rows: self.rows + other.rows,
+            last_updated: self.last_updated.max(other.last_updated),
+            counts: self.counts + other.counts,
+        }
+    }
+}
+
+/// Storage for a user's categories. Every method is scoped to the given user;
+/// categories owned by someone else behave as if they did not exist.
+/// Backends that keep an audit log write each mutation to it together with
+/// the change itself.
+#[async_trait]
+pub trait CategoryRepository: Send + Sync {
+    /// Live categories in display order.
+    async fn list(&self, user_id: Uuid, active_only: bool) -> RepositoryResult<Vec<Category>>;
+
+    async fn get(&self, user_id: Uuid, id: Uuid) -> RepositoryResult<Category>;
+
+    /// Adds a category at the end of the user's layout.
+    async fn create(
+        &self,
+        user: &AuthenticatedUser,
+        context: &AuditContext,
+        request: &CreateCategoryRequest,
+    ) -> RepositoryResult<Category>;
+
+    /// Applies the request if the category still matches `precondition`.
+    async fn update(
+        &self,
+        user: &AuthenticatedUser,
+        context: &AuditContext,
+        id: Uuid,
+        request: &UpdateCategoryRequest,
+        precondition: &Precondition,
+    ) -> RepositoryResult<Category>;
+
+    /// Moves a category to the trash, keeping its counter history, if it
+    /// still matches `precondition`.
+    async fn archive(
+        &self,
+        user: &AuthenticatedUser,
+        context: &AuditContext,
+        id: Uuid,
+        precondition: &Precondition,
+    ) -> RepositoryResult<Category>;
+
+    /// Listed categories take positions 0..n; the rest follow in their old order.
+    async fn reorder(
+        &self,
+        user: &AuthenticatedUser,
+        context: &AuditContext,
+        positions: &[CategoryPosition],
+    ) -> RepositoryResult<Vec<Category>>;
+
+    /// Archived categories, most recently deleted first.
+    async fn list_trash(&self, user_id: Uuid) -> RepositoryResult<Vec<Category>>;
+
+    async fn restore(
+        &self,
+        user: &AuthenticatedUser,
+        context: &AuditContext,
+        id: Uuid,
+    ) -> RepositoryResult<Category>;
+
+    /// Moves all counter data from `source_id` into the request's target,
+    /// combining days both have entries for, then archives the source.
+    async fn merge(
+        &self,
+        user: &AuthenticatedUser,
+        context: &AuditContext,
+        source_id: Uuid,
+        request: &MergeCategoryRequest,
+    ) -> RepositoryResult<MergeCategoryResult>;
+
+    /// Covers all of the user's categories, archived ones included.
+    async fn fingerprint(&self, user_id: Uuid) -> RepositoryResult<Fingerprint>;
+}
+
+/// Storage for daily counter entries.
+#[async_trait]
+pub trait CounterRepository: Send + Sync {
+    /// Every active category with its counters on `date`, zeroed when empty.
+    async fn day(&self, user_id: Uuid, date: NaiveDate) -> RepositoryResult<CounterDataResponseV2>;
+
+    /// Days in `start..=end` that have entries, each in display order.
+    async fn range(
+        &self,
+        user_id: Uuid,
+        start: NaiveDate,
+        end: NaiveDate,
+        include_archived: bool,
+    ) -> RepositoryResult<Vec<CounterDataResponseV2>>;
+
+    /// Records one serving on `date`, creating the day's entry if needed.
+    /// With an idempotency key, a request already recorded under it is not
+    /// applied again; its stored result is returned instead.
+    async fn add(
+        &self,
+        user: &AuthenticatedUser,
+        context: &AuditContext,
+        date: NaiveDate,
+        request: &AddCounterRequest,
+        idempotency: Option<&IdempotencyKey>,
+    ) -> RepositoryResult<Added>;
+
+    /// Replaces a category's servings on `date` if its entry still matches
+    /// `precondition`; no amounts deletes the entry. Idempotency keys work as
+    /// for `add`.
+    async fn set(
+        &self,
+        user: &AuthenticatedUser,
+        context: &AuditContext,
+        date: NaiveDate,
+        request: &SetCounterRequest,
+        precondition: &Precondition,
+        idempotency: Option<&IdempotencyKey>,
+    ) -> RepositoryResult<Added>;
+
+    /// Covers the user's categories and their entries in `start..=end`,
+    /// which together decide what `day` and `range` return.
+    async fn fingerprint_range(
+        &self,
+        user_id: Uuid,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> RepositoryResult<Fingerprint>;
+}
+
+/// The feed of changes offline clients sync from. Every insert, update and
+/// delete of a category or counter entry takes the next number of a sequence,
+/// and a user's changes become visible in that order.
+#[async_trait]
+pub trait SyncRepository: Send + Sync {
+    /// The user's first `limit` changes numbered after `since`.
+    async fn changes(&self, user_id: Uuid, since: i64, limit: usize) -> RepositoryResult<SyncChanges>;
+}
+
+/// Which audit entries to read.
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilter {
+    /// `None` reads every user's entries.
+    pub user_id: Option<Uuid>,
+    pub entity_type: Option<String>,
+    pub entity_id: Option<Uuid>,
+    /// Only entries created strictly before this instant.
+    pub before: Option<DateTime<Utc>>,
+    pub limit: i64,
+}
+
+/// The audit log of backends that keep one. Entries are written by the
+/// category and counter repositories, in the transaction of the change.
+#[async_trait]
+pub trait AuditRepository: Send + Sync {
+    /// Matching entries, newest first.
+    async fn entries(&self, filter: &AuditFilter) -> RepositoryResult<Vec<AuditLogEntry>>;
+    /// Deletes entries older than `retention_days`; returns how many.
+    async fn purge_entries(&self, retention_days: i64) -> RepositoryResult<u64>;
+}
+
+/// Permanent deletion of categories left in the trash.
+#[async_trait]
+pub trait TrashRepository: Send + Sync {
+    /// Deletes categories archived more than `retention_days` ago, together
+    /// with their counter history, and records each purge in the audit log
+    /// as a system action. Returns how many categories were purged.
+    async fn purge_trash(&self, retention_days: i64) -> RepositoryResult<u64>;
+}
+
+pub type DynCategoryRepository = Arc<dyn CategoryRepository>;
+pub type DynCounterRepository = Arc<dyn CounterRepository>;
+pub type DynSyncRepository = Arc<dyn SyncRepository>;
+pub type DynAuditRepository = Arc<dyn AuditRepository>;
+pub type DynTrashRepository = Arc<dyn TrashRepository>;
+
+/// Rejects layouts that mention the same category more than once.
+pub fn ensure_unique_positions(positions: &[CategoryPosition]) -> RepositoryResult<()> {
+    let mut seen = HashSet::new();
+    if positions.iter().all(|item| seen.insert(item.id)) {
+        Ok(())
+    } else {
+        Err(RepositoryError::InvalidInput(
+            "Each category may only appear once".to_string(),
+        ))
+    }
+}
+
+/// Factor applied to the source's amounts when merging, preferring the
+/// caller's override over the built-in unit table.
+pub fn merge_conversion_factor(
+    source: &Category,
+    target: &Category,
+    requested: Option<f64>,
+) -> RepositoryResult<f64> {
+    requested
+        .or_else(|| units::conversion_factor(&source.unit, &target.unit))
+        .ok_or_else(|| RepositoryError::IncompatibleUnits {
+            from: source.unit.clone(),
+            to: target.unit.clone(),
+        })
+}
+
+/// Amounts are stored with two decimal places, as in Postgres' `DECIMAL(10,2)`.
+pub fn round_amount(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
+}
+
+/// Appends a note on its own line, the way repeated entries on a day accumulate.
+pub fn join_notes(existing: Option<String>, added: Option<String>) -> Option<String> {
+    match (existing, added) {
+        (Some(existing), Some(added)) => Some(format!("{}\n{}", existing, added)),
+        (existing, added) => existing.or(added),
+    }
+}
+
+/// Builds a category's entry for one day; the total is derived from the amounts.
+pub fn counter_entry(
+    category: &Category,
+    count: i32,
+    amounts: Vec<f64>,
+    updated_at: Option<DateTime<Utc>>,
+) -> CategoryCounterData {
+    let total_amount = amounts.iter().sum();
+
+    CategoryCounterData {
+        category_id: category.id,
+        name: category.name.clone(),
+        icon: category.icon.clone(),
+        color: category.color.clone(),
+        unit: category.unit.clone(),
+        sort_order: category.sort_order,
+        group_name: category.group_name.clone(),
+        count,
+        amounts,
+        total_amount,
+        updated_at,
+        deleted_at: category.deleted_at,
+    }
+}
+
+/// Pages changes read from each table, each list ordered by sequence number
+/// and holding up to `limit + 1` rows after `since`: keeps the first `limit`
+/// overall and points the cursor at the last of them.
+pub fn page_changes(
+    since: i64,
+    limit: usize,
+    categories: Vec<(i64, Category)>,
+    counters: Vec<(i64, CounterData)>,
+    deleted: Vec<(i64, SyncTombstone)>,
+) -> SyncChanges {
+    let mut numbers: Vec<i64> = categories
+        .iter()
+        .map(|(seq, _)| *seq)
+        .chain(counters.iter().map(|(seq, _)| *seq))
+        .chain(deleted.iter().map(|(seq, _)| *seq))
+        .collect();
+    numbers.sort_unstable();
+
+    let page = &numbers[..limit.min(numbers.len())];
+    let Some(&cursor) = page.last() else {
+        return SyncChanges {
+            cursor: since,
+            ..SyncChanges::default()
+        };
+    };
+
+    fn upto<T>(rows: Vec<(i64, T)>, cursor: i64) -> Vec<T> {
+        rows.into_iter()
+            .take_while(|(seq, _)| *seq <= cursor)
+            .map(|(_, row)| row)
+            .collect()
+    }
+
+    SyncChanges {
+        categories: upto(categories, cursor),
+        counters: upto(counters, cursor),
+        deleted: upto(deleted, cursor),
+        cursor,
+        has_more: numbers.len() > limit,
+    }
+}
+
+/// Groups entries already sorted by date into one response per day.
+pub fn group_by_date(
+    entries: impl IntoIterator<Item = (NaiveDate, CategoryCounterData)>,
+) -> Vec<CounterDataResponseV2> {
+    let mut days: Vec<CounterDataResponseV2> = Vec::new();
+
+    for (date, entry) in entries {
+        match days.last_mut() {
+            Some(day) if day.date == date => day.categories.push(entry),
+            _ => days.push(CounterDataResponseV2 {
+                date,
+                categories: vec![entry],
+            }),
+        }
+    }
+
+    days
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn category(unit: &str) -> Category {
+        Category {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            name: format!("In {}", unit),
+            icon: "coffee".to_string(),
+            color: "#8B4513".to_string(),
+            unit: unit.to_string(),
+            default_amount: 250.0,
+            is_active: true,
+            sort_order: 0,
+            group_name: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            deleted_at: None,
+        }
+    }
+
+    #[test]
+    fn test_duplicate_positions_are_rejected() {
+        let id = Uuid::new_v4();
+        let position = |id| CategoryPosition {
+            id,
+            group_name: None,
+        };
+
+        assert!(ensure_unique_positions(&[position(id), position(Uuid::new_v4())]).is_ok());
+        assert!(matches!(
+            ensure_unique_positions(&[position(id), position(id)]),
+            Err(RepositoryError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_precondition_names_acceptable_versions() {
+        let coffee = category("ml");
+
+        assert!(Precondition::Any.check(&coffee).is_ok());
+        assert!(Precondition::UpdatedAt(vec![coffee.updated_at]).check(&coffee).is_ok());
+        match Precondition::UpdatedAt(vec![coffee.created_at - chrono::Duration::seconds(1)])
+            .check(&coffee)
+        {
+            Err(RepositoryError::PreconditionFailed(current)) => assert_eq!(current.id, coffee.id),
+            other => panic!("expected a failed precondition, got {:?}", other),
+        }
+
+        // An entry that doesn't exist yet only matches `Any`
+        let empty = counter_entry(&coffee, 0, Vec::new(), None);
+        assert!(Precondition::Any.check_entry(&empty).is_ok());
+        assert!(matches!(
+            Precondition::UpdatedAt(vec![coffee.updated_at]).check_entry(&empty),
+            Err(RepositoryError::EntryChanged(_))
+        ));
+    }
+
+    #[test]
+    fn test_merge_conversion_factor() {
+        let litres = category("l");
+        let millilitres = category("ml");
+        let grams = category("g");
+
+        assert_eq!(merge_conversion_factor(&litres, &millilitres, None).unwrap(), 1000.0);
+        assert_eq!(merge_conversion_factor(&litres, &grams, Some(2.0)).unwrap(), 2.0);
+        assert!(matches!(
+            merge_conversion_factor(&millilitres, &grams, None),
+            Err(RepositoryError::IncompatibleUnits { .. })
+        ));
+    }
+
+    #[test]
+    fn test_page_changes_stops_at_the_limit() {
+        let coffee = category("ml");
+        let tombstone = SyncTombstone {
+            entity_type: "category".to_string(),
+            entity_id: Uuid::new_v4(),
+            category_id: None,
+            date: None,
+            deleted_at: Utc::now(),
+        };
+        let read = || {
+            (
+                vec![(3, coffee.clone()), (7, coffee.clone())],
+                vec![(5, tombstone.clone())],
+            )
+        };
+
+        let (categories, deleted) = read();
+        let page = page_changes(2, 2, categories, Vec::new(), deleted);
+        assert_eq!((page.categories.len(), page.deleted.len()), (1, 1));
+        assert_eq!((page.cursor, page.has_more), (5, true));
+
+        let (categories, deleted) = read();
+        let page = page_changes(2, 3, categories, Vec::new(), deleted);
+        assert_eq!((page.categories.len(), page.cursor, page.has_more), (2, 7, false));
+
+        // Nothing new keeps the cursor where it was
+        let page = page_changes(7, 3, Vec::new(), Vec::new(), Vec::new());
+        assert_eq!((page.cursor, page.has_more), (7, false));
+    }
+
+    #[test]
+    fn test_group_by_date_keeps_order_within_day() {
+        let coffee = category("ml");
+        let protein = category("g");
+        let first = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let second = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+
+        let days = group_by_date(vec![
+            (first, counter_entry(&coffee, 2, vec![250.0, 100.0], None)),
+            (first, counter_entry(&protein, 1, vec![30.0], None)),
+            (second, counter_entry(&coffee, 1, vec![250.0], None)),
+        ]);
+
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].categories.len(), 2);
+        assert_eq!(days[0].categories[0].total_amount, 350.0);
+        assert_eq!(days[0].categories[1].category_id, protein.id);
+        assert_eq!(days[1].date, second);
+    }
+}
@@ -0,0 +1,1636 @@
+use axum::async_trait;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde_json::Value;
+use sqlx::migrate::Migrator;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions};
+use sqlx::types::Json;
+use sqlx::{Sqlite, Transaction};
+use std::str::FromStr;
+use uuid::Uuid;
+
+use super::migrations::{verify_applied, MigrationMode, SchemaError};
+use super::repository::{
+    counter_entry, ensure_unique_positions, group_by_date, join_notes, merge_conversion_factor,
+    page_changes, round_amount, Added, AuditFilter, AuditRepository, CategoryRepository,
+    CounterRepository, Fingerprint, IdempotencyKey, Precondition, RepositoryError,
+    RepositoryResult, SyncRepository, TrashRepository,
+};
+use crate::models::{
+    AddCounterRequest, AuditLogEntry, Category, CategoryCounterData, CategoryPosition, CounterData,
+    CounterDataResponseV2, CreateCategoryRequest, MergeCategoryRequest, MergeCategoryResult,
+    SetCounterRequest, SyncChanges, SyncTombstone, UpdateCategoryRequest,
+};
+use crate::utils::audit::{AuditContext, AuditEvent};
+use crate::utils::auth::AuthenticatedUser;
+
+/// Schema migrations for SQLite, embedded at compile time.
+pub static SQLITE_MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+const CATEGORY_COLUMNS: &str = "id, user_id, name, icon, color, unit, \
+    CAST(default_amount AS REAL) AS default_amount, is_active, sort_order, group_name, \
+    created_at, updated_at, deleted_at";
+
+/// `CATEGORY_COLUMNS` for queries that join categories as `c`.
+const JOINED_CATEGORY_COLUMNS: &str = "c.id, c.user_id, c.name, c.icon, c.color, c.unit, \
+    CAST(c.default_amount AS REAL) AS default_amount, c.is_active, c.sort_order, c.group_name, \
+    c.created_at, c.updated_at, c.deleted_at";
+
+const COUNTER_DATA_COLUMNS: &str =
+    "id, user_id, category_id, date, count, amounts, notes, created_at, updated_at";
+
+/// A category joined with its counters on one day.
+#[derive(sqlx::FromRow)]
+struct CounterRow {
+    #[sqlx(flatten)]
+    category: Category,
+    date: Option<NaiveDate>,
+    count: Option<i32>,
+    amounts: Option<Json<Vec<f64>>>,
+    entry_updated_at: Option<DateTime<Utc>>,
+}
+
+impl CounterRow {
+    fn into_entry(self) -> CategoryCounterData {
+        counter_entry(
+            &self.category,
+            self.count.unwrap_or(0),
+            self.amounts.map(|amounts| amounts.0).unwrap_or_default(),
+            self.entry_updated_at,
+        )
+    }
+}
+
+/// A `counter_data` row, read with `COUNTER_DATA_COLUMNS`.
+#[derive(sqlx::FromRow)]
+struct DayEntry {
+    id: Uuid,
+    user_id: Uuid,
+    category_id: Uuid,
+    date: NaiveDate,
+    count: i32,
+    amounts: Json<Vec<f64>>,
+    notes: Option<String>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl DayEntry {
+    fn to_counter_data(&self) -> CounterData {
+        CounterData {
+            id: self.id,
+            user_id: self.user_id,
+            category_id: self.category_id,
+            date: self.date,
+            count: self.count,
+            amounts: self.amounts.0.clone(),
+            notes: self.notes.clone(),
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+        }
+    }
+
+    /// The row as written to the audit log.
+    fn audit_value(&self) -> Option<Value> {
+        serde_json::to_value(self.to_counter_data()).ok()
+    }
+}
+
+/// A category read from the change feed with its sequence number.
+#[derive(sqlx::FromRow)]
+struct CategoryChange {
+    change_seq: i64,
+    #[sqlx(flatten)]
+    category: Category,
+}
+
+/// A counter entry read from the change feed with its sequence number.
+#[derive(sqlx::FromRow)]
+struct EntryChange {
+    change_seq: i64,
+    #[sqlx(flatten)]
+    entry: DayEntry,
+}
+
+impl EntryChange {
+    fn into_pair(self) -> (i64, CounterData) {
+        (self.change_seq, self.entry.to_counter_data())
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct TombstoneChange {
+    change_seq: i64,
+    #[sqlx(flatten)]
+    tombstone: SyncTombstone,
+}
+
+/// An audit log entry as stored, with its data as JSON TEXT.
+#[derive(sqlx::FromRow)]
+struct AuditRow {
+    id: Uuid,
+    user_id: Uuid,
+    actor_id: Uuid,
+    action: String,
+    entity_type: String,
+    entity_id: Option<Uuid>,
+    session_id: Option<String>,
+    request_id: Option<String>,
+    before_data: Option<Json<Value>>,
+    after_data: Option<Json<Value>>,
+    created_at: DateTime<Utc>,
+}
+
+impl AuditRow {
+    fn into_entry(self) -> AuditLogEntry {
+        AuditLogEntry {
+            id: self.id,
+            user_id: self.user_id,
+            actor_id: self.actor_id,
+            action: self.action,
+            entity_type: self.entity_type,
+            entity_id: self.entity_id,
+            session_id: self.session_id,
+            request_id: self.request_id,
+            before_data: self.before_data.map(|data| data.0),
+            after_data: self.after_data.map(|data| data.0),
+            created_at: self.created_at,
+        }
+    }
+}
+
+/// Storage in a single SQLite file, for self-hosting without Postgres.
+#[derive(Clone)]
+pub struct SqliteRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteRepository {
+    /// Opens (creating if needed) the database at `database_url`, e.g.
+    /// `sqlite://coffee.db` or `sqlite::memory:`.
+    pub async fn connect(database_url: &str) -> Result<Self, sqlx::Error> {
+        let options = SqliteConnectOptions::from_str(database_url)?
+            .create_if_missing(true)
+            .foreign_keys(true)
+            .journal_mode(SqliteJournalMode::Wal);
+
+        // A single connection serialises writers, which is what SQLite wants
+        // anyway, and keeps `sqlite::memory:` to one shared database.
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await?;
+
+        Ok(Self { pool })
+    }
+
+    pub async fn prepare_schema(&self, mode: MigrationMode) -> Result<(), SchemaError> {
+        match mode {
+            MigrationMode::Run => {
+                SQLITE_MIGRATOR.run(&self.pool).await?;
+                Ok(())
+            }
+            MigrationMode::Check => self.check_schema().await,
+        }
+    }
+
+    /// Compares applied migrations against the embedded ones without creating
+    /// or changing anything in the database.
+    pub async fn check_schema(&self) -> Result<(), SchemaError> {
+        let mut conn = self.pool.acquire().await?;
+
+        let has_table: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations')",
+        )
+        .fetch_one(&mut conn)
+        .await?;
+
+        verify_applied(&mut *conn, has_table, &SQLITE_MIGRATOR).await
+    }
+
+    /// Whether the database file can still be read.
+    pub async fn ping(&self) -> Result<(), sqlx::Error> {
+        sqlx::query("SELECT 1").fetch_one(&self.pool).await.map(|_| ())
+    }
+}
+
+fn unique_violation_as_conflict(e: sqlx::Error) -> RepositoryError {
+    match &e {
+        // SQLITE_CONSTRAINT_UNIQUE
+        sqlx::Error::Database(db) if db.code().as_deref() == Some("2067") => {
+            RepositoryError::duplicate_name()
+        }
+        _ => RepositoryError::Database(e),
+    }
+}
+
+/// Appends an entry to the audit log inside the caller's transaction, so the
+/// entry is committed if and only if the mutation it describes is. Without a
+/// request `context` the entry is a system action.
+async fn append_audit_entry(
+    tx: &mut Transaction<'_, Sqlite>,
+    user_id: Uuid,
+    actor_id: Uuid,
+    context: Option<&AuditContext>,
+    event: AuditEvent,
+) -> RepositoryResult<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO audit_log
+            (id, user_id, actor_id, action, entity_type, entity_id, session_id, request_id, before_data, after_data, created_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(actor_id)
+    .bind(event.action)
+    .bind(event.entity_type)
+    .bind(event.entity_id)
+    .bind(context.and_then(|context| context.session_id.as_deref()))
+    .bind(context.map(|context| context.request_id.as_str()))
+    .bind(event.before.map(Json))
+    .bind(event.after.map(Json))
+    .bind(Utc::now())
+    .execute(tx)
+    .await
+    .map_err(RepositoryError::Audit)?;
+
+    Ok(())
+}
+
+async fn record(
+    tx: &mut Transaction<'_, Sqlite>,
+    user: &AuthenticatedUser,
+    context: &AuditContext,
+    event: AuditEvent,
+) -> RepositoryResult<()> {
+    append_audit_entry(tx, user.user_id, user.user_id, Some(context), event).await
+}
+
+async fn fetch_category(
+    tx: &mut Transaction<'_, Sqlite>,
+    user_id: Uuid,
+    id: Uuid,
+    archived: bool,
+) -> RepositoryResult<Category> {
+    sqlx::query_as::<_, Category>(&format!(
+        "SELECT {} FROM categories WHERE id = ? AND user_id = ? AND (deleted_at IS NOT NULL) = ?",
+        CATEGORY_COLUMNS
+    ))
+    .bind(id)
+    .bind(user_id)
+    .bind(archived)
+    .fetch_optional(tx)
+    .await?
+    .ok_or_else(RepositoryError::category_not_found)
+}
+
+/// The user's live, active category: the only kind that can be counted.
+async fn fetch_active_category(
+    tx: &mut Transaction<'_, Sqlite>,
+    user_id: Uuid,
+    id: Uuid,
+) -> RepositoryResult<Category> {
+    fetch_category(tx, user_id, id, false)
+        .await
+        .ok()
+        .filter(|c| c.is_active)
+        .ok_or_else(RepositoryError::category_not_found)
+}
+
+async fn fetch_entry(
+    tx: &mut Transaction<'_, Sqlite>,
+    user_id: Uuid,
+    category_id: Uuid,
+    date: NaiveDate,
+) -> RepositoryResult<Option<DayEntry>> {
+    let entry = sqlx::query_as::<_, DayEntry>(&format!(
+        "SELECT {} FROM counter_data WHERE user_id = ? AND category_id = ? AND date = ?",
+        COUNTER_DATA_COLUMNS
+    ))
+    .bind(user_id)
+    .bind(category_id)
+    .bind(date)
+    .fetch_optional(tx)
+    .await?;
+
+    Ok(entry)
+}
+
+async fn live_categories(
+    tx: &mut Transaction<'_, Sqlite>,
+    user_id: Uuid,
+) -> RepositoryResult<Vec<Category>> {
+    let categories = sqlx::query_as::<_, Category>(&format!(
+        "SELECT {} FROM categories WHERE user_id = ? AND deleted_at IS NULL ORDER BY sort_order ASC, created_at ASC",
+        CATEGORY_COLUMNS
+    ))
+    .bind(user_id)
+    .fetch_all(tx)
+    .await?;
+
+    Ok(categories)
+}
+
+async fn set_deleted_at(
+    tx: &mut Transaction<'_, Sqlite>,
+    user_id: Uuid,
+    id: Uuid,
+    deleted: bool,
+) -> RepositoryResult<Category> {
+    let now = Utc::now();
+
+    sqlx::query_as::<_, Category>(&format!(
+        "UPDATE categories SET deleted_at = ?, updated_at = ? WHERE id = ? AND user_id = ? RETURNING {}",
+        CATEGORY_COLUMNS
+    ))
+    .bind(deleted.then_some(now))
+    .bind(now)
+    .bind(id)
+    .bind(user_id)
+    .fetch_one(tx)
+    .await
+    .map_err(unique_violation_as_conflict)
+}
+
+/// Claims `key` for this request, or returns the result stored by the earlier
+/// request that claimed it.
+async fn claim_idempotency_key(
+    tx: &mut Transaction<'_, Sqlite>,
+    user_id: Uuid,
+    key: &IdempotencyKey,
+) -> RepositoryResult<Option<CategoryCounterData>> {
+    sqlx::query("DELETE FROM idempotency_keys WHERE user_id = ? AND created_at < ?")
+        .bind(user_id)
+        .bind(key.expired_before)
+        .execute(&mut *tx)
+        .await?;
+
+    let claimed = sqlx::query(
+        "INSERT INTO idempotency_keys (user_id, operation, idempotency_key, request_hash, created_at) VALUES (?, ?, ?, ?, ?) ON CONFLICT DO NOTHING",
+    )
+    .bind(user_id)
+    .bind(key.operation)
+    .bind(&key.key)
+    .bind(&key.request_hash)
+    .bind(Utc::now())
+    .execute(&mut *tx)
+    .await?
+    .rows_affected()
+        == 1;
+    if claimed {
+        return Ok(None);
+    }
+
+    let (request_hash, response): (String, Option<Json<CategoryCounterData>>) = sqlx::query_as(
+        "SELECT request_hash, response FROM idempotency_keys WHERE user_id = ? AND operation = ? AND idempotency_key = ?",
+    )
+    .bind(user_id)
+    .bind(key.operation)
+    .bind(&key.key)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if request_hash != key.request_hash {
+        return Err(RepositoryError::IdempotencyKeyReused);
+    }
+    response
+        .map(|response| Some(response.0))
+        .ok_or_else(|| RepositoryError::Conflict("Idempotency key is still in use".to_string()))
+}
+
+async fn store_idempotent_result(
+    tx: &mut Transaction<'_, Sqlite>,
+    user_id: Uuid,
+    key: &IdempotencyKey,
+    entry: &CategoryCounterData,
+) -> RepositoryResult<()> {
+    sqlx::query("UPDATE idempotency_keys SET response = ? WHERE user_id = ? AND operation = ? AND idempotency_key = ?")
+        .bind(Json(entry))
+        .bind(user_id)
+        .bind(key.operation)
+        .bind(&key.key)
+        .execute(tx)
+        .await?;
+
+    Ok(())
+}
+
+#[async_trait]
+impl CategoryRepository for SqliteRepository {
+    async fn list(&self, user_id: Uuid, active_only: bool) -> RepositoryResult<Vec<Category>> {
+        let categories = sqlx::query_as::<_, Category>(&format!(
+            "SELECT {} FROM categories WHERE user_id = ? AND deleted_at IS NULL AND (is_active = 1 OR NOT ?) ORDER BY sort_order ASC, created_at ASC",
+            CATEGORY_COLUMNS
+        ))
+        .bind(user_id)
+        .bind(active_only)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(categories)
+    }
+
+    async fn get(&self, user_id: Uuid, id: Uuid) -> RepositoryResult<Category> {
+        sqlx::query_as::<_, Category>(&format!(
+            "SELECT {} FROM categories WHERE id = ? AND user_id = ? AND deleted_at IS NULL",
+            CATEGORY_COLUMNS
+        ))
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(RepositoryError::category_not_found)
+    }
+
+    async fn create(
+        &self,
+        user: &AuthenticatedUser,
+        context: &AuditContext,
+        request: &CreateCategoryRequest,
+    ) -> RepositoryResult<Category> {
+        let mut tx = self.pool.begin().await?;
+        let now = Utc::now();
+
+        let category = sqlx::query_as::<_, Category>(&format!(
+            r#"
+            INSERT INTO categories (id, user_id, name, icon, color, unit, default_amount, group_name, sort_order, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, NULLIF(?8, ''), (
+                SELECT COALESCE(MAX(sort_order) + 1, 0) FROM categories WHERE user_id = ?2
+            ), ?9, ?9)
+            RETURNING {}
+            "#,
+            CATEGORY_COLUMNS
+        ))
+        .bind(Uuid::new_v4())
+        .bind(user.user_id)
+        .bind(&request.name)
+        .bind(&request.icon)
+        .bind(&request.color)
+        .bind(&request.unit)
+        .bind(request.default_amount)
+        .bind(request.group_name.as_deref())
+        .bind(now)
+        .fetch_one(&mut tx)
+        .await
+        .map_err(unique_violation_as_conflict)?;
+
+        record(
+            &mut tx,
+            user,
+            context,
+            AuditEvent {
+                action: "category.create",
+                entity_type: "category",
+                entity_id: Some(category.id),
+                before: None,
+                after: serde_json::to_value(&category).ok(),
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(category)
+    }
+
+    async fn update(
+        &self,
+        user: &AuthenticatedUser,
+        context: &AuditContext,
+        id: Uuid,
+        request: &UpdateCategoryRequest,
+        precondition: &Precondition,
+    ) -> RepositoryResult<Category> {
+        let mut tx = self.pool.begin().await?;
+
+        let before = fetch_category(&mut tx, user.user_id, id, false).await?;
+        precondition.check(&before)?;
+
+        let category = sqlx::query_as::<_, Category>(&format!(
+            r#"
+            UPDATE categories SET
+                name = COALESCE(?3, name),
+                icon = COALESCE(?4, icon),
+                color = COALESCE(?5, color),
+                unit = COALESCE(?6, unit),
+                default_amount = COALESCE(?7, default_amount),
+                is_active = COALESCE(?8, is_active),
+                group_name = CASE WHEN ?9 IS NULL THEN group_name ELSE NULLIF(?9, '') END,
+                updated_at = ?10
+            WHERE id = ?1 AND user_id = ?2 AND deleted_at IS NULL
+            RETURNING {}
+            "#,
+            CATEGORY_COLUMNS
+        ))
+        .bind(id)
+        .bind(user.user_id)
+        .bind(request.name.as_deref())
+        .bind(request.icon.as_deref())
+        .bind(request.color.as_deref())
+        .bind(request.unit.as_deref())
+        .bind(request.default_amount)
+        .bind(request.is_active)
+        .bind(request.group_name.as_deref())
+        .bind(Utc::now())
+        .fetch_one(&mut tx)
+        .await
+        .map_err(unique_violation_as_conflict)?;
+
+        record(
+            &mut tx,
+            user,
+            context,
+            AuditEvent {
+                action: "category.update",
+                entity_type: "category",
+                entity_id: Some(category.id),
+                before: serde_json::to_value(&before).ok(),
+                after: serde_json::to_value(&category).ok(),
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(category)
+    }
+
+    async fn archive(
+        &self,
+        user: &AuthenticatedUser,
+        context: &AuditContext,
+        id: Uuid,
+        precondition: &Precondition,
+    ) -> RepositoryResult<Category> {
+        let mut tx = self.pool.begin().await?;
+
+        let before = fetch_category(&mut tx, user.user_id, id, false).await?;
+        precondition.check(&before)?;
+        let category = set_deleted_at(&mut tx, user.user_id, id, true).await?;
+
+        record(
+            &mut tx,
+            user,
+            context,
+            AuditEvent {
+                action: "category.archive",
+                entity_type: "category",
+                entity_id: Some(category.id),
+                before: serde_json::to_value(&before).ok(),
+                after: serde_json::to_value(&category).ok(),
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(category)
+    }
+
+    async fn reorder(
+        &self,
+        user: &AuthenticatedUser,
+        context: &AuditContext,
+        positions: &[CategoryPosition],
+    ) -> RepositoryResult<Vec<Category>> {
+        ensure_unique_positions(positions)?;
+
+        let mut tx = self.pool.begin().await?;
+        let before = live_categories(&mut tx, user.user_id).await?;
+
+        if let Some(unknown) = positions
+            .iter()
+            .find(|item| !before.iter().any(|c| c.id == item.id))
+        {
+            return Err(RepositoryError::NotFound(format!(
+                "Category {} not found",
+                unknown.id
+            )));
+        }
+
+        // Listed categories take positions 0..n; the rest follow in their old order
+        let remaining = before
+            .iter()
+            .filter(|c| !positions.iter().any(|item| item.id == c.id))
+            .map(|c| (c.id, c.group_name.as_deref()));
+        let layout = positions
+            .iter()
+            .map(|item| (item.id, item.group_name.as_deref()))
+            .chain(remaining);
+
+        let now = Utc::now();
+        for (position, (id, group_name)) in layout.enumerate() {
+            sqlx::query("UPDATE categories SET sort_order = ?, group_name = ?, updated_at = ? WHERE id = ? AND user_id = ?")
+                .bind(position as i32)
+                .bind(group_name)
+                .bind(now)
+                .bind(id)
+                .bind(user.user_id)
+                .execute(&mut tx)
+                .await?;
+        }
+
+        let categories = live_categories(&mut tx, user.user_id).await?;
+
+        let layout = |categories: &[Category]| {
+            categories
+                .iter()
+                .map(|c| serde_json::json!({ "id": c.id, "sort_order": c.sort_order, "group_name": c.group_name }))
+                .collect::<Vec<_>>()
+        };
+
+        record(
+            &mut tx,
+            user,
+            context,
+            AuditEvent {
+                action: "category.reorder",
+                entity_type: "category",
+                entity_id: None,
+                before: Some(Value::from(layout(&before))),
+                after: Some(Value::from(layout(&categories))),
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(categories)
+    }
+
+    async fn list_trash(&self, user_id: Uuid) -> RepositoryResult<Vec<Category>> {
+        let categories = sqlx::query_as::<_, Category>(&format!(
+            "SELECT {} FROM categories WHERE user_id = ? AND deleted_at IS NOT NULL ORDER BY deleted_at DESC",
+            CATEGORY_COLUMNS
+        ))
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(categories)
+    }
+
+    async fn restore(
+        &self,
+        user: &AuthenticatedUser,
+        context: &AuditContext,
+        id: Uuid,
+    ) -> RepositoryResult<Category> {
+        let mut tx = self.pool.begin().await?;
+
+        let before = fetch_category(&mut tx, user.user_id, id, true).await?;
+        // A live category may have taken the name in the meantime
+        let category = set_deleted_at(&mut tx, user.user_id, id, false).await?;
+
+        record(
+            &mut tx,
+            user,
+            context,
+            AuditEvent {
+                action: "category.restore",
+                entity_type: "category",
+                entity_id: Some(category.id),
+                before: serde_json::to_value(&before).ok(),
+                after: serde_json::to_value(&category).ok(),
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(category)
+    }
+
+    async fn merge(
+        &self,
+        user: &AuthenticatedUser,
+        context: &AuditContext,
+        source_id: Uuid,
+        request: &MergeCategoryRequest,
+    ) -> RepositoryResult<MergeCategoryResult> {
+        if request.target_id == source_id {
+            return Err(RepositoryError::InvalidInput(
+                "Cannot merge a category into itself".to_string(),
+            ));
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        let source = fetch_category(&mut tx, user.user_id, source_id, false).await?;
+        let target = fetch_category(&mut tx, user.user_id, request.target_id, false).await?;
+        let conversion_factor = merge_conversion_factor(&source, &target, request.conversion_factor)?;
+
+        let select = format!(
+            "SELECT {} FROM counter_data WHERE user_id = ? AND category_id = ?",
+            COUNTER_DATA_COLUMNS
+        );
+        let entries = |category_id: Uuid| {
+            sqlx::query_as::<_, DayEntry>(&select)
+                .bind(user.user_id)
+                .bind(category_id)
+        };
+        let source_entries = entries(source.id).fetch_all(&mut tx).await?;
+        let target_entries = entries(target.id).fetch_all(&mut tx).await?;
+
+        let now = Utc::now();
+        let mut moved_days = 0;
+        let mut combined_days = 0;
+
+        for entry in source_entries {
+            let converted = entry
+                .amounts
+                .0
+                .iter()
+                .map(|amount| round_amount(amount * conversion_factor));
+
+            match target_entries.iter().find(|t| t.date == entry.date) {
+                // Days present in both categories are folded into the target entry
+                Some(existing) => {
+                    let amounts: Vec<f64> = existing.amounts.0.iter().copied().chain(converted).collect();
+
+                    sqlx::query("UPDATE counter_data SET count = ?, amounts = ?, notes = ?, updated_at = ? WHERE id = ?")
+                        .bind(existing.count + entry.count)
+                        .bind(Json(amounts))
+                        .bind(join_notes(existing.notes.clone(), entry.notes))
+                        .bind(now)
+                        .bind(existing.id)
+                        .execute(&mut tx)
+                        .await?;
+
+                    sqlx::query("DELETE FROM counter_data WHERE id = ?")
+                        .bind(entry.id)
+                        .execute(&mut tx)
+                        .await?;
+
+                    combined_days += 1;
+                }
+                None => {
+                    sqlx::query("UPDATE counter_data SET category_id = ?, amounts = ?, updated_at = ? WHERE id = ?")
+                        .bind(target.id)
+                        .bind(Json(converted.collect::<Vec<f64>>()))
+                        .bind(now)
+                        .bind(entry.id)
+                        .execute(&mut tx)
+                        .await?;
+
+                    moved_days += 1;
+                }
+            }
+        }
+
+        let archived = set_deleted_at(&mut tx, user.user_id, source.id, true).await?;
+        let result = MergeCategoryResult {
+            source: archived,
+            target,
+            conversion_factor,
+            moved_days,
+            combined_days,
+        };
+
+        record(
+            &mut tx,
+            user,
+            context,
+            AuditEvent {
+                action: "category.merge",
+                entity_type: "category",
+                entity_id: Some(source.id),
+                before: serde_json::to_value(&source).ok(),
+                after: serde_json::to_value(&result).ok(),
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(result)
+    }
+
+    async fn fingerprint(&self, user_id: Uuid) -> RepositoryResult<Fingerprint> {
+        let fingerprint = sqlx::query_as::<_, Fingerprint>(
+            "SELECT COUNT(*) AS rows, MAX(updated_at) AS last_updated, 0 AS counts FROM categories WHERE user_id = ?",
+        )
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(fingerprint)
+    }
+}
+
+#[async_trait]
+impl CounterRepository for SqliteRepository {
+    async fn day(&self, user_id: Uuid, date: NaiveDate) -> RepositoryResult<CounterDataResponseV2> {
+        let rows = sqlx::query_as::<_, CounterRow>(&format!(
+            r#"
+            SELECT {}, cd.date, cd.count, cd.amounts, cd.updated_at AS entry_updated_at
+            FROM categories c
+            LEFT JOIN counter_data cd ON c.id = cd.category_id AND cd.date = ?1 AND cd.user_id = ?2
+            WHERE c.user_id = ?2 AND c.is_active = 1 AND c.deleted_at IS NULL
+            ORDER BY c.sort_order ASC, c.created_at ASC
+            "#,
+            JOINED_CATEGORY_COLUMNS
+        ))
+        .bind(date)
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(CounterDataResponseV2 {
+            date,
+            categories: rows.into_iter().map(CounterRow::into_entry).collect(),
+        })
+    }
+
+    async fn range(
+        &self,
+        user_id: Uuid,
+        start: NaiveDate,
+        end: NaiveDate,
+        include_archived: bool,
+    ) -> RepositoryResult<Vec<CounterDataResponseV2>> {
+        let rows = sqlx::query_as::<_, CounterRow>(&format!(
+            r#"
+            SELECT {}, cd.date, cd.count, cd.amounts, cd.updated_at AS entry_updated_at
+            FROM counter_data cd
+            JOIN categories c ON c.id = cd.category_id
+            WHERE cd.user_id = ? AND cd.date BETWEEN ? AND ? AND c.is_active = 1
+                AND (c.deleted_at IS NULL OR ?)
+            ORDER BY cd.date ASC, c.sort_order ASC, c.created_at ASC
+            "#,
+            JOINED_CATEGORY_COLUMNS
+        ))
+        .bind(user_id)
+        .bind(start)
+        .bind(end)
+        .bind(include_archived)
+        .fetch_all(&self.pool)
+        .await?;
+
+        // Rows arrive grouped by date and in display order within each day
+        Ok(group_by_date(rows.into_iter().filter_map(|row| {
+            let date = row.date?;
+            Some((date, row.into_entry()))
+        })))
+    }
+
+    async fn add(
+        &self,
+        user: &AuthenticatedUser,
+        context: &AuditContext,
+        date: NaiveDate,
+        request: &AddCounterRequest,
+        idempotency: Option<&IdempotencyKey>,
+    ) -> RepositoryResult<Added> {
+        let mut tx = self.pool.begin().await?;
+
+        if let Some(key) = idempotency {
+            if let Some(entry) = claim_idempotency_key(&mut tx, user.user_id, key).await? {
+                return Ok(Added::replayed(entry));
+            }
+        }
+
+        let category = fetch_active_category(&mut tx, user.user_id, request.category_id).await?;
+        let before = fetch_entry(&mut tx, user.user_id, category.id, date).await?;
+
+        let amount = round_amount(request.amount);
+        let now = Utc::now();
+
+        let result = match &before {
+            Some(entry) => {
+                let mut amounts = entry.amounts.0.clone();
+                amounts.push(amount);
+
+                sqlx::query_as::<_, DayEntry>(&format!(
+                    "UPDATE counter_data SET count = ?, amounts = ?, notes = ?, updated_at = ? WHERE id = ? RETURNING {}",
+                    COUNTER_DATA_COLUMNS
+                ))
+                .bind(entry.count + 1)
+                .bind(Json(amounts))
+                .bind(join_notes(entry.notes.clone(), request.notes.clone()))
+                .bind(now)
+                .bind(entry.id)
+                .fetch_one(&mut tx)
+                .await?
+            }
+            None => {
+                sqlx::query_as::<_, DayEntry>(&format!(
+                    r#"
+                    INSERT INTO counter_data (id, user_id, category_id, date, count, amounts, notes, created_at, updated_at)
+                    VALUES (?1, ?2, ?3, ?4, 1, ?5, ?6, ?7, ?7)
+                    RETURNING {}
+                    "#,
+                    COUNTER_DATA_COLUMNS
+                ))
+                .bind(Uuid::new_v4())
+                .bind(user.user_id)
+                .bind(category.id)
+                .bind(date)
+                .bind(Json(vec![amount]))
+                .bind(request.notes.as_deref())
+                .bind(now)
+                .fetch_one(&mut tx)
+                .await?
+            }
+        };
+
+        record(
+            &mut tx,
+            user,
+            context,
+            AuditEvent {
+                action: "counter.add",
+                entity_type: "counter_data",
+                entity_id: Some(result.id),
+                before: before.and_then(|entry| entry.audit_value()),
+                after: result.audit_value(),
+            },
+        )
+        .await?;
+
+        let entry = counter_entry(
+            &category,
+            result.count,
+            result.amounts.0,
+            Some(result.updated_at),
+        );
+        if let Some(key) = idempotency {
+            store_idempotent_result(&mut tx, user.user_id, key, &entry).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(Added::new(entry))
+    }
+
+    async fn set(
+        &self,
+        user: &AuthenticatedUser,
+        context: &AuditContext,
+        date: NaiveDate,
+        request: &SetCounterRequest,
+        precondition: &Precondition,
+        idempotency: Option<&IdempotencyKey>,
+    ) -> RepositoryResult<Added> {
+        let mut tx = self.pool.begin().await?;
+
+        if let Some(key) = idempotency {
+            if let Some(entry) = claim_idempotency_key(&mut tx, user.user_id, key).await? {
+                return Ok(Added::replayed(entry));
+            }
+        }
+
+        let category = fetch_active_category(&mut tx, user.user_id, request.category_id).await?;
+        let before = fetch_entry(&mut tx, user.user_id, category.id, date).await?;
+        precondition.check_entry(&match &before {
+            Some(entry) => counter_entry(
+                &category,
+                entry.count,
+                entry.amounts.0.clone(),
+                Some(entry.updated_at),
+            ),
+            None => counter_entry(&category, 0, Vec::new(), None),
+        })?;
+
+        let amounts: Vec<f64> = request.amounts.iter().copied().map(round_amount).collect();
+        let now = Utc::now();
+
+        let (entry, event) = if amounts.is_empty() {
+            if let Some(entry) = &before {
+                sqlx::query("DELETE FROM counter_data WHERE id = ?")
+                    .bind(entry.id)
+                    .execute(&mut tx)
+                    .await?;
+            }
+
+            let event = before.map(|entry| AuditEvent {
+                action: "counter.delete",
+                entity_type: "counter_data",
+                entity_id: Some(entry.id),
+                before: entry.audit_value(),
+                after: None,
+            });
+            (counter_entry(&category, 0, Vec::new(), None), event)
+        } else {
+            let count = amounts.len() as i32;
+            let result = match &before {
+                Some(entry) => {
+                    sqlx::query_as::<_, DayEntry>(&format!(
+                        "UPDATE counter_data SET count = ?, amounts = ?, notes = ?, updated_at = ? WHERE id = ? RETURNING {}",
+                        COUNTER_DATA_COLUMNS
+                    ))
+                    .bind(count)
+                    .bind(Json(amounts))
+                    .bind(request.notes.clone().or(entry.notes.clone()))
+                    .bind(now)
+                    .bind(entry.id)
+                    .fetch_one(&mut tx)
+                    .await?
+                }
+                None => {
+                    sqlx::query_as::<_, DayEntry>(&format!(
+                        r#"
+                        INSERT INTO counter_data (id, user_id, category_id, date, count, amounts, notes, created_at, updated_at)
+                        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?8)
+                        RETURNING {}
+                        "#,
+                        COUNTER_DATA_COLUMNS
+                    ))
+                    .bind(Uuid::new_v4())
+                    .bind(user.user_id)
+                    .bind(category.id)
+                    .bind(date)
+                    .bind(count)
+                    .bind(Json(amounts))
+                    .bind(request.notes.as_deref())
+                    .bind(now)
+                    .fetch_one(&mut tx)
+                    .await?
+                }
+            };
+
+            let event = AuditEvent {
+                action: "counter.set",
+                entity_type: "counter_data",
+                entity_id: Some(result.id),
+                before: before.and_then(|entry| entry.audit_value()),
+                after: result.audit_value(),
+            };
+            let entry = counter_entry(
+                &category,
+                result.count,
+                result.amounts.0,
+                Some(result.updated_at),
+            );
+            (entry, Some(event))
+        };
+
+        // Deleting a day with nothing logged changes nothing worth recording
+        if let Some(event) = event {
+            record(&mut tx, user, context, event).await?;
+        }
+        if let Some(key) = idempotency {
+            store_idempotent_result(&mut tx, user.user_id, key, &entry).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(Added::new(entry))
+    }
+
+    async fn fingerprint_range(
+        &self,
+        user_id: Uuid,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> RepositoryResult<Fingerprint> {
+        let categories = self.fingerprint(user_id).await?;
+        let counters = sqlx::query_as::<_, Fingerprint>(
+            r#"
+            SELECT COUNT(*) AS rows, MAX(updated_at) AS last_updated, COALESCE(SUM(count), 0) AS counts
+            FROM counter_data WHERE user_id = ? AND date BETWEEN ? AND ?
+            "#,
+        )
+        .bind(user_id)
+        .bind(start)
+        .bind(end)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(categories.combine(counters))
+    }
+}
+
+#[async_trait]
+impl SyncRepository for SqliteRepository {
+    async fn changes(&self, user_id: Uuid, since: i64, limit: usize) -> RepositoryResult<SyncChanges> {
+        // One transaction for a consistent view across the three reads
+        let mut tx = self.pool.begin().await?;
+        let fetch = limit as i64 + 1;
+
+        let categories = sqlx::query_as::<_, CategoryChange>(&format!(
+            "SELECT change_seq, {} FROM categories WHERE user_id = ? AND change_seq > ? ORDER BY change_seq LIMIT ?",
+            CATEGORY_COLUMNS
+        ))
+        .bind(user_id)
+        .bind(since)
+        .bind(fetch)
+        .fetch_all(&mut tx)
+        .await?;
+
+        let counters = sqlx::query_as::<_, EntryChange>(&format!(
+            "SELECT change_seq, {} FROM counter_data WHERE user_id = ? AND change_seq > ? ORDER BY change_seq LIMIT ?",
+            COUNTER_DATA_COLUMNS
+        ))
+        .bind(user_id)
+        .bind(since)
+        .bind(fetch)
+        .fetch_all(&mut tx)
+        .await?;
+
+        let deleted = sqlx::query_as::<_, TombstoneChange>(
+            r#"
+            SELECT change_seq, entity_type, entity_id, category_id, date, deleted_at
+            FROM sync_tombstones WHERE user_id = ? AND change_seq > ? ORDER BY change_seq LIMIT ?
+            "#,
+        )
+        .bind(user_id)
+        .bind(since)
+        .bind(fetch)
+        .fetch_all(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(page_changes(
+            since,
+            limit,
+            categories
+                .into_iter()
+                .map(|change| (change.change_seq, change.category))
+                .collect(),
+            counters.into_iter().map(EntryChange::into_pair).collect(),
+            deleted
+                .into_iter()
+                .map(|change| (change.change_seq, change.tombstone))
+                .collect(),
+        ))
+    }
+}
+
+#[async_trait]
+impl AuditRepository for SqliteRepository {
+    async fn entries(&self, filter: &AuditFilter) -> RepositoryResult<Vec<AuditLogEntry>> {
+        let rows = sqlx::query_as::<_, AuditRow>(
+            r#"
+            SELECT id, user_id, actor_id, action, entity_type, entity_id,
+                   session_id, request_id, before_data, after_data, created_at
+            FROM audit_log
+            WHERE (?1 IS NULL OR user_id = ?1)
+              AND (?2 IS NULL OR entity_type = ?2)
+              AND (?3 IS NULL OR entity_id = ?3)
+              AND (?4 IS NULL OR created_at < ?4)
+            ORDER BY created_at DESC
+            LIMIT ?5
+            "#,
+        )
+        .bind(filter.user_id)
+        .bind(filter.entity_type.as_deref())
+        .bind(filter.entity_id)
+        .bind(filter.before)
+        .bind(filter.limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(AuditRow::into_entry).collect())
+    }
+
+    async fn purge_entries(&self, retention_days: i64) -> RepositoryResult<u64> {
+        let result = sqlx::query("DELETE FROM audit_log WHERE created_at < ?")
+            .bind(Utc::now() - Duration::days(retention_days))
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[async_trait]
+impl TrashRepository for SqliteRepository {
+    async fn purge_trash(&self, retention_days: i64) -> RepositoryResult<u64> {
+        let mut tx = self.pool.begin().await?;
+        let cutoff = Utc::now() - Duration::days(retention_days);
+
+        // Counter history goes with the category, by the foreign key, so it
+        // is read first for the audit log
+        let history = sqlx::query_as::<_, DayEntry>(&format!(
+            "SELECT {} FROM counter_data WHERE category_id IN \
+                (SELECT id FROM categories WHERE deleted_at < ?) ORDER BY date",
+            COUNTER_DATA_COLUMNS
+        ))
+        .bind(cutoff)
+        .fetch_all(&mut tx)
+        .await?;
+
+        let purged = sqlx::query_as::<_, Category>(&format!(
+            "DELETE FROM categories WHERE deleted_at < ? RETURNING {}",
+            CATEGORY_COLUMNS
+        ))
+        .bind(cutoff)
+        .fetch_all(&mut tx)
+        .await?;
+
+        for category in &purged {
+            let counter_data: Vec<Value> = history
+                .iter()
+                .filter(|entry| entry.category_id == category.id)
+                .filter_map(DayEntry::audit_value)
+                .collect();
+            let before = serde_json::to_value(category).ok().map(|mut before| {
+                before["counter_data"] = Value::Array(counter_data);
+                before
+            });
+            let event = AuditEvent {
+                action: "category.purge",
+                entity_type: "category",
+                entity_id: Some(category.id),
+                before,
+                after: None,
+            };
+            append_audit_entry(&mut tx, category.user_id, Uuid::nil(), None, event).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(purged.len() as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::auth::UserInfo;
+
+    async fn repository() -> SqliteRepository {
+        let repository = SqliteRepository::connect("sqlite::memory:").await.unwrap();
+        repository.prepare_schema(MigrationMode::Run).await.unwrap();
+        repository
+    }
+
+    fn user() -> AuthenticatedUser {
+        let user_id = Uuid::new_v4();
+        AuthenticatedUser {
+            user_id,
+            user: UserInfo {
+                id: user_id,
+                email: "test@example.com".to_string(),
+                display_name: None,
+                is_active: true,
+            },
+        }
+    }
+
+    fn context() -> AuditContext {
+        AuditContext {
+            request_id: Uuid::new_v4().to_string(),
+            session_id: None,
+        }
+    }
+
+    fn create_request(name: &str, unit: &str) -> CreateCategoryRequest {
+        CreateCategoryRequest {
+            name: name.to_string(),
+            icon: "coffee".to_string(),
+            color: "#8B4513".to_string(),
+            unit: unit.to_string(),
+            default_amount: 250.0,
+            group_name: None,
+        }
+    }
+
+    fn add_request(category_id: Uuid, amount: f64, notes: Option<&str>) -> AddCounterRequest {
+        AddCounterRequest {
+            category_id,
+            amount,
+            notes: notes.map(str::to_string),
+        }
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 1, day).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_schema_check_after_migrating() {
+        let repo = SqliteRepository::connect("sqlite::memory:").await.unwrap();
+
+        assert!(repo.prepare_schema(MigrationMode::Check).await.is_err());
+        repo.prepare_schema(MigrationMode::Run).await.unwrap();
+        repo.prepare_schema(MigrationMode::Check).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_category_names_are_unique_among_live_categories() {
+        let repo = repository().await;
+        let alice = user();
+
+        let coffee = repo.create(&alice, &context(), &create_request("Coffee", "ml")).await.unwrap();
+        assert_eq!(repo.get(alice.user_id, coffee.id).await.unwrap().name, "Coffee");
+        assert!(matches!(
+            repo.create(&alice, &context(), &create_request("Coffee", "ml")).await,
+            Err(RepositoryError::Conflict(_))
+        ));
+        repo.create(&user(), &context(), &create_request("Coffee", "ml")).await.unwrap();
+
+        repo.archive(&alice, &context(), coffee.id, &Precondition::Any).await.unwrap();
+        repo.create(&alice, &context(), &create_request("Coffee", "ml")).await.unwrap();
+        assert!(matches!(
+            repo.restore(&alice, &context(), coffee.id).await,
+            Err(RepositoryError::Conflict(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_add_upserts_amounts_and_notes() {
+        let repo = repository().await;
+        let alice = user();
+        let coffee = repo.create(&alice, &context(), &create_request("Coffee", "ml")).await.unwrap();
+        let water = repo.create(&alice, &context(), &create_request("Water", "ml")).await.unwrap();
+
+        repo.add(&alice, &context(), date(1), &add_request(coffee.id, 250.0, Some("morning")), None).await.unwrap();
+        let entry = repo
+            .add(&alice, &context(), date(1), &add_request(coffee.id, 99.999, Some("noon")), None)
+            .await
+            .unwrap()
+            .entry;
+        assert_eq!(entry.count, 2);
+        assert_eq!(entry.amounts, vec![250.0, 100.0]);
+
+        let day = repo.day(alice.user_id, date(1)).await.unwrap();
+        assert_eq!(day.categories[0].total_amount, 350.0);
+        assert_eq!(day.categories[1].category_id, water.id);
+        assert_eq!(day.categories[1].count, 0);
+
+        assert!(matches!(
+            repo.add(&user(), &context(), date(1), &add_request(coffee.id, 1.0, None), None).await,
+            Err(RepositoryError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_reorder_and_merge() {
+        let repo = repository().await;
+        let alice = user();
+        let litres = repo.create(&alice, &context(), &create_request("Big coffee", "l")).await.unwrap();
+        let millilitres = repo.create(&alice, &context(), &create_request("Coffee", "ml")).await.unwrap();
+
+        let layout = repo
+            .reorder(
+                &alice,
+                &context(),
+                &[CategoryPosition {
+                    id: millilitres.id,
+                    group_name: Some("Drinks".to_string()),
+                }],
+            )
+            .await
+            .unwrap();
+        assert_eq!(layout[0].id, millilitres.id);
+        assert_eq!(layout[0].group_name.as_deref(), Some("Drinks"));
+        assert_eq!(layout[1].sort_order, 1);
+
+        repo.add(&alice, &context(), date(1), &add_request(litres.id, 0.5, None), None).await.unwrap();
+        repo.add(&alice, &context(), date(2), &add_request(litres.id, 0.25, None), None).await.unwrap();
+        repo.add(&alice, &context(), date(1), &add_request(millilitres.id, 100.0, None), None).await.unwrap();
+
+        let request = MergeCategoryRequest {
+            target_id: millilitres.id,
+            conversion_factor: None,
+        };
+        let result = repo.merge(&alice, &context(), litres.id, &request).await.unwrap();
+        assert_eq!((result.combined_days, result.moved_days), (1, 1));
+
+        let days = repo.range(alice.user_id, date(1), date(31), true).await.unwrap();
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].categories[0].amounts, vec![100.0, 500.0]);
+        assert_eq!(days[1].categories[0].amounts, vec![250.0]);
+        assert_eq!(repo.list_trash(alice.user_id).await.unwrap()[0].id, litres.id);
+    }
+
+    #[tokio::test]
+    async fn test_fingerprint_follows_entries_in_range() {
+        let repo = repository().await;
+        let alice = user();
+        let empty = repo.fingerprint_range(alice.user_id, date(1), date(31)).await.unwrap();
+        assert_eq!(empty, Fingerprint::default());
+
+        let coffee = repo.create(&alice, &context(), &create_request("Coffee", "ml")).await.unwrap();
+        let created = repo.fingerprint_range(alice.user_id, date(1), date(31)).await.unwrap();
+        assert_eq!(created.rows, 1);
+        assert_eq!(created.last_updated, Some(coffee.updated_at));
+
+        repo.add(&alice, &context(), date(15), &add_request(coffee.id, 250.0, None), None).await.unwrap();
+        let logged = repo.fingerprint_range(alice.user_id, date(1), date(31)).await.unwrap();
+        assert_eq!((logged.rows, logged.counts), (2, 1));
+        repo.add(&alice, &context(), date(15), &add_request(coffee.id, 250.0, None), None).await.unwrap();
+        let again = repo.fingerprint_range(alice.user_id, date(1), date(31)).await.unwrap();
+        assert_eq!(again.counts, 2);
+        assert_ne!(again, logged);
+
+        // Days outside the range don't count
+        assert_eq!(
+            repo.fingerprint_range(alice.user_id, date(16), date(31)).await.unwrap().counts,
+            0
+        );
+        assert_eq!(repo.fingerprint(user().user_id).await.unwrap(), Fingerprint::default());
+    }
+
+    #[tokio::test]
+    async fn test_idempotent_add_replays_the_first_result() {
+        let repo = repository().await;
+        let alice = user();
+        let coffee = repo.create(&alice, &context(), &create_request("Coffee", "ml")).await.unwrap();
+        let key = |request_hash: &str| IdempotencyKey {
+            operation: "counters.add",
+            key: "tap-1".to_string(),
+            request_hash: request_hash.to_string(),
+            expired_before: Utc::now() - chrono::Duration::hours(24),
+        };
+
+        let first = repo
+            .add(&alice, &context(), date(1), &add_request(coffee.id, 250.0, None), Some(&key("a")))
+            .await
+            .unwrap();
+        let retry = repo
+            .add(&alice, &context(), date(1), &add_request(coffee.id, 250.0, None), Some(&key("a")))
+            .await
+            .unwrap();
+        assert!(!first.replayed && retry.replayed);
+        assert_eq!(retry.entry.count, 1);
+        assert_eq!(retry.entry.updated_at, first.entry.updated_at);
+        assert_eq!(repo.day(alice.user_id, date(1)).await.unwrap().categories[0].count, 1);
+
+        assert!(matches!(
+            repo.add(&alice, &context(), date(1), &add_request(coffee.id, 100.0, None), Some(&key("b")))
+                .await,
+            Err(RepositoryError::IdempotencyKeyReused)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_set_checks_the_entry_version() {
+        let repo = repository().await;
+        let alice = user();
+        let coffee = repo.create(&alice, &context(), &create_request("Coffee", "ml")).await.unwrap();
+        let set_request = |amounts: Vec<f64>, notes: Option<&str>| SetCounterRequest {
+            category_id: coffee.id,
+            amounts,
+            notes: notes.map(str::to_string),
+        };
+
+        // Nothing logged yet, so no version can match
+        let stale = Precondition::UpdatedAt(vec![coffee.updated_at]);
+        assert!(matches!(
+            repo.set(&alice, &context(), date(1), &set_request(vec![250.0], None), &stale, None).await,
+            Err(RepositoryError::EntryChanged(_))
+        ));
+
+        let added = repo
+            .add(&alice, &context(), date(1), &add_request(coffee.id, 250.0, Some("oat")), None)
+            .await
+            .unwrap()
+            .entry;
+        let read = Precondition::UpdatedAt(vec![added.updated_at.unwrap()]);
+        let edited = repo
+            .set(&alice, &context(), date(1), &set_request(vec![100.004, 50.0], None), &read, None)
+            .await
+            .unwrap()
+            .entry;
+        assert_eq!(edited.amounts, vec![100.0, 50.0]);
+        let day = repo.range(alice.user_id, date(1), date(1), false).await.unwrap();
+        assert_eq!(day[0].categories[0].updated_at, edited.updated_at);
+
+        let deleted = repo
+            .set(&alice, &context(), date(1), &set_request(Vec::new(), None), &Precondition::Any, None)
+            .await
+            .unwrap()
+            .entry;
+        assert_eq!(deleted.count, 0);
+        assert!(repo.range(alice.user_id, date(1), date(1), false).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_changes_include_tombstones() {
+        let repo = repository().await;
+        let alice = user();
+        let coffee = repo.create(&alice, &context(), &create_request("Coffee", "ml")).await.unwrap();
+        let tea = repo.create(&alice, &context(), &create_request("Tea", "ml")).await.unwrap();
+        repo.add(&alice, &context(), date(1), &add_request(coffee.id, 250.0, None), None)
+            .await
+            .unwrap();
+        repo.add(&alice, &context(), date(1), &add_request(tea.id, 200.0, None), None)
+            .await
+            .unwrap();
+
+        let all = repo.changes(alice.user_id, 0, 10).await.unwrap();
+        assert_eq!((all.categories.len(), all.counters.len()), (2, 2));
+        assert_eq!(all.counters[0].amounts, vec![250.0]);
+        assert!(repo.changes(user().user_id, 0, 10).await.unwrap().categories.is_empty());
+
+        // Both days are combined into coffee's entry and tea's row goes away
+        let merge = MergeCategoryRequest {
+            target_id: coffee.id,
+            conversion_factor: None,
+        };
+        repo.merge(&alice, &context(), tea.id, &merge).await.unwrap();
+
+        let later = repo.changes(alice.user_id, all.cursor, 10).await.unwrap();
+        assert_eq!(later.counters.len(), 1);
+        assert_eq!(later.counters[0].amounts, vec![250.0, 200.0]);
+        assert_eq!(later.deleted.len(), 1);
+        assert_eq!(later.deleted[0].category_id, Some(tea.id));
+        assert_eq!(later.categories[0].id, tea.id);
+        assert!(later.categories[0].deleted_at.is_some());
+
+        let page = repo.changes(alice.user_id, all.cursor, 1).await.unwrap();
+        assert!(page.has_more);
+        assert!(page.cursor > all.cursor && page.cursor < later.cursor);
+    }
+
+    #[tokio::test]
+    async fn test_changes_are_audited_with_the_change() {
+        let repo = repository().await;
+        let alice = user();
+        let coffee = repo.create(&alice, &context(), &create_request("Coffee", "ml")).await.unwrap();
+        repo.add(&alice, &context(), date(1), &add_request(coffee.id, 250.0, None), None)
+            .await
+            .unwrap();
+        let set_request = SetCounterRequest {
+            category_id: coffee.id,
+            amounts: Vec::new(),
+            notes: None,
+        };
+        repo.set(&alice, &context(), date(1), &set_request, &Precondition::Any, None)
+            .await
+            .unwrap();
+        repo.archive(&alice, &context(), coffee.id, &Precondition::Any).await.unwrap();
+        repo.create(&user(), &context(), &create_request("Tea", "ml")).await.unwrap();
+
+        let filter = AuditFilter {
+            user_id: Some(alice.user_id),
+            limit: 10,
+            ..Default::default()
+        };
+        let entries = repo.entries(&filter).await.unwrap();
+        let actions: Vec<&str> = entries.iter().map(|entry| entry.action.as_str()).collect();
+        assert_eq!(
+            actions,
+            ["category.archive", "counter.delete", "counter.add", "category.create"]
+        );
+        assert_eq!(entries[0].actor_id, alice.user_id);
+        assert_eq!(entries[1].before_data.as_ref().unwrap()["amounts"], serde_json::json!([250.0]));
+        assert!(entries[1].after_data.is_none());
+        assert_eq!(entries[3].after_data.as_ref().unwrap()["name"], "Coffee");
+
+        let counters = AuditFilter {
+            entity_type: Some("counter_data".to_string()),
+            ..filter.clone()
+        };
+        assert_eq!(repo.entries(&counters).await.unwrap().len(), 2);
+        let everyone = AuditFilter {
+            user_id: None,
+            ..filter
+        };
+        assert_eq!(repo.entries(&everyone).await.unwrap().len(), 5);
+
+        // A failed change leaves no entry behind
+        assert!(repo.create(&alice, &context(), &create_request("Coffee", "ml")).await.is_ok());
+        assert!(repo.create(&alice, &context(), &create_request("Coffee", "ml")).await.is_err());
+        assert_eq!(repo.entries(&everyone).await.unwrap().len(), 6);
+
+        assert!(sqlx::query("UPDATE audit_log SET action = 'forged'")
+            .execute(&repo.pool)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_purges_remove_expired_trash_and_audit_entries() {
+        let repo = repository().await;
+        let alice = user();
+        let coffee = repo.create(&alice, &context(), &create_request("Coffee", "ml")).await.unwrap();
+        let water = repo.create(&alice, &context(), &create_request("Water", "ml")).await.unwrap();
+        repo.add(&alice, &context(), date(1), &add_request(coffee.id, 250.0, None), None)
+            .await
+            .unwrap();
+        repo.archive(&alice, &context(), coffee.id, &Precondition::Any).await.unwrap();
+        let cursor = repo.changes(alice.user_id, 0, 10).await.unwrap().cursor;
+
+        assert_eq!(repo.purge_trash(30).await.unwrap(), 0);
+        assert_eq!(repo.purge_trash(0).await.unwrap(), 1);
+        assert!(repo.list_trash(alice.user_id).await.unwrap().is_empty());
+        assert_eq!(repo.list(alice.user_id, false).await.unwrap()[0].id, water.id);
+
+        // The history goes too, and synced clients hear about both
+        let later = repo.changes(alice.user_id, cursor, 10).await.unwrap();
+        assert_eq!(later.deleted.len(), 2);
+
+        let filter = AuditFilter {
+            entity_id: Some(coffee.id),
+            limit: 10,
+            ..Default::default()
+        };
+        let purge = &repo.entries(&filter).await.unwrap()[0];
+        assert_eq!(purge.action, "category.purge");
+        assert_eq!((purge.user_id, purge.actor_id), (alice.user_id, Uuid::nil()));
+        let before = purge.before_data.as_ref().unwrap();
+        assert_eq!(before["name"], "Coffee");
+        assert_eq!(before["counter_data"][0]["amounts"], serde_json::json!([250.0]));
+        assert!(purge.request_id.is_none());
+
+        assert_eq!(repo.purge_entries(30).await.unwrap(), 0);
+        assert_eq!(repo.purge_entries(0).await.unwrap(), 5);
+        assert!(repo.entries(&filter).await.unwrap().is_empty());
+    }
+}
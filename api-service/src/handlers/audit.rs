@@ -0,0 +1,131 @@
+use axum::{
+    extract::{Extension, Query},
+    http::StatusCode,
+    response::Json,
+    routing::get,
+    Router,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+use utoipa::IntoParams;
+use uuid::Uuid;
+
+use super::repository_error;
+use crate::db::{AuditFilter, DynAuditRepository};
+use crate::models::{ApiResponse, AuditLogEntry};
+use crate::utils::auth::{AdminUser, AuthenticatedUser};
+
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 200;
+
+#[derive(Debug, Deserialize, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditLogQuery {
+    /// Admin only; ignored on the per-user endpoint.
+    pub user_id: Option<Uuid>,
+    pub entity_type: Option<String>,
+    pub entity_id: Option<Uuid>,
+    /// Only return entries created strictly before this instant (for paging).
+    pub before: Option<DateTime<Utc>>,
+    /// Defaults to 50, at most 200.
+    pub limit: Option<i64>,
+}
+
+impl AuditLogQuery {
+    fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+    }
+
+    fn filter(&self, user_id: Option<Uuid>) -> AuditFilter {
+        AuditFilter {
+            user_id,
+            entity_type: self.entity_type.clone(),
+            entity_id: self.entity_id,
+            before: self.before,
+            limit: self.limit(),
+        }
+    }
+}
+
+/// Audit trail of the authenticated user's own data.
+pub fn routes() -> Router {
+    Router::new().route("/", get(get_audit_log))
+}
+
+/// Audit trail across all users, restricted to `ADMIN_EMAILS`.
+pub fn admin_routes() -> Router {
+    Router::new().route("/", get(get_admin_audit_log))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/audit",
+    tag = "audit",
+    params(AuditLogQuery),
+    responses(
+        (status = 200, description = "The user's audit entries, newest first", body = AuditLogResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn get_audit_log(
+    user: AuthenticatedUser,
+    Query(params): Query<AuditLogQuery>,
+    Extension(audit): Extension<DynAuditRepository>,
+) -> Result<Json<ApiResponse<Vec<AuditLogEntry>>>, (StatusCode, Json<Value>)> {
+    let entries = audit
+        .entries(&params.filter(Some(user.user_id)))
+        .await
+        .map_err(repository_error("Failed to fetch audit log"))?;
+
+    Ok(Json(ApiResponse::success("Audit log retrieved successfully", entries)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/audit",
+    tag = "audit",
+    params(AuditLogQuery),
+    responses(
+        (status = 200, description = "Audit entries of all users, newest first", body = AuditLogResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse),
+        (status = 403, description = "Not an admin", body = ErrorResponse)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn get_admin_audit_log(
+    _admin: AdminUser,
+    Query(params): Query<AuditLogQuery>,
+    Extension(audit): Extension<DynAuditRepository>,
+) -> Result<Json<ApiResponse<Vec<AuditLogEntry>>>, (StatusCode, Json<Value>)> {
+    let entries = audit
+        .entries(&params.filter(params.user_id))
+        .await
+        .map_err(repository_error("Failed to fetch audit log"))?;
+
+    Ok(Json(ApiResponse::success("Audit log retrieved successfully", entries)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_log_query_limit_is_clamped() {
+        let query = AuditLogQuery::default();
+        assert_eq!(query.limit(), DEFAULT_LIMIT);
+
+        let query = AuditLogQuery {
+            limit: Some(10_000),
+            ..Default::default()
+        };
+        assert_eq!(query.limit(), MAX_LIMIT);
+
+        let query = AuditLogQuery {
+            limit: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(query.limit(), 1);
+    }
+}
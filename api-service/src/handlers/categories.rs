@@ -1,82 +1,410 @@
-// Simplified categories handler for testing
-use axum::{http::StatusCode, response::Json, routing::get, Router};
-use serde::{Deserialize, Serialize};
+use axum::{
+    extract::{Extension, Path, Query},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    routing::{get, post, put},
+    Router,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use utoipa::IntoParams;
 use uuid::Uuid;
+use validator::Validate;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Category {
-    pub id: Uuid,
-    pub name: String,
-    pub icon: String,
-    pub color: String,
-    pub unit: String,
-    pub default_amount: f64,
-    pub is_active: bool,
-}
+use super::{repository_error, validation_failed};
+use crate::db::DynCategoryRepository;
+use crate::models::{
+    ApiResponse, Category, CreateCategoryRequest, MergeCategoryRequest, MergeCategoryResult,
+    ReorderCategoriesRequest, UpdateCategoryRequest,
+};
+use crate::utils::audit::AuditContext;
+use crate::utils::auth::AuthenticatedUser;
+use crate::utils::etag::{ETag, IfMatch, IfNoneMatch};
+use crate::utils::live::LiveUpdates;
 
-#[derive(Debug, Deserialize)]
-pub struct CreateCategoryRequest {
-    pub name: String,
-    pub icon: String,
-    pub color: String,
-    pub unit: String,
-    pub default_amount: f64,
+#[derive(Debug, Deserialize, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CategoryQuery {
+    /// Leave out inactive categories; defaults to `true`.
+    pub active_only: Option<bool>,
 }
 
-#[derive(Debug, Deserialize, Default)]
-pub struct UpdateCategoryRequest {
-    pub name: Option<String>,
-    pub icon: Option<String>,
-    pub color: Option<String>,
-    pub unit: Option<String>,
-    pub default_amount: Option<f64>,
+pub fn routes() -> Router {
+    Router::new()
+        .route("/", get(get_categories).post(create_category))
+        .route("/order", put(reorder_categories))
+        .route("/trash", get(get_trash))
+        .route("/:id/restore", post(restore_category))
+        .route("/:id/merge", post(merge_category))
+        .route(
+            "/:id",
+            get(get_category).put(update_category).delete(delete_category),
+        )
 }
 
-#[derive(Debug, Deserialize, Default)]
-pub struct CategoryQuery {
-    pub active_only: Option<bool>,
+#[utoipa::path(
+    get,
+    path = "/api/v1/categories",
+    tag = "categories",
+    params(
+        CategoryQuery,
+        ("If-None-Match" = Option<String>, Header, description = "`ETag` of a copy the client already has")
+    ),
+    responses(
+        (status = 200, description = "The user's categories in display order", body = CategoryListResponse),
+        (status = 304, description = "Unchanged since the `ETag` in `If-None-Match`"),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn get_categories(
+    user: AuthenticatedUser,
+    if_none_match: IfNoneMatch,
+    Query(params): Query<CategoryQuery>,
+    Extension(categories): Extension<DynCategoryRepository>,
+) -> Result<Response, (StatusCode, Json<Value>)> {
+    let active_only = params.active_only.unwrap_or(true);
+
+    let fingerprint = categories
+        .fingerprint(user.user_id)
+        .await
+        .map_err(repository_error("Failed to fetch categories"))?;
+    let etag = ETag::new(format_args!(
+        "categories {} {} {:?}",
+        user.user_id, active_only, fingerprint
+    ));
+    if if_none_match.matches(&etag) {
+        return Ok(etag.not_modified());
+    }
+
+    let categories = categories
+        .list(user.user_id, active_only)
+        .await
+        .map_err(repository_error("Failed to fetch categories"))?;
+
+    Ok(etag.attach(
+        Json(ApiResponse::success("Categories retrieved successfully", categories)).into_response(),
+    ))
 }
 
-// Routes function
-pub fn routes() -> Router {
-    Router::new().route("/", get(get_categories).post(create_category))
+#[utoipa::path(
+    get,
+    path = "/api/v1/categories/{id}",
+    tag = "categories",
+    params(("id" = Uuid, Path, description = "Category id")),
+    responses(
+        (status = 200, description = "The category", body = CategoryResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse),
+        (status = 404, description = "No such category", body = ErrorResponse)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn get_category(
+    user: AuthenticatedUser,
+    Path(id): Path<Uuid>,
+    Extension(categories): Extension<DynCategoryRepository>,
+) -> Result<Response, (StatusCode, Json<Value>)> {
+    let category = categories
+        .get(user.user_id, id)
+        .await
+        .map_err(repository_error("Failed to fetch category"))?;
+
+    Ok(respond_with_category(StatusCode::OK, "Category retrieved successfully", category))
 }
 
-// Mock handlers for testing
-pub async fn get_categories() -> Result<Json<Vec<Category>>, StatusCode> {
-    Ok(Json(vec![]))
+/// A single category, tagged with its version for later `If-Match` writes.
+fn respond_with_category(status: StatusCode, message: &str, category: Category) -> Response {
+    let etag = ETag::version(category.updated_at);
+    etag.attach((status, Json(ApiResponse::success(message, category))).into_response())
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/v1/categories",
+    tag = "categories",
+    request_body = CreateCategoryRequest,
+    responses(
+        (status = 201, description = "The new category, placed last", body = CategoryResponse),
+        (status = 400, description = "Validation failed", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse),
+        (status = 409, description = "A category with this name exists", body = ErrorResponse)
+    ),
+    security(("bearer_auth" = []))
+)]
 pub async fn create_category(
+    user: AuthenticatedUser,
+    context: AuditContext,
+    Extension(categories): Extension<DynCategoryRepository>,
     Json(payload): Json<CreateCategoryRequest>,
-) -> Result<(StatusCode, Json<Category>), StatusCode> {
-    let category = Category {
-        id: Uuid::new_v4(),
-        name: payload.name,
-        icon: payload.icon,
-        color: payload.color,
-        unit: payload.unit,
-        default_amount: payload.default_amount,
-        is_active: true,
-    };
-
-    Ok((StatusCode::CREATED, Json(category)))
+) -> Result<Response, (StatusCode, Json<Value>)> {
+    payload.validate().map_err(validation_failed)?;
+
+    let category = categories
+        .create(&user, &context, &payload)
+        .await
+        .map_err(repository_error("Failed to create category"))?;
+
+    Ok(respond_with_category(StatusCode::CREATED, "Category created successfully", category))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/categories/{id}",
+    tag = "categories",
+    params(
+        ("id" = Uuid, Path, description = "Category id"),
+        ("If-Match" = Option<String>, Header, description = "`ETag` of the version being replaced; other versions are rejected with 412")
+    ),
+    request_body = UpdateCategoryRequest,
+    responses(
+        (status = 200, description = "The updated category", body = CategoryResponse),
+        (status = 400, description = "Validation failed", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse),
+        (status = 404, description = "No such category", body = ErrorResponse),
+        (status = 409, description = "A category with this name exists", body = ErrorResponse),
+        (status = 412, description = "Changed since the `If-Match` version; carries the current category", body = CategoryResponse)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn update_category(
+    user: AuthenticatedUser,
+    context: AuditContext,
+    if_match: IfMatch,
+    Path(id): Path<Uuid>,
+    Extension(categories): Extension<DynCategoryRepository>,
+    Extension(live): Extension<LiveUpdates>,
+    Json(payload): Json<UpdateCategoryRequest>,
+) -> Result<Response, (StatusCode, Json<Value>)> {
+    payload.validate().map_err(validation_failed)?;
+
+    let category = categories
+        .update(&user, &context, id, &payload, &if_match.precondition())
+        .await
+        .map_err(repository_error("Failed to update category"))?;
+    live.resync(user.user_id).await;
+
+    Ok(respond_with_category(StatusCode::OK, "Category updated successfully", category))
+}
+
+/// Moves a category to the trash. Its counter history is kept until the
+/// trash retention period expires.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/categories/{id}",
+    tag = "categories",
+    params(
+        ("id" = Uuid, Path, description = "Category id"),
+        ("If-Match" = Option<String>, Header, description = "`ETag` of the version being replaced; other versions are rejected with 412")
+    ),
+    responses(
+        (status = 200, description = "The category, now in the trash", body = CategoryResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse),
+        (status = 404, description = "No such category", body = ErrorResponse),
+        (status = 412, description = "Changed since the `If-Match` version; carries the current category", body = CategoryResponse)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn delete_category(
+    user: AuthenticatedUser,
+    context: AuditContext,
+    if_match: IfMatch,
+    Path(id): Path<Uuid>,
+    Extension(categories): Extension<DynCategoryRepository>,
+    Extension(live): Extension<LiveUpdates>,
+) -> Result<Response, (StatusCode, Json<Value>)> {
+    let category = categories
+        .archive(&user, &context, id, &if_match.precondition())
+        .await
+        .map_err(repository_error("Failed to delete category"))?;
+    live.resync(user.user_id).await;
+
+    Ok(respond_with_category(StatusCode::OK, "Category moved to trash", category))
+}
+
+/// Persists the user's category layout: display position and optional group.
+#[utoipa::path(
+    put,
+    path = "/api/v1/categories/order",
+    tag = "categories",
+    request_body = ReorderCategoriesRequest,
+    responses(
+        (status = 200, description = "All live categories in their new order", body = CategoryListResponse),
+        (status = 400, description = "Validation failed or a category is listed twice", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse),
+        (status = 404, description = "A listed category doesn't exist", body = ErrorResponse)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn reorder_categories(
+    user: AuthenticatedUser,
+    context: AuditContext,
+    Extension(categories): Extension<DynCategoryRepository>,
+    Extension(live): Extension<LiveUpdates>,
+    Json(payload): Json<ReorderCategoriesRequest>,
+) -> Result<Json<ApiResponse<Vec<Category>>>, (StatusCode, Json<Value>)> {
+    payload.validate().map_err(validation_failed)?;
+
+    let categories = categories
+        .reorder(&user, &context, &payload.categories)
+        .await
+        .map_err(repository_error("Failed to reorder categories"))?;
+    live.resync(user.user_id).await;
+
+    Ok(Json(ApiResponse::success("Categories reordered successfully", categories)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/categories/trash",
+    tag = "categories",
+    params(
+        ("If-None-Match" = Option<String>, Header, description = "`ETag` of a copy the client already has")
+    ),
+    responses(
+        (status = 200, description = "Archived categories, most recent first", body = CategoryListResponse),
+        (status = 304, description = "Unchanged since the `ETag` in `If-None-Match`"),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn get_trash(
+    user: AuthenticatedUser,
+    if_none_match: IfNoneMatch,
+    Extension(categories): Extension<DynCategoryRepository>,
+) -> Result<Response, (StatusCode, Json<Value>)> {
+    let fingerprint = categories
+        .fingerprint(user.user_id)
+        .await
+        .map_err(repository_error("Failed to fetch trash"))?;
+    let etag = ETag::new(format_args!("categories/trash {} {:?}", user.user_id, fingerprint));
+    if if_none_match.matches(&etag) {
+        return Ok(etag.not_modified());
+    }
+
+    let categories = categories
+        .list_trash(user.user_id)
+        .await
+        .map_err(repository_error("Failed to fetch trash"))?;
+
+    Ok(etag.attach(
+        Json(ApiResponse::success("Trash retrieved successfully", categories)).into_response(),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/categories/{id}/restore",
+    tag = "categories",
+    params(("id" = Uuid, Path, description = "Id of a category in the trash")),
+    responses(
+        (status = 200, description = "The restored category", body = CategoryResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse),
+        (status = 404, description = "No such category in the trash", body = ErrorResponse),
+        (status = 409, description = "A live category has taken the name", body = ErrorResponse)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn restore_category(
+    user: AuthenticatedUser,
+    context: AuditContext,
+    Path(id): Path<Uuid>,
+    Extension(categories): Extension<DynCategoryRepository>,
+    Extension(live): Extension<LiveUpdates>,
+) -> Result<Response, (StatusCode, Json<Value>)> {
+    let category = categories
+        .restore(&user, &context, id)
+        .await
+        .map_err(repository_error("Failed to restore category"))?;
+    live.resync(user.user_id).await;
+
+    Ok(respond_with_category(StatusCode::OK, "Category restored successfully", category))
+}
+
+/// Moves all counter data from the category in the path into `target_id`,
+/// combining days both categories have entries for, then archives the source.
+#[utoipa::path(
+    post,
+    path = "/api/v1/categories/{id}/merge",
+    tag = "categories",
+    params(("id" = Uuid, Path, description = "Category to merge and archive")),
+    request_body = MergeCategoryRequest,
+    responses(
+        (status = 200, description = "Both categories and what was moved", body = MergeCategoryResponse),
+        (status = 400, description = "Validation failed or merging into itself", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse),
+        (status = 404, description = "Either category doesn't exist", body = ErrorResponse),
+        (status = 422, description = "Units can't be converted without a conversion_factor", body = ErrorResponse)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn merge_category(
+    user: AuthenticatedUser,
+    context: AuditContext,
+    Path(id): Path<Uuid>,
+    Extension(categories): Extension<DynCategoryRepository>,
+    Extension(live): Extension<LiveUpdates>,
+    Json(payload): Json<MergeCategoryRequest>,
+) -> Result<Json<ApiResponse<MergeCategoryResult>>, (StatusCode, Json<Value>)> {
+    payload.validate().map_err(validation_failed)?;
+
+    let result = categories
+        .merge(&user, &context, id, &payload)
+        .await
+        .map_err(repository_error("Failed to merge categories"))?;
+    live.resync(user.user_id).await;
+
+    Ok(Json(ApiResponse::success("Categories merged successfully", result)))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::db::MemoryRepository;
+    use crate::utils::auth::UserInfo;
+    use chrono::Utc;
+    use std::sync::Arc;
+
+    fn user() -> AuthenticatedUser {
+        let user_id = Uuid::new_v4();
+        AuthenticatedUser {
+            user_id,
+            user: UserInfo {
+                id: user_id,
+                email: "test@example.com".to_string(),
+                display_name: None,
+                is_active: true,
+            },
+        }
+    }
+
+    fn context() -> AuditContext {
+        AuditContext {
+            request_id: Uuid::new_v4().to_string(),
+            session_id: None,
+        }
+    }
+
+    async fn body<T: serde::de::DeserializeOwned>(response: Response) -> ApiResponse<T> {
+        let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
 
     #[test]
     fn test_category_creation() {
         let category = Category {
             id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
             name: "Coffee".to_string(),
             icon: "coffee".to_string(),
             color: "#8B4513".to_string(),
             unit: "ml".to_string(),
             default_amount: 250.0,
             is_active: true,
+            sort_order: 0,
+            group_name: Some("Drinks".to_string()),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            deleted_at: None,
         };
 
         assert_eq!(category.name, "Coffee");
@@ -92,10 +420,29 @@ mod tests {
             color: "#90EE90".to_string(),
             unit: "ml".to_string(),
             default_amount: 200.0,
+            group_name: None,
         };
 
         assert_eq!(request.name, "Tea");
         assert_eq!(request.default_amount, 200.0);
+        assert!(request.validate().is_ok());
+    }
+
+    #[test]
+    fn test_create_category_request_validation() {
+        let request = CreateCategoryRequest {
+            name: String::new(),
+            icon: "tea".to_string(),
+            color: "#90EE90".to_string(),
+            unit: "ml".to_string(),
+            default_amount: 0.0,
+            group_name: None,
+        };
+
+        let errors = request.validate().unwrap_err();
+        let fields = errors.field_errors();
+        assert!(fields.contains_key("name"));
+        assert!(fields.contains_key("default_amount"));
     }
 
     #[test]
@@ -106,6 +453,8 @@ mod tests {
             color: Some("#654321".to_string()),
             unit: None,
             default_amount: Some(300.0),
+            is_active: None,
+            group_name: Some(String::new()),
         };
 
         assert_eq!(request.name, Some("Updated Coffee".to_string()));
@@ -114,6 +463,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn test_category_query_defaults() {
         let query = CategoryQuery { active_only: None };
         assert_eq!(query.active_only.unwrap_or(true), true);
@@ -126,11 +476,18 @@ mod tests {
 
     #[tokio::test]
     async fn test_get_categories_handler() {
-        let result = get_categories().await;
+        let categories: DynCategoryRepository = Arc::new(MemoryRepository::new());
+        let result = get_categories(
+            user(),
+            IfNoneMatch::default(),
+            Query(CategoryQuery { active_only: None }),
+            Extension(categories),
+        )
+        .await;
         assert!(result.is_ok());
 
-        let categories = result.unwrap().0;
-        assert_eq!(categories.len(), 0); // Empty mock response
+        let response = body::<Vec<Category>>(result.unwrap()).await;
+        assert_eq!(response.data.unwrap().len(), 0); // Nothing created yet
     }
 
     #[tokio::test]
@@ -141,13 +498,16 @@ mod tests {
             color: "#FF0000".to_string(),
             unit: "units".to_string(),
             default_amount: 1.0,
+            group_name: None,
         };
 
-        let result = create_category(Json(request)).await;
+        let categories: DynCategoryRepository = Arc::new(MemoryRepository::new());
+        let result = create_category(user(), context(), Extension(categories), Json(request)).await;
         assert!(result.is_ok());
 
-        let (status, category) = result.unwrap();
-        assert_eq!(status, StatusCode::CREATED);
+        let response = result.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let category = body::<Category>(response).await.data.unwrap();
         assert_eq!(category.name, "Test Category");
         assert_eq!(category.default_amount, 1.0);
         assert!(category.is_active);
@@ -1,339 +1,590 @@
 use axum::{
     extract::{Extension, Path, Query},
-    http::StatusCode,
-    response::Json,
-    routing::get,
+    http::{HeaderValue, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Json, Response,
+    },
+    routing::{get, post},
     Router,
 };
-use chrono::NaiveDate;
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::Deserialize;
-use serde_json::Value;
-use sqlx::PgPool;
-use std::collections::HashMap;
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use tokio_stream::StreamExt;
+use utoipa::IntoParams;
 use uuid::Uuid;
 use validator::Validate;
 
-use crate::models::{AddCounterRequest, ApiResponse, CategoryCounterData, CounterDataResponse};
+use super::{repository_error, validation_failed};
+use crate::db::{Added, DynCounterRepository, Precondition};
+use crate::models::{
+    AddCounterRequest, ApiResponse, CategoryCounterData, CounterBatchRequest, CounterBatchResult,
+    CounterChange, CounterDataResponse, CounterDataResponseV2, CounterOperation,
+    CounterOperationResult, SetCounterRequest,
+};
+use crate::utils::audit::AuditContext;
 use crate::utils::auth::AuthenticatedUser;
-
-#[derive(Debug, Deserialize)]
+use crate::utils::etag::{ETag, IfMatch, IfNoneMatch};
+use crate::utils::idempotency::{IdempotencyKeyHeader, IdempotencySettings, IDEMPOTENT_REPLAYED};
+use crate::utils::live::{LiveEvent, LiveUpdates};
+use crate::utils::metrics::Metrics;
+use crate::utils::version::ApiVersion;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct GetCounterQuery {
+    /// Defaults to today (UTC).
     pub date: Option<NaiveDate>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct GetCounterRangeQuery {
     pub start_date: NaiveDate,
     pub end_date: NaiveDate,
+    /// Include history of categories that have since been moved to the trash.
+    pub include_archived: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeleteCounterQuery {
+    /// The category whose entry that day is removed.
+    pub category_id: Uuid,
 }
 
 pub fn routes() -> Router {
     Router::new()
         .route("/", get(get_counter_data).post(add_counter_data))
         .route("/range", get(get_counter_range))
-        .route("/:date", get(get_counter_data_by_date))
+        .route("/batch", post(apply_counter_batch))
+        .route("/live", get(stream_counter_updates))
+        .route(
+            "/:date",
+            get(get_counter_data_by_date)
+                .put(set_counter_data)
+                .delete(delete_counter_data),
+        )
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/counters",
+    tag = "counters",
+    params(
+        GetCounterQuery,
+        ("If-None-Match" = Option<String>, Header, description = "`ETag` of a copy the client already has")
+    ),
+    responses(
+        (status = 200, description = "Every active category with its counts that day; a `CounterDayResponseV2` under `/api/v2`", body = CounterDayResponse),
+        (status = 304, description = "Unchanged since the `ETag` in `If-None-Match`"),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse)
+    ),
+    security(("bearer_auth" = []))
+)]
 pub async fn get_counter_data(
     user: AuthenticatedUser,
+    version: ApiVersion,
+    if_none_match: IfNoneMatch,
     Query(params): Query<GetCounterQuery>,
-    Extension(pool): Extension<PgPool>,
-) -> Result<Json<ApiResponse<CounterDataResponse>>, (StatusCode, Json<Value>)> {
+    Extension(counters): Extension<DynCounterRepository>,
+) -> Result<Response, (StatusCode, Json<Value>)> {
     let date = params.date.unwrap_or_else(|| chrono::Utc::now().date_naive());
-    
-    get_counter_data_for_date(user, date, pool).await
+
+    day_response(&user, version, if_none_match, date, counters).await
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/counters/{date}",
+    tag = "counters",
+    params(
+        ("date" = NaiveDate, Path, description = "Day to read, `YYYY-MM-DD`"),
+        ("If-None-Match" = Option<String>, Header, description = "`ETag` of a copy the client already has")
+    ),
+    responses(
+        (status = 200, description = "Every active category with its counts that day; a `CounterDayResponseV2` under `/api/v2`", body = CounterDayResponse),
+        (status = 304, description = "Unchanged since the `ETag` in `If-None-Match`"),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse)
+    ),
+    security(("bearer_auth" = []))
+)]
 pub async fn get_counter_data_by_date(
     user: AuthenticatedUser,
+    version: ApiVersion,
+    if_none_match: IfNoneMatch,
     Path(date): Path<NaiveDate>,
-    Extension(pool): Extension<PgPool>,
-) -> Result<Json<ApiResponse<CounterDataResponse>>, (StatusCode, Json<Value>)> {
-    get_counter_data_for_date(user, date, pool).await
+    Extension(counters): Extension<DynCounterRepository>,
+) -> Result<Response, (StatusCode, Json<Value>)> {
+    day_response(&user, version, if_none_match, date, counters).await
 }
 
-async fn get_counter_data_for_date(
-    user: AuthenticatedUser,
+/// A day's counters, or `304 Not Modified` if the client's copy is current.
+async fn day_response(
+    user: &AuthenticatedUser,
+    version: ApiVersion,
+    if_none_match: IfNoneMatch,
     date: NaiveDate,
-    pool: PgPool,
-) -> Result<Json<ApiResponse<CounterDataResponse>>, (StatusCode, Json<Value>)> {
-    #[derive(sqlx::FromRow)]
-    struct CategoryDataRow {
-        category_id: Uuid,
-        name: String,
-        icon: String,
-        color: String,
-        unit: String,
-        count: Option<i32>,
-        amounts: Option<Vec<f64>>,
+    counters: DynCounterRepository,
+) -> Result<Response, (StatusCode, Json<Value>)> {
+    let fingerprint = counters
+        .fingerprint_range(user.user_id, date, date)
+        .await
+        .map_err(repository_error("Failed to fetch counter data"))?;
+    let etag = ETag::new(format_args!(
+        "counters/day {} {:?} {} {:?}",
+        user.user_id, version, date, fingerprint
+    ));
+    if if_none_match.matches(&etag) {
+        return Ok(etag.not_modified());
     }
 
-    let data = sqlx::query_as::<_, CategoryDataRow>(
-        r#"
-        SELECT 
-            c.id as category_id,
-            c.name,
-            c.icon,
-            c.color,
-            c.unit,
-            cd.count,
-            cd.amounts
-        FROM categories c
-        LEFT JOIN counter_data cd ON c.id = cd.category_id AND cd.date = $1 AND cd.user_id = $2
-        WHERE c.user_id = $2 AND c.is_active = true
-        ORDER BY c.created_at ASC
-        "#
-    )
-    .bind(date)
-    .bind(user.user_id)
-    .fetch_all(&pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Database error: {}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({
-                "success": false,
-                "message": "Failed to fetch counter data"
-            }))
-        )
-    })?;
+    let day = counters
+        .day(user.user_id, date)
+        .await
+        .map_err(repository_error("Failed to fetch counter data"))?;
 
-    let mut categories = HashMap::new();
-
-    for row in data {
-        let amounts: Vec<f64> = row.amounts
-            .unwrap_or_default();
-
-        let total_amount: f64 = amounts.iter().sum();
-
-        categories.insert(
-            row.name.clone(),
-            CategoryCounterData {
-                category_id: row.category_id,
-                name: row.name,
-                icon: row.icon,
-                color: row.color,
-                unit: row.unit,
-                count: row.count.unwrap_or(0),
-                amounts,
-                total_amount,
-            },
-        );
-    }
-
-    let response = CounterDataResponse { date, categories };
+    Ok(etag.attach(respond_with_day(version, day)))
+}
 
-    Ok(Json(ApiResponse::success("Counter data retrieved successfully", response)))
+fn respond_with_day(version: ApiVersion, day: CounterDataResponseV2) -> Response {
+    let message = "Counter data retrieved successfully";
+    match version {
+        ApiVersion::V1 => {
+            version.respond(ApiResponse::success(message, CounterDataResponse::from(day)))
+        }
+        ApiVersion::V2 => version.respond(ApiResponse::success(message, day)),
+    }
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/v1/counters",
+    tag = "counters",
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Makes the request safe to retry: a repeat with the same key returns the first response, marked `Idempotent-Replayed: true`, instead of logging again")
+    ),
+    request_body = AddCounterRequest,
+    responses(
+        (status = 200, description = "The category's counts for today", body = CounterEntryResponse),
+        (status = 400, description = "Validation failed", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse),
+        (status = 404, description = "No such active category", body = ErrorResponse),
+        (status = 422, description = "The `Idempotency-Key` was used for a different request", body = ErrorResponse)
+    ),
+    security(("bearer_auth" = []))
+)]
+#[allow(clippy::too_many_arguments)]
 pub async fn add_counter_data(
     user: AuthenticatedUser,
-    Extension(pool): Extension<PgPool>,
+    context: AuditContext,
+    idempotency_key: IdempotencyKeyHeader,
+    Extension(counters): Extension<DynCounterRepository>,
+    Extension(metrics): Extension<Metrics>,
+    Extension(idempotency): Extension<IdempotencySettings>,
+    Extension(live): Extension<LiveUpdates>,
     Json(payload): Json<AddCounterRequest>,
-) -> Result<Json<ApiResponse<CategoryCounterData>>, (StatusCode, Json<Value>)> {
+) -> Result<Response, (StatusCode, Json<Value>)> {
     // Validate input
-    if let Err(errors) = payload.validate() {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(serde_json::json!({
-                "success": false,
-                "message": "Validation failed",
-                "errors": errors
-            }))
-        ));
-    }
+    payload.validate().map_err(validation_failed)?;
 
-    #[derive(sqlx::FromRow)]
-    struct CategoryInfo {
-        name: String,
-        icon: String,
-        color: String,
-        unit: String,
-    }
-
-    // Verify category belongs to user
-    let category = sqlx::query_as::<_, CategoryInfo>(
-        "SELECT name, icon, color, unit FROM categories WHERE id = $1 AND user_id = $2 AND is_active = true"
-    )
-    .bind(payload.category_id)
-    .bind(user.user_id)
-    .fetch_optional(&pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Database error: {}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({
-                "success": false,
-                "message": "Failed to verify category"
-            }))
-        )
-    })?;
+    let today = chrono::Utc::now().date_naive();
+    let key = idempotency_key.bind("counters.add", &payload, &idempotency);
 
-    let category = category.ok_or_else(|| {
-        (
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({
-                "success": false,
-                "message": "Category not found"
-            }))
-        )
-    })?;
+    let added = counters
+        .add(&user, &context, today, &payload, key.as_ref())
+        .await
+        .map_err(repository_error("Failed to add counter data"))?;
 
-    let today = chrono::Utc::now().date_naive();
-    let amount_decimal = payload.amount;
+    if !added.replayed {
+        metrics.entry_logged(&added.entry.unit, payload.amount);
+        live.publish(user.user_id, today, added.entry.clone()).await;
+    }
 
-    #[derive(sqlx::FromRow)]
-    struct CounterResult {
-        count: i32,
-        amounts: Vec<f64>,
+    let mut response =
+        Json(ApiResponse::success("Counter data added successfully", added.entry)).into_response();
+    if added.replayed {
+        response
+            .headers_mut()
+            .insert(IDEMPOTENT_REPLAYED, HeaderValue::from_static("true"));
     }
 
-    // Insert or update counter data
-    let result = sqlx::query_as::<_, CounterResult>(
-        r#"
-        INSERT INTO counter_data (user_id, category_id, date, count, amounts, notes)
-        VALUES ($1, $2, $3, 1, ARRAY[$4], $5)
-        ON CONFLICT (user_id, category_id, date)
-        DO UPDATE SET 
-            count = counter_data.count + 1,
-            amounts = array_append(counter_data.amounts, $4),
-            notes = CASE 
-                WHEN $5 IS NOT NULL THEN 
-                    CASE 
-                        WHEN counter_data.notes IS NULL THEN $5
-                        ELSE counter_data.notes || E'\n' || $5
-                    END
-                ELSE counter_data.notes
-            END,
-            updated_at = NOW()
-        RETURNING count, amounts
-        "#
-    )
-    .bind(user.user_id)
-    .bind(payload.category_id)
-    .bind(today)
-    .bind(amount_decimal)
-    .bind(payload.notes.as_deref())
-    .fetch_one(&pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Database error: {}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({
-                "success": false,
-                "message": "Failed to add counter data"
-            }))
-        )
-    })?;
+    Ok(response)
+}
 
-    let amounts: Vec<f64> = result.amounts;
+/// A category's entry on one day, tagged with its version for `If-Match`.
+fn respond_with_entry(message: &str, entry: CategoryCounterData) -> Response {
+    let etag = entry.updated_at.map(ETag::version);
+    let response = Json(ApiResponse::success(message, entry)).into_response();
+    match etag {
+        Some(etag) => etag.attach(response),
+        None => response,
+    }
+}
 
-    let total_amount: f64 = amounts.iter().sum();
+/// Replaces a category's servings on one day.
+#[utoipa::path(
+    put,
+    path = "/api/v1/counters/{date}",
+    tag = "counters",
+    params(
+        ("date" = NaiveDate, Path, description = "Day to edit, `YYYY-MM-DD`"),
+        ("If-Match" = Option<String>, Header, description = "`ETag` of the entry version being replaced; other versions are rejected with 412")
+    ),
+    request_body = SetCounterRequest,
+    responses(
+        (status = 200, description = "The category's counts that day", body = CounterEntryResponse),
+        (status = 400, description = "Validation failed", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse),
+        (status = 404, description = "No such active category", body = ErrorResponse),
+        (status = 412, description = "Changed since the `If-Match` version; carries the current entry", body = CounterEntryResponse)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn set_counter_data(
+    user: AuthenticatedUser,
+    context: AuditContext,
+    if_match: IfMatch,
+    Path(date): Path<NaiveDate>,
+    Extension(counters): Extension<DynCounterRepository>,
+    Extension(live): Extension<LiveUpdates>,
+    Json(payload): Json<SetCounterRequest>,
+) -> Result<Response, (StatusCode, Json<Value>)> {
+    payload.validate().map_err(validation_failed)?;
+
+    let set = counters
+        .set(&user, &context, date, &payload, &if_match.precondition(), None)
+        .await
+        .map_err(repository_error("Failed to update counter data"))?;
+    live.publish(user.user_id, date, set.entry.clone()).await;
+
+    Ok(respond_with_entry("Counter data updated successfully", set.entry))
+}
 
-    let response = CategoryCounterData {
-        category_id: payload.category_id,
-        name: category.name,
-        icon: category.icon,
-        color: category.color,
-        unit: category.unit,
-        count: result.count,
-        amounts,
-        total_amount,
+/// Removes a category's entry on one day.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/counters/{date}",
+    tag = "counters",
+    params(
+        ("date" = NaiveDate, Path, description = "Day to clear, `YYYY-MM-DD`"),
+        DeleteCounterQuery,
+        ("If-Match" = Option<String>, Header, description = "`ETag` of the entry version being removed; other versions are rejected with 412")
+    ),
+    responses(
+        (status = 200, description = "The category's counts that day, now zero", body = CounterEntryResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse),
+        (status = 404, description = "No such active category", body = ErrorResponse),
+        (status = 412, description = "Changed since the `If-Match` version; carries the current entry", body = CounterEntryResponse)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn delete_counter_data(
+    user: AuthenticatedUser,
+    context: AuditContext,
+    if_match: IfMatch,
+    Path(date): Path<NaiveDate>,
+    Query(params): Query<DeleteCounterQuery>,
+    Extension(counters): Extension<DynCounterRepository>,
+    Extension(live): Extension<LiveUpdates>,
+) -> Result<Response, (StatusCode, Json<Value>)> {
+    let request = SetCounterRequest {
+        category_id: params.category_id,
+        amounts: Vec::new(),
+        notes: None,
     };
 
-    Ok(Json(ApiResponse::success("Counter data added successfully", response)))
+    let deleted = counters
+        .set(&user, &context, date, &request, &if_match.precondition(), None)
+        .await
+        .map_err(repository_error("Failed to delete counter data"))?;
+    live.publish(user.user_id, date, deleted.entry.clone()).await;
+
+    Ok(respond_with_entry("Counter data deleted successfully", deleted.entry))
 }
 
-pub async fn get_counter_range(
+#[utoipa::path(
+    post,
+    path = "/api/v1/counters/batch",
+    tag = "counters",
+    request_body = CounterBatchRequest,
+    responses(
+        (status = 200, description = "Each operation's result, in request order, and the days they touched. Operations succeed or fail on their own, so check each result", body = CounterBatchResponse),
+        (status = 400, description = "No operations, more than 100, or a malformed one", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn apply_counter_batch(
     user: AuthenticatedUser,
-    Query(params): Query<GetCounterRangeQuery>,
-    Extension(pool): Extension<PgPool>,
-) -> Result<Json<ApiResponse<Vec<CounterDataResponse>>>, (StatusCode, Json<Value>)> {
-    #[derive(sqlx::FromRow)]
-    struct CounterRangeRow {
-        date: NaiveDate,
-        category_id: Uuid,
-        name: String,
-        icon: String,
-        color: String,
-        unit: String,
-        count: i32,
-        amounts: Vec<f64>,
+    context: AuditContext,
+    Extension(counters): Extension<DynCounterRepository>,
+    Extension(metrics): Extension<Metrics>,
+    Extension(idempotency): Extension<IdempotencySettings>,
+    Extension(live): Extension<LiveUpdates>,
+    Json(payload): Json<CounterBatchRequest>,
+) -> Result<Json<ApiResponse<CounterBatchResult>>, (StatusCode, Json<Value>)> {
+    payload.validate().map_err(validation_failed)?;
+
+    // Queued changes are applied in the order they were made, whatever order
+    // they were sent in
+    let mut operations: Vec<(usize, &CounterOperation)> =
+        payload.operations.iter().enumerate().collect();
+    operations.sort_by_key(|(_, operation)| operation.recorded_at);
+
+    let mut results = Vec::with_capacity(operations.len());
+    for (index, operation) in operations {
+        let applied = apply_operation(&user, &context, &counters, &idempotency, operation).await;
+        if let Ok(added) = &applied {
+            if !added.replayed {
+                if let CounterChange::Add { amount, .. } = &operation.change {
+                    metrics.entry_logged(&added.entry.unit, *amount);
+                }
+                let date = operation.change.date(operation.recorded_at);
+                live.publish(user.user_id, date, added.entry.clone()).await;
+            }
+        }
+        results.push((index, operation_result(operation.id, applied)));
+    }
+    results.sort_by_key(|(index, _)| *index);
+
+    let dates: BTreeSet<NaiveDate> = payload
+        .operations
+        .iter()
+        .map(|operation| operation.change.date(operation.recorded_at))
+        .collect();
+    let mut days = Vec::with_capacity(dates.len());
+    for date in dates {
+        days.push(
+            counters
+                .day(user.user_id, date)
+                .await
+                .map_err(repository_error("Failed to fetch counter data"))?,
+        );
+    }
+
+    Ok(Json(ApiResponse::success(
+        "Counter operations processed",
+        CounterBatchResult {
+            results: results.into_iter().map(|(_, result)| result).collect(),
+            days,
+        },
+    )))
+}
+
+/// Applies one operation as its own request would have been, keyed by its
+/// id so that sending it again replays the first result.
+async fn apply_operation(
+    user: &AuthenticatedUser,
+    context: &AuditContext,
+    counters: &DynCounterRepository,
+    idempotency: &IdempotencySettings,
+    operation: &CounterOperation,
+) -> Result<Added, (StatusCode, Json<Value>)> {
+    let key = idempotency.key(operation.id.to_string(), "counters.batch", operation);
+    let date = operation.change.date(operation.recorded_at);
+    let failed = repository_error("Failed to apply counter operation");
+
+    match &operation.change {
+        CounterChange::Add {
+            category_id,
+            amount,
+            notes,
+        } => {
+            let request = AddCounterRequest {
+                category_id: *category_id,
+                amount: *amount,
+                notes: notes.clone(),
+            };
+            request.validate().map_err(validation_failed)?;
+
+            counters
+                .add(user, context, date, &request, Some(&key))
+                .await
+                .map_err(failed)
+        }
+        CounterChange::Edit {
+            category_id,
+            amounts,
+            notes,
+            if_updated_at,
+            ..
+        } => {
+            let request = SetCounterRequest {
+                category_id: *category_id,
+                amounts: amounts.clone(),
+                notes: notes.clone(),
+            };
+            request.validate().map_err(validation_failed)?;
+
+            counters
+                .set(user, context, date, &request, &precondition(*if_updated_at), Some(&key))
+                .await
+                .map_err(failed)
+        }
+        CounterChange::Delete {
+            category_id,
+            if_updated_at,
+            ..
+        } => {
+            let request = SetCounterRequest {
+                category_id: *category_id,
+                amounts: Vec::new(),
+                notes: None,
+            };
+
+            counters
+                .set(user, context, date, &request, &precondition(*if_updated_at), Some(&key))
+                .await
+                .map_err(failed)
+        }
+    }
+}
+
+fn precondition(if_updated_at: Option<DateTime<Utc>>) -> Precondition {
+    if_updated_at.map_or(Precondition::Any, |version| {
+        Precondition::UpdatedAt(vec![version])
+    })
+}
+
+/// Reports an operation's outcome with the status and body its own request
+/// would have had.
+fn operation_result(
+    id: Uuid,
+    applied: Result<Added, (StatusCode, Json<Value>)>,
+) -> CounterOperationResult {
+    match applied {
+        Ok(added) => CounterOperationResult {
+            id,
+            success: true,
+            status: StatusCode::OK.as_u16(),
+            message: if added.replayed {
+                "Operation was already applied".to_string()
+            } else {
+                "Operation applied".to_string()
+            },
+            replayed: added.replayed,
+            data: Some(added.entry),
+            errors: None,
+        },
+        Err((status, Json(mut body))) => CounterOperationResult {
+            id,
+            success: false,
+            status: status.as_u16(),
+            message: body["message"].as_str().unwrap_or_default().to_string(),
+            replayed: false,
+            data: serde_json::from_value(body["data"].take()).unwrap_or_default(),
+            errors: body.get_mut("errors").map(Value::take),
+        },
     }
+}
 
-    let data = sqlx::query_as::<_, CounterRangeRow>(
-        r#"
-        SELECT 
-            cd.date,
-            c.id as category_id,
-            c.name,
-            c.icon,
-            c.color,
-            c.unit,
-            cd.count,
-            cd.amounts
-        FROM counter_data cd
-        JOIN categories c ON c.id = cd.category_id
-        WHERE cd.user_id = $1 AND cd.date BETWEEN $2 AND $3 AND c.is_active = true
-        ORDER BY cd.date ASC, c.created_at ASC
-        "#
-    )
-    .bind(user.user_id)
-    .bind(params.start_date)
-    .bind(params.end_date)
-    .fetch_all(&pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Database error: {}", e);
+#[utoipa::path(
+    get,
+    path = "/api/v1/counters/live",
+    tag = "counters",
+    responses(
+        (status = 200, description = "Server-sent events: a `counter` event with a `CounterUpdate` whenever one of the user's counts changes, and a `resync` event when the client should refetch: some were missed, or a category change altered its days", content_type = "text/event-stream", body = CounterUpdate),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse),
+        (status = 503, description = "Updates can't be received right now", body = ErrorResponse)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn stream_counter_updates(
+    user: AuthenticatedUser,
+    Extension(live): Extension<LiveUpdates>,
+) -> Result<Response, (StatusCode, Json<Value>)> {
+    let updates = live.subscribe(user.user_id).await.map_err(|e| {
+        tracing::error!("Failed to subscribe to counter updates: {}", e);
         (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({
                 "success": false,
-                "message": "Failed to fetch counter range data"
-            }))
+                "message": "Live updates are unavailable"
+            })),
         )
     })?;
 
-    let mut response_map: HashMap<NaiveDate, HashMap<String, CategoryCounterData>> = HashMap::new();
-
-    for row in data {
-        let amounts: Vec<f64> = row.amounts;
+    let events = updates.map(|event| match event {
+        LiveEvent::Changed(update) => Event::default().event("counter").json_data(update),
+        LiveEvent::Resync => Ok(Event::default().event("resync").data("")),
+    });
 
-        let total_amount: f64 = amounts.iter().sum();
+    let mut response = Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response();
+    // Tells nginx to pass events on as they come instead of buffering them
+    response
+        .headers_mut()
+        .insert("x-accel-buffering", HeaderValue::from_static("no"));
 
-        let category_data = CategoryCounterData {
-            category_id: row.category_id,
-            name: row.name.clone(),
-            icon: row.icon,
-            color: row.color,
-            unit: row.unit,
-            count: row.count,
-            amounts,
-            total_amount,
-        };
+    Ok(response)
+}
 
-        response_map
-            .entry(row.date)
-            .or_insert_with(HashMap::new)
-            .insert(row.name, category_data);
+#[utoipa::path(
+    get,
+    path = "/api/v1/counters/range",
+    tag = "counters",
+    params(
+        GetCounterRangeQuery,
+        ("If-None-Match" = Option<String>, Header, description = "`ETag` of a copy the client already has")
+    ),
+    responses(
+        (status = 200, description = "Days with any entries, oldest first; a `CounterRangeResponseV2` under `/api/v2`", body = CounterRangeResponse),
+        (status = 304, description = "Unchanged since the `ETag` in `If-None-Match`"),
+        (status = 400, description = "Missing or malformed dates", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn get_counter_range(
+    user: AuthenticatedUser,
+    version: ApiVersion,
+    if_none_match: IfNoneMatch,
+    Query(params): Query<GetCounterRangeQuery>,
+    Extension(counters): Extension<DynCounterRepository>,
+) -> Result<Response, (StatusCode, Json<Value>)> {
+    let include_archived = params.include_archived.unwrap_or(false);
+
+    let fingerprint = counters
+        .fingerprint_range(user.user_id, params.start_date, params.end_date)
+        .await
+        .map_err(repository_error("Failed to fetch counter range data"))?;
+    let etag = ETag::new(format_args!(
+        "counters/range {} {:?} {} {} {} {:?}",
+        user.user_id, version, params.start_date, params.end_date, include_archived, fingerprint
+    ));
+    if if_none_match.matches(&etag) {
+        return Ok(etag.not_modified());
     }
 
-    let mut responses: Vec<CounterDataResponse> = response_map
-        .into_iter()
-        .map(|(date, categories)| CounterDataResponse { date, categories })
-        .collect();
-
-    responses.sort_by_key(|r| r.date);
-
-    Ok(Json(ApiResponse::success("Counter range data retrieved successfully", responses)))
+    let responses = counters
+        .range(
+            user.user_id,
+            params.start_date,
+            params.end_date,
+            include_archived,
+        )
+        .await
+        .map_err(repository_error("Failed to fetch counter range data"))?;
+
+    let message = "Counter range data retrieved successfully";
+    Ok(etag.attach(match version {
+        ApiVersion::V1 => {
+            let responses: Vec<CounterDataResponse> =
+                responses.into_iter().map(CounterDataResponse::from).collect();
+            version.respond(ApiResponse::success(message, responses))
+        }
+        ApiVersion::V2 => version.respond(ApiResponse::success(message, responses)),
+    }))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::CategoryCounterData;
     use chrono::NaiveDate;
     use uuid::Uuid;
 
@@ -354,12 +605,44 @@ mod tests {
         assert_eq!(query.date.unwrap(), test_date);
     }
 
+    #[test]
+    fn test_v1_shape_keeps_display_order_by_name() {
+        let entry = |name: &str, sort_order: i32| CategoryCounterData {
+            category_id: Uuid::new_v4(),
+            name: name.to_string(),
+            icon: "coffee".to_string(),
+            color: "#8B4513".to_string(),
+            unit: "ml".to_string(),
+            sort_order,
+            group_name: None,
+            count: 1,
+            amounts: vec![250.0],
+            total_amount: 250.0,
+            updated_at: None,
+            deleted_at: None,
+        };
+        let day = CounterDataResponseV2 {
+            date: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+            categories: vec![entry("Water", 0), entry("Coffee", 1)],
+        };
+        let coffee_id = day.categories[1].category_id;
+
+        let v1 = CounterDataResponse::from(day);
+
+        assert_eq!(v1.order, vec!["Water".to_string(), "Coffee".to_string()]);
+        assert_eq!(v1.categories["Coffee"].category_id, coffee_id);
+    }
+
     #[test]
     fn test_get_counter_range_query_validation() {
         let start_date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
         let end_date = NaiveDate::from_ymd_opt(2024, 1, 31).unwrap();
         
-        let query = GetCounterRangeQuery { start_date, end_date };
+        let query = GetCounterRangeQuery {
+            start_date,
+            end_date,
+            include_archived: None,
+        };
         
         assert!(query.start_date <= query.end_date);
     }
@@ -0,0 +1,35 @@
+use axum::response::{Html, Json};
+use utoipa::OpenApi;
+
+use crate::openapi::ApiDoc;
+
+/// Swagger UI pointed at the spec below. Its assets come from a CDN, so the
+/// page needs internet access in the browser but nothing extra in the image.
+const VIEWER: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>Coffee Counter API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5.11.0/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5.11.0/swagger-ui-bundle.js" crossorigin></script>
+  <script>
+    window.onload = () => {
+      window.ui = SwaggerUIBundle({ url: "/api/openapi.json", dom_id: "#swagger-ui" });
+    };
+  </script>
+</body>
+</html>
+"##;
+
+/// The OpenAPI document describing this API.
+pub async fn openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+/// Interactive API documentation.
+pub async fn viewer() -> Html<&'static str> {
+    Html(VIEWER)
+}
@@ -1,19 +1,201 @@
-use axum::{extract::Extension, response::Json};
+use axum::{extract::Extension, http::StatusCode, response::Json};
+use serde::Serialize;
 use serde_json::{json, Value};
 use sqlx::PgPool;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-pub async fn health_check(Extension(pool): Extension<PgPool>) -> Json<Value> {
-    // Check database connection
-    let db_status = match sqlx::query("SELECT 1").fetch_one(&pool).await {
-        Ok(_) => "healthy",
-        Err(_) => "unhealthy",
-    };
+use crate::db::migrations::check_schema;
+#[cfg(feature = "sqlite")]
+use crate::db::SqliteRepository;
+use crate::utils::auth::AuthService;
+
+/// How long a single dependency check may take before it counts as failed.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Whether the service should still receive traffic. Cleared when graceful
+/// shutdown begins so load balancers stop routing to it while in-flight
+/// requests finish.
+#[derive(Clone, Default)]
+pub struct Readiness {
+    shutting_down: Arc<AtomicBool>,
+}
+
+impl Readiness {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn begin_shutdown(&self) {
+        self.shutting_down.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
+    }
+}
+
+/// Result of checking one dependency.
+#[derive(Debug, Serialize)]
+struct Check {
+    /// `healthy`, `unhealthy`, or `disabled` when this setup doesn't use it.
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    latency_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Check {
+    fn disabled() -> Self {
+        Self {
+            status: "disabled",
+            latency_ms: None,
+            error: None,
+        }
+    }
+
+    async fn run<F>(check: F) -> Self
+    where
+        F: Future<Output = Result<(), String>>,
+    {
+        let started = Instant::now();
+        let result = tokio::time::timeout(CHECK_TIMEOUT, check)
+            .await
+            .unwrap_or_else(|_| Err(format!("timed out after {:?}", CHECK_TIMEOUT)));
+        let latency_ms = Some(started.elapsed().as_secs_f64() * 1000.0);
 
+        match result {
+            Ok(()) => Self {
+                status: "healthy",
+                latency_ms,
+                error: None,
+            },
+            Err(error) => Self {
+                status: "unhealthy",
+                latency_ms,
+                error: Some(error),
+            },
+        }
+    }
+
+    fn is_healthy(&self) -> bool {
+        self.status != "unhealthy"
+    }
+}
+
+/// Liveness: the process is up and serving. Never checks dependencies, so
+/// an outage elsewhere doesn't get this instance restarted.
+#[utoipa::path(
+    get,
+    path = "/health/live",
+    tag = "health",
+    responses((status = 200, description = "The process is up", body = Object))
+)]
+pub async fn liveness() -> Json<Value> {
     Json(json!({
-        "status": if db_status == "healthy" { "healthy" } else { "unhealthy" },
+        "status": "alive",
         "service": "coffee-counter-api",
         "version": env!("CARGO_PKG_VERSION"),
-        "timestamp": chrono::Utc::now(),
-        "database": db_status
+        "timestamp": chrono::Utc::now()
     }))
 }
+
+/// Readiness: 200 when every dependency this setup uses is reachable, 503
+/// otherwise or once shutdown has begun. Also served at `/health`.
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    tag = "health",
+    responses(
+        (status = 200, description = "Every dependency is reachable", body = Object),
+        (status = 503, description = "A dependency is down or shutdown has begun", body = Object)
+    )
+)]
+pub async fn readiness(
+    Extension(readiness): Extension<Readiness>,
+    Extension(auth_service): Extension<AuthService>,
+    pool: Option<Extension<PgPool>>,
+    #[cfg(feature = "sqlite")] sqlite: Option<Extension<Arc<SqliteRepository>>>,
+) -> (StatusCode, Json<Value>) {
+    if readiness.is_shutting_down() {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({
+                "status": "shutting_down",
+                "service": "coffee-counter-api",
+                "version": env!("CARGO_PKG_VERSION"),
+                "timestamp": chrono::Utc::now()
+            })),
+        );
+    }
+
+    // In-memory storage has no database to check
+    let pool = pool.map(|Extension(pool)| pool);
+    #[cfg(feature = "sqlite")]
+    let sqlite = sqlite.map(|Extension(sqlite)| sqlite);
+    let database = async {
+        if let Some(pool) = &pool {
+            return Check::run(async {
+                sqlx::query("SELECT 1")
+                    .fetch_one(pool)
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())
+            })
+            .await;
+        }
+        #[cfg(feature = "sqlite")]
+        if let Some(sqlite) = &sqlite {
+            return Check::run(async { sqlite.ping().await.map_err(|e| e.to_string()) }).await;
+        }
+        Check::disabled()
+    };
+    let migrations = async {
+        if let Some(pool) = &pool {
+            return Check::run(async { check_schema(pool).await.map_err(|e| e.to_string()) })
+                .await;
+        }
+        #[cfg(feature = "sqlite")]
+        if let Some(sqlite) = &sqlite {
+            return Check::run(async { sqlite.check_schema().await.map_err(|e| e.to_string()) })
+                .await;
+        }
+        Check::disabled()
+    };
+    let auth = async {
+        if auth_service.is_single_user() {
+            Check::disabled()
+        } else {
+            Check::run(auth_service.check_health()).await
+        }
+    };
+    let (database, migrations, auth) = tokio::join!(database, migrations, auth);
+
+    let ready = [&database, &migrations, &auth]
+        .iter()
+        .all(|check| check.is_healthy());
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        status,
+        Json(json!({
+            "status": if ready { "healthy" } else { "unhealthy" },
+            "service": "coffee-counter-api",
+            "version": env!("CARGO_PKG_VERSION"),
+            "timestamp": chrono::Utc::now(),
+            "database": database.status,
+            "checks": {
+                "database": database,
+                "migrations": migrations,
+                "auth_service": auth
+            }
+        })),
+    )
+}
@@ -1,3 +1,83 @@
+pub mod audit;
 pub mod categories;
 pub mod counters;
+pub mod docs;
 pub mod health;
+pub mod metrics;
+pub mod sync;
+
+use axum::{http::StatusCode, response::Json};
+use serde_json::Value;
+use validator::ValidationErrors;
+
+use crate::db::RepositoryError;
+use crate::utils::audit::audit_error;
+
+/// Maps a repository failure to the API's error body; `message` describes the
+/// operation and is only shown when the database itself failed.
+pub fn repository_error(
+    message: &'static str,
+) -> impl Fn(RepositoryError) -> (StatusCode, Json<Value>) {
+    move |error| {
+        let (status, message) = match error {
+            RepositoryError::NotFound(message) => (StatusCode::NOT_FOUND, message),
+            RepositoryError::Conflict(message) => (StatusCode::CONFLICT, message),
+            RepositoryError::InvalidInput(message) => (StatusCode::BAD_REQUEST, message),
+            RepositoryError::IdempotencyKeyReused => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "Idempotency-Key was already used for a different request".to_string(),
+            ),
+            RepositoryError::IncompatibleUnits { from, to } => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!(
+                    "Cannot convert '{}' to '{}'; provide a conversion_factor",
+                    from, to
+                ),
+            ),
+            RepositoryError::PreconditionFailed(current) => {
+                return (
+                    StatusCode::PRECONDITION_FAILED,
+                    Json(serde_json::json!({
+                        "success": false,
+                        "message": "Category was changed by another request",
+                        "data": current
+                    })),
+                )
+            }
+            RepositoryError::EntryChanged(current) => {
+                return (
+                    StatusCode::PRECONDITION_FAILED,
+                    Json(serde_json::json!({
+                        "success": false,
+                        "message": "Counter entry was changed by another request",
+                        "data": current
+                    })),
+                )
+            }
+            RepositoryError::Audit(e) => return audit_error(e),
+            RepositoryError::Database(e) => {
+                tracing::error!("Database error: {}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, message.to_string())
+            }
+        };
+
+        (
+            status,
+            Json(serde_json::json!({
+                "success": false,
+                "message": message
+            })),
+        )
+    }
+}
+
+pub fn validation_failed(errors: ValidationErrors) -> (StatusCode, Json<Value>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(serde_json::json!({
+            "success": false,
+            "message": "Validation failed",
+            "errors": errors
+        })),
+    )
+}
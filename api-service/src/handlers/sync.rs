@@ -0,0 +1,65 @@
+use axum::{
+    extract::{Extension, Query},
+    http::StatusCode,
+    response::Json,
+    routing::get,
+    Router,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use utoipa::IntoParams;
+
+use super::repository_error;
+use crate::db::DynSyncRepository;
+use crate::models::{ApiResponse, SyncChanges};
+use crate::utils::auth::AuthenticatedUser;
+
+const DEFAULT_LIMIT: usize = 500;
+const MAX_LIMIT: usize = 1000;
+
+#[derive(Debug, Deserialize, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SyncQuery {
+    /// The `cursor` of the previous response; 0 or absent for a full sync.
+    pub since: Option<i64>,
+    /// Changes per response; defaults to 500, at most 1000.
+    pub limit: Option<usize>,
+}
+
+impl SyncQuery {
+    fn limit(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+    }
+}
+
+pub fn routes() -> Router {
+    Router::new().route("/", get(get_changes))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/sync",
+    tag = "sync",
+    params(SyncQuery),
+    responses(
+        (status = 200, description = "Categories, counter entries and deletions changed after `since`", body = SyncResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn get_changes(
+    user: AuthenticatedUser,
+    Query(params): Query<SyncQuery>,
+    Extension(sync): Extension<DynSyncRepository>,
+) -> Result<Json<ApiResponse<SyncChanges>>, (StatusCode, Json<Value>)> {
+    let since = params.since.unwrap_or(0).max(0);
+    let changes = sync
+        .changes(user.user_id, since, params.limit())
+        .await
+        .map_err(repository_error("Failed to fetch changes"))?;
+
+    Ok(Json(ApiResponse::success(
+        "Changes retrieved successfully",
+        changes,
+    )))
+}
@@ -1,4 +1,7 @@
+pub mod app;
+pub mod config;
 pub mod handlers;
 pub mod models;
+pub mod openapi;
 pub mod db;
 pub mod utils;
@@ -1,113 +1,151 @@
-use axum::{
-    extract::Extension,
-    http::{header, Method, StatusCode},
-    response::Json,
-    routing::get,
-    Router,
-};
-use serde_json::json;
-use sqlx::postgres::PgPoolOptions;
 use std::net::SocketAddr;
-use tower::ServiceBuilder;
-use tower_http::{
-    cors::{Any, CorsLayer},
-    trace::TraceLayer,
-};
-use tracing::{info, warn};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
-
-mod handlers;
-mod models;
-mod db;
-mod utils;
-
-use handlers::{categories, counters, health};
-use utils::auth::AuthService;
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::{info, warn, Level};
+use tracing_subscriber::{filter::Targets, layer::SubscriberExt, util::SubscriberInitExt, Layer};
+
+use coffee_counter_api::app::{app, Storage};
+use coffee_counter_api::config::{Config, LogFormat};
+use coffee_counter_api::db::MigrationMode;
+use coffee_counter_api::handlers::health::Readiness;
+use coffee_counter_api::utils::audit::spawn_retention_task;
+use coffee_counter_api::utils::telemetry::{init_tracer, shutdown_tracer};
+use coffee_counter_api::utils::trash::spawn_purge_task;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Initialize tracing
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "coffee_counter_api=debug,tower_http=debug".into()),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
-
     // Load environment variables
     dotenvy::dotenv().ok();
 
-    // Database connection
-    let database_url = std::env::var("DATABASE_URL")
-        .expect("DATABASE_URL must be set");
+    // Configuration
+    let args = Args::parse(std::env::args().skip(1))?;
+    let mut config = Config::load(args.config_file.as_deref())?;
 
-    let pool = PgPoolOptions::new()
-        .max_connections(20)
-        .connect(&database_url)
-        .await?;
+    if args.print_config {
+        print!("{}", config.redacted().to_toml());
+        return Ok(());
+    }
+
+    // Initialize tracing
+    init_tracing(&config)?;
+    if let Some(endpoint) = &config.telemetry.otlp_endpoint {
+        info!("Exporting traces to {}", endpoint);
+    }
+
+    // `migrate`: apply the schema and exit, for a one-shot job that runs
+    // before anything using the database starts
+    if args.migrate {
+        config.storage.migrations = MigrationMode::Run;
+        Storage::connect(&config.storage).await?;
+        info!("Migrations applied");
+        return Ok(());
+    }
 
-    info!("Database connected");
+    // Storage
+    let storage = Storage::connect(&config.storage).await?;
 
-    // Auth service client
-    let auth_service_url = std::env::var("AUTH_SERVICE_URL")
-        .unwrap_or_else(|_| "http://auth-service:3001".to_string());
-    
-    let auth_service = AuthService::new(&auth_service_url);
+    if config.features.background_jobs {
+        // Audit log retention
+        if let Some(audit) = &storage.audit {
+            spawn_retention_task(audit.clone(), config.audit.settings());
+        }
 
-    // CORS configuration
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
-        .allow_headers([header::CONTENT_TYPE, header::AUTHORIZATION]);
+        // Purge of archived categories
+        if let Some(trash) = &storage.trash {
+            spawn_purge_task(trash.clone(), config.trash.settings());
+        }
+    }
 
     // Build the application
-    let app = Router::new()
-        // Health check
-        .route("/health", get(health::health_check))
-        
-        // API routes
-        .nest("/api/categories", categories::routes())
-        .nest("/api/counters", counters::routes())
-        
-        // Middleware
-        .layer(
-            ServiceBuilder::new()
-                .layer(TraceLayer::new_for_http())
-                .layer(cors)
-                .layer(Extension(pool))
-                .layer(Extension(auth_service))
-        )
-        
-        // 404 handler
-        .fallback(|| async {
-            (
-                StatusCode::NOT_FOUND,
-                Json(json!({
-                    "success": false,
-                    "message": "Route not found"
-                }))
-            )
-        });
+    let readiness = Readiness::new();
+    let app = app(&config, storage, readiness.clone());
 
     // Start server
-    let port = std::env::var("PORT")
-        .unwrap_or_else(|_| "8080".to_string())
-        .parse::<u16>()?;
-
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    let addr = SocketAddr::new(config.server.host, config.server.port);
     
     info!("🚀 Coffee Counter API server starting on {}", addr);
 
     axum::Server::bind(&addr)
         .serve(app.into_make_service())
-        .with_graceful_shutdown(shutdown_signal())
+        .with_graceful_shutdown(shutdown_signal(readiness, config.server.shutdown_drain()))
         .await?;
 
+    shutdown_tracer();
+
     Ok(())
 }
 
-async fn shutdown_signal() {
+fn init_tracing(config: &Config) -> anyhow::Result<()> {
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| "coffee_counter_api=debug,tower_http=debug".into());
+    let logs = match config.log.format {
+        LogFormat::Text => tracing_subscriber::fmt::layer().boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .flatten_event(true)
+            .boxed(),
+    };
+
+    // Spans are exported however quiet RUST_LOG makes the logs
+    let traces = init_tracer(&config.telemetry)?.map(|tracer| {
+        tracing_opentelemetry::layer()
+            .with_tracer(tracer)
+            .with_filter(Targets::new().with_target("coffee_counter_api", Level::INFO))
+    });
+
+    tracing_subscriber::registry()
+        .with(logs.with_filter(filter))
+        .with(traces)
+        .init();
+
+    Ok(())
+}
+
+/// Command line flags; everything else is configured through the environment
+/// or the configuration file.
+#[derive(Debug, Default)]
+struct Args {
+    /// `--config <path>`, or `CONFIG_FILE` when the flag is absent.
+    config_file: Option<PathBuf>,
+    /// `--print-config`: print the effective configuration, secrets redacted, and exit.
+    print_config: bool,
+    /// `migrate`: apply pending migrations and exit instead of serving.
+    migrate: bool,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> anyhow::Result<Self> {
+        let mut parsed = Args::default();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--print-config" => parsed.print_config = true,
+                "migrate" => parsed.migrate = true,
+                "--config" => {
+                    let path = args
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("--config requires a path"))?;
+                    parsed.config_file = Some(path.into());
+                }
+                other => match other.strip_prefix("--config=") {
+                    Some(path) => parsed.config_file = Some(path.into()),
+                    None => anyhow::bail!("unknown argument '{}'", other),
+                },
+            }
+        }
+
+        if parsed.config_file.is_none() {
+            parsed.config_file = std::env::var_os("CONFIG_FILE").map(PathBuf::from);
+        }
+
+        Ok(parsed)
+    }
+}
+
+/// Resolves once the server should stop accepting connections. On SIGTERM
+/// the service first reports not-ready for `drain` while still serving, so
+/// load balancers can move traffic away; Ctrl+C stops straight away.
+async fn shutdown_signal(readiness: Readiness, drain: Duration) {
     let ctrl_c = async {
         tokio::signal::ctrl_c()
             .await
@@ -131,6 +169,14 @@ async fn shutdown_signal() {
         },
         _ = terminate => {
             warn!("Received SIGTERM, shutting down gracefully");
+            readiness.begin_shutdown();
+
+            if !drain.is_zero() {
+                info!("Reporting not ready for {:?} before closing connections", drain);
+                tokio::time::sleep(drain).await;
+            }
         },
     }
+
+    readiness.begin_shutdown();
 }
@@ -1,6 +1,7 @@
 use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use utoipa::ToSchema;
 use uuid::Uuid;
 use validator::Validate;
 
@@ -13,7 +14,7 @@ pub struct User {
     pub created_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct Category {
     pub id: Uuid,
     pub user_id: Uuid,
@@ -23,11 +24,15 @@ pub struct Category {
     pub unit: String,
     pub default_amount: f64,
     pub is_active: bool,
+    pub sort_order: i32,
+    pub group_name: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Set while the category is in the trash.
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Validate)]
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
 pub struct CreateCategoryRequest {
     #[validate(length(min = 1, max = 100))]
     pub name: String,
@@ -39,9 +44,11 @@ pub struct CreateCategoryRequest {
     pub unit: String,
     #[validate(range(min = 0.01))]
     pub default_amount: f64,
+    #[validate(length(max = 50))]
+    pub group_name: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Validate)]
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
 pub struct UpdateCategoryRequest {
     #[validate(length(min = 1, max = 100))]
     pub name: Option<String>,
@@ -54,9 +61,46 @@ pub struct UpdateCategoryRequest {
     #[validate(range(min = 0.01))]
     pub default_amount: Option<f64>,
     pub is_active: Option<bool>,
+    /// An empty string removes the category from its group.
+    #[validate(length(max = 50))]
+    pub group_name: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct ReorderCategoriesRequest {
+    /// The full layout in display order. Categories left out keep their
+    /// relative order after the listed ones.
+    #[validate]
+    pub categories: Vec<CategoryPosition>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct CategoryPosition {
+    pub id: Uuid,
+    #[validate(length(min = 1, max = 50))]
+    pub group_name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct MergeCategoryRequest {
+    pub target_id: Uuid,
+    /// Overrides the built-in unit conversion when the units are unknown.
+    #[validate(range(min = 0.000001))]
+    pub conversion_factor: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MergeCategoryResult {
+    pub source: Category,
+    pub target: Category,
+    pub conversion_factor: f64,
+    /// Days moved over as-is because the target had no entry on that date.
+    pub moved_days: u64,
+    /// Days combined with an existing target entry for the same date.
+    pub combined_days: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct CounterData {
     pub id: Uuid,
     pub user_id: Uuid,
@@ -69,7 +113,7 @@ pub struct CounterData {
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Validate)]
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
 pub struct AddCounterRequest {
     pub category_id: Uuid,
     #[validate(range(min = 0.01))]
@@ -77,25 +121,235 @@ pub struct AddCounterRequest {
     pub notes: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// A category's servings on one day, replacing whatever was logged.
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct SetCounterRequest {
+    pub category_id: Uuid,
+    /// One amount per serving. Empty removes the day's entry.
+    #[validate(custom = "validate_amounts")]
+    pub amounts: Vec<f64>,
+    /// Replaces the day's notes; absent keeps them.
+    pub notes: Option<String>,
+}
+
+/// Each serving must be at least 0.01, as in `AddCounterRequest`.
+fn validate_amounts(amounts: &[f64]) -> Result<(), validator::ValidationError> {
+    if amounts.iter().all(|amount| *amount >= 0.01) {
+        Ok(())
+    } else {
+        Err(validator::ValidationError::new("range"))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct CounterBatchRequest {
+    /// Applied in `recorded_at` order, each on its own: one failing doesn't
+    /// undo or stop the others.
+    #[validate(length(min = 1, max = 100))]
+    pub operations: Vec<CounterOperation>,
+}
+
+/// One change an offline client queued.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CounterOperation {
+    /// Chosen by the client. An operation is applied once however many times
+    /// it is sent, for as long as idempotency keys are kept.
+    pub id: Uuid,
+    /// When the change was made on the client. An `add` counts on this day (UTC).
+    pub recorded_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub change: CounterChange,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum CounterChange {
+    /// Logs one serving, like `POST /counters`.
+    Add {
+        category_id: Uuid,
+        amount: f64,
+        notes: Option<String>,
+    },
+    /// Replaces the servings logged on `date`.
+    Edit {
+        category_id: Uuid,
+        date: NaiveDate,
+        amounts: Vec<f64>,
+        notes: Option<String>,
+        /// Only applies if the entry's `updated_at` is still this.
+        if_updated_at: Option<DateTime<Utc>>,
+    },
+    /// Removes everything logged on `date`.
+    Delete {
+        category_id: Uuid,
+        date: NaiveDate,
+        /// Only applies if the entry's `updated_at` is still this.
+        if_updated_at: Option<DateTime<Utc>>,
+    },
+}
+
+impl CounterChange {
+    /// The day the change applies to, given when it was recorded.
+    pub fn date(&self, recorded_at: DateTime<Utc>) -> NaiveDate {
+        match self {
+            CounterChange::Add { .. } => recorded_at.date_naive(),
+            CounterChange::Edit { date, .. } | CounterChange::Delete { date, .. } => *date,
+        }
+    }
+}
+
+/// The outcome of one operation: what the same change sent on its own
+/// would have got back.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CounterOperationResult {
+    pub id: Uuid,
+    pub success: bool,
+    /// The HTTP status the operation would have had as a request of its own.
+    pub status: u16,
+    pub message: String,
+    /// Applied by an earlier request; this is its result.
+    #[serde(default)]
+    pub replayed: bool,
+    /// The category's entry afterwards, or its current state when
+    /// `if_updated_at` no longer matched.
+    pub data: Option<CategoryCounterData>,
+    /// Per-field messages when validation failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub errors: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CounterBatchResult {
+    /// One per operation, in request order.
+    pub results: Vec<CounterOperationResult>,
+    /// Every day an operation touched, as it stands after the batch, oldest
+    /// first.
+    pub days: Vec<CounterDataResponseV2>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct CounterDataResponse {
     pub date: NaiveDate,
     pub categories: std::collections::HashMap<String, CategoryCounterData>,
+    /// Keys of `categories` in the user's display order.
+    #[serde(default)]
+    pub order: Vec<String>,
+}
+
+/// Version 2 of a day's counters: categories as an array in display order,
+/// identified by `category_id` so renames don't change how clients find them.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CounterDataResponseV2 {
+    pub date: NaiveDate,
+    pub categories: Vec<CategoryCounterData>,
+}
+
+impl From<CounterDataResponseV2> for CounterDataResponse {
+    fn from(day: CounterDataResponseV2) -> Self {
+        let order = day.categories.iter().map(|c| c.name.clone()).collect();
+        let categories = day
+            .categories
+            .into_iter()
+            .map(|c| (c.name.clone(), c))
+            .collect();
+
+        CounterDataResponse {
+            date: day.date,
+            categories,
+            order,
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct CategoryCounterData {
     pub category_id: Uuid,
     pub name: String,
     pub icon: String,
     pub color: String,
     pub unit: String,
+    pub sort_order: i32,
+    pub group_name: Option<String>,
     pub count: i32,
     pub amounts: Vec<f64>,
     pub total_amount: f64,
+    /// When the day's entry last changed; absent while there is none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Utc>>,
+    /// Only present for archived categories included on request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// A category's counts on one day right after they changed, as sent by the
+/// live updates stream.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CounterUpdate {
+    pub date: NaiveDate,
+    pub entry: CategoryCounterData,
+}
+
+/// A deleted category or counter entry, kept so that clients learn of the
+/// deletion.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct SyncTombstone {
+    /// `category` or `counter_data`.
+    pub entity_type: String,
+    pub entity_id: Uuid,
+    /// The entry's category and day, for counter entries.
+    pub category_id: Option<Uuid>,
+    pub date: Option<NaiveDate>,
+    pub deleted_at: DateTime<Utc>,
+}
+
+/// Everything that changed after a cursor, oldest change first within each
+/// list. Archiving a category is an update that sets `deleted_at`; rows that
+/// are gone for good come back as tombstones.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct SyncChanges {
+    /// Created or updated categories, archived ones included.
+    pub categories: Vec<Category>,
+    /// Created or updated counter entries.
+    pub counters: Vec<CounterData>,
+    pub deleted: Vec<SyncTombstone>,
+    /// Send as `since` to get the changes after these.
+    pub cursor: i64,
+    /// More changes are waiting; ask again with the new cursor.
+    pub has_more: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct AuditLogEntry {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub actor_id: Uuid,
+    pub action: String,
+    pub entity_type: String,
+    pub entity_id: Option<Uuid>,
+    pub session_id: Option<String>,
+    pub request_id: Option<String>,
+    #[schema(value_type = Option<Object>)]
+    pub before_data: Option<serde_json::Value>,
+    #[schema(value_type = Option<Object>)]
+    pub after_data: Option<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[aliases(
+    CategoryResponse = ApiResponse<Category>,
+    CategoryListResponse = ApiResponse<Vec<Category>>,
+    MergeCategoryResponse = ApiResponse<MergeCategoryResult>,
+    CounterDayResponse = ApiResponse<CounterDataResponse>,
+    CounterDayResponseV2 = ApiResponse<CounterDataResponseV2>,
+    CounterRangeResponse = ApiResponse<Vec<CounterDataResponse>>,
+    CounterRangeResponseV2 = ApiResponse<Vec<CounterDataResponseV2>>,
+    CounterEntryResponse = ApiResponse<CategoryCounterData>,
+    CounterBatchResponse = ApiResponse<CounterBatchResult>,
+    SyncResponse = ApiResponse<SyncChanges>,
+    AuditLogResponse = ApiResponse<Vec<AuditLogEntry>>
+)]
 pub struct ApiResponse<T> {
     pub success: bool,
     pub message: String,
@@ -120,3 +374,18 @@ impl<T> ApiResponse<T> {
         }
     }
 }
+
+/// Body of every error response.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ErrorResponse {
+    /// Always `false`.
+    pub success: bool,
+    pub message: String,
+    /// Per-field messages when validation failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub errors: Option<serde_json::Value>,
+    /// The `X-Request-ID` of the failed request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
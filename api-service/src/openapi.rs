@@ -0,0 +1,119 @@
+use utoipa::{
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    Modify, OpenApi,
+};
+
+use crate::handlers::{audit, categories, counters, health, metrics, sync};
+use crate::models::{
+    AddCounterRequest, AuditLogEntry, AuditLogResponse, Category, CategoryCounterData,
+    CategoryListResponse, CategoryPosition, CategoryResponse, CounterBatchRequest,
+    CounterBatchResponse, CounterBatchResult, CounterChange, CounterData, CounterDataResponse,
+    CounterDataResponseV2, CounterDayResponse, CounterDayResponseV2, CounterEntryResponse,
+    CounterOperation, CounterOperationResult, CounterRangeResponse, CounterRangeResponseV2,
+    CounterUpdate,
+    CreateCategoryRequest, ErrorResponse, MergeCategoryRequest, MergeCategoryResponse,
+    MergeCategoryResult, ReorderCategoriesRequest, SetCounterRequest, SyncChanges, SyncResponse, SyncTombstone,
+    UpdateCategoryRequest,
+};
+
+/// The OpenAPI document for every route `app` can serve, served at
+/// `/api/openapi.json`. A copy is committed as `openapi.json`; the
+/// `openapi_test` integration test fails when the two differ.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Coffee Counter API",
+        description = "Daily counters for user-defined categories. Tokens come from the auth service.\n\n\
+            The same routes are served under `/api/v2`, where handlers with a newer response shape \
+            use it. The unversioned `/api/...` paths are deprecated aliases of `/api/v1`.",
+        license(name = "MIT")
+    ),
+    paths(
+        categories::get_categories,
+        categories::create_category,
+        categories::get_category,
+        categories::update_category,
+        categories::delete_category,
+        categories::reorder_categories,
+        categories::get_trash,
+        categories::restore_category,
+        categories::merge_category,
+        counters::get_counter_data,
+        counters::add_counter_data,
+        counters::apply_counter_batch,
+        counters::stream_counter_updates,
+        counters::get_counter_range,
+        counters::get_counter_data_by_date,
+        counters::set_counter_data,
+        counters::delete_counter_data,
+        sync::get_changes,
+        audit::get_audit_log,
+        audit::get_admin_audit_log,
+        health::liveness,
+        health::readiness,
+        metrics::metrics,
+    ),
+    components(schemas(
+        Category,
+        CreateCategoryRequest,
+        UpdateCategoryRequest,
+        ReorderCategoriesRequest,
+        CategoryPosition,
+        MergeCategoryRequest,
+        MergeCategoryResult,
+        AddCounterRequest,
+        SetCounterRequest,
+        CounterBatchRequest,
+        CounterOperation,
+        CounterChange,
+        CounterOperationResult,
+        CounterBatchResult,
+        CounterDataResponse,
+        CounterDataResponseV2,
+        CategoryCounterData,
+        CounterUpdate,
+        CounterData,
+        SyncChanges,
+        SyncTombstone,
+        AuditLogEntry,
+        ErrorResponse,
+        CategoryResponse,
+        CategoryListResponse,
+        MergeCategoryResponse,
+        CounterDayResponse,
+        CounterDayResponseV2,
+        CounterRangeResponse,
+        CounterRangeResponseV2,
+        CounterEntryResponse,
+        CounterBatchResponse,
+        SyncResponse,
+        AuditLogResponse,
+    )),
+    modifiers(&BearerAuth),
+    tags(
+        (name = "categories", description = "The user's categories, their order and the trash"),
+        (name = "counters", description = "Logging and reading daily counts"),
+        (name = "sync", description = "Changes since a cursor, for offline clients"),
+        (name = "audit", description = "Audit trail; not kept by in-memory storage"),
+        (name = "health", description = "Probes and metrics, without authentication")
+    )
+)]
+pub struct ApiDoc;
+
+/// Declares the `bearer_auth` scheme the authenticated paths refer to.
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer_auth",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some("Access token issued by the auth service"))
+                    .build(),
+            ),
+        );
+    }
+}
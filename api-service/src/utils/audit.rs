@@ -0,0 +1,204 @@
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{request::Parts, StatusCode},
+    response::Json,
+};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::convert::Infallible;
+use std::time::Duration;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use super::auth::AuthenticatedUser;
+use super::telemetry::query_span;
+use crate::db::DynAuditRepository;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Per-request details recorded alongside every audit entry.
+#[derive(Debug, Clone)]
+pub struct AuditContext {
+    pub request_id: String,
+    pub session_id: Option<String>,
+}
+
+/// A single data-changing operation, captured before it is committed.
+#[derive(Debug)]
+pub struct AuditEvent {
+    pub action: &'static str,
+    pub entity_type: &'static str,
+    pub entity_id: Option<Uuid>,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AuditSettings {
+    /// Entries older than this many days are purged; 0 keeps them forever.
+    pub retention_days: i64,
+    pub purge_interval: Duration,
+}
+
+impl AuditSettings {
+    pub fn new(retention_days: i64) -> Self {
+        Self {
+            retention_days,
+            purge_interval: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+/// Identifies the session a request came from without storing the bearer token.
+pub fn session_fingerprint(token: &str) -> String {
+    let digest = Sha256::digest(token.as_bytes());
+    hex::encode(&digest[..16])
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuditContext
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let request_id = parts
+            .headers
+            .get(REQUEST_ID_HEADER)
+            .and_then(|header| header.to_str().ok())
+            .filter(|id| !id.is_empty() && id.len() <= 100)
+            .map(str::to_string)
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+        let session_id = parts
+            .headers
+            .get("authorization")
+            .and_then(|header| header.to_str().ok())
+            .and_then(|header| header.strip_prefix("Bearer "))
+            .map(session_fingerprint);
+
+        Ok(AuditContext {
+            request_id,
+            session_id,
+        })
+    }
+}
+
+/// Appends an entry to the audit log inside the caller's transaction, so the
+/// entry is committed if and only if the mutation it describes is.
+pub async fn record(
+    tx: &mut Transaction<'_, Postgres>,
+    user: &AuthenticatedUser,
+    context: &AuditContext,
+    event: AuditEvent,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO audit_log
+            (user_id, actor_id, action, entity_type, entity_id, session_id, request_id, before_data, after_data)
+        VALUES ($1, $1, $2, $3, $4, $5, $6, $7, $8)
+        "#,
+    )
+    .bind(user.user_id)
+    .bind(event.action)
+    .bind(event.entity_type)
+    .bind(event.entity_id)
+    .bind(context.session_id.as_deref())
+    .bind(&context.request_id)
+    .bind(event.before)
+    .bind(event.after)
+    .execute(tx)
+    .instrument(query_span("INSERT", "audit_log"))
+    .await?;
+
+    Ok(())
+}
+
+pub fn audit_error(e: sqlx::Error) -> (StatusCode, Json<Value>) {
+    tracing::error!("Failed to write audit log: {}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(serde_json::json!({
+            "success": false,
+            "message": "Failed to record change"
+        })),
+    )
+}
+
+/// Deletes audit entries older than the configured retention period.
+pub async fn purge_expired(pool: &PgPool, retention_days: i64) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        "DELETE FROM audit_log WHERE created_at < NOW() - make_interval(days => $1)",
+    )
+    .bind(retention_days as i32)
+    .execute(pool)
+    .instrument(query_span("DELETE", "audit_log"))
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+pub fn spawn_retention_task(audit: DynAuditRepository, settings: AuditSettings) {
+    if settings.retention_days <= 0 {
+        tracing::info!("Audit log retention disabled");
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(settings.purge_interval);
+        loop {
+            interval.tick().await;
+            match audit.purge_entries(settings.retention_days).await {
+                Ok(0) => {}
+                Ok(purged) => tracing::info!("Purged {} expired audit log entries", purged),
+                Err(e) => tracing::error!("Failed to purge audit log: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+
+    async fn context_for(request: Request<()>) -> AuditContext {
+        let (mut parts, _) = request.into_parts();
+        AuditContext::from_request_parts(&mut parts, &()).await.unwrap()
+    }
+
+    #[test]
+    fn test_session_fingerprint_is_stable_and_hides_token() {
+        let token = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.mock.signature";
+        let fingerprint = session_fingerprint(token);
+
+        assert_eq!(fingerprint, session_fingerprint(token));
+        assert_eq!(fingerprint.len(), 32);
+        assert!(!fingerprint.contains("mock"));
+        assert_ne!(fingerprint, session_fingerprint("another.token"));
+    }
+
+    #[tokio::test]
+    async fn test_audit_context_uses_request_headers() {
+        let request = Request::builder()
+            .header(REQUEST_ID_HEADER, "req-123")
+            .header("authorization", "Bearer test-token")
+            .body(())
+            .unwrap();
+
+        let context = context_for(request).await;
+        assert_eq!(context.request_id, "req-123");
+        assert_eq!(context.session_id, Some(session_fingerprint("test-token")));
+    }
+
+    #[tokio::test]
+    async fn test_audit_context_generates_request_id() {
+        let context = context_for(Request::builder().body(()).unwrap()).await;
+
+        assert!(Uuid::parse_str(&context.request_id).is_ok());
+        assert!(context.session_id.is_none());
+    }
+}
@@ -4,17 +4,30 @@ use axum::{
     http::{request::Parts, StatusCode},
     response::Json,
 };
-use reqwest::Client;
+use reqwest::{header::HeaderMap, Client, RequestBuilder};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::Instrument;
 use uuid::Uuid;
 
+use crate::utils::metrics::{AuthFailure, Metrics};
+use crate::utils::telemetry;
+
 #[derive(Clone)]
 pub struct AuthService {
     client: Client,
     base_url: String,
+    /// In single-user mode every request acts as this user and no token is checked.
+    single_user: Option<Uuid>,
+    metrics: Option<Metrics>,
 }
 
+/// Email reported for the user in single-user mode.
+pub const SINGLE_USER_EMAIL: &str = "owner@localhost";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct VerifyTokenRequest {
     pub token: String,
@@ -34,7 +47,7 @@ pub struct TokenData {
     pub user: UserInfo,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserInfo {
     pub id: Uuid,
     pub email: String,
@@ -42,49 +55,162 @@ pub struct UserInfo {
     pub is_active: bool,
 }
 
+#[derive(Clone)]
 pub struct AuthenticatedUser {
     pub user_id: Uuid,
     #[allow(dead_code)]
     pub user: UserInfo,
 }
 
+/// Email addresses allowed to use the admin endpoints, from `ADMIN_EMAILS`.
+#[derive(Clone, Default)]
+pub struct AdminUsers {
+    emails: Arc<HashSet<String>>,
+}
+
+pub struct AdminUser(pub AuthenticatedUser);
+
 impl AuthService {
     pub fn new(base_url: &str) -> Self {
         Self {
             client: Client::new(),
             base_url: base_url.to_string(),
+            single_user: None,
+            metrics: None,
+        }
+    }
+
+    /// Gives up on verification calls that take longer than `timeout`.
+    pub fn with_timeout(base_url: &str, timeout: Duration) -> Self {
+        let client = Client::builder()
+            .timeout(timeout)
+            .build()
+            .expect("HTTP client configuration is valid");
+
+        Self {
+            client,
+            base_url: base_url.to_string(),
+            single_user: None,
+            metrics: None,
+        }
+    }
+
+    pub fn single_user(user_id: Uuid) -> Self {
+        Self {
+            client: Client::new(),
+            base_url: String::new(),
+            single_user: Some(user_id),
+            metrics: None,
         }
     }
 
+    pub fn is_single_user(&self) -> bool {
+        self.single_user.is_some()
+    }
+
+    /// Whether the auth service answers its health check.
+    pub async fn check_health(&self) -> Result<(), String> {
+        let url = format!("{}/health", self.base_url);
+
+        let response = self
+            .send("GET", &url, self.client.get(&url))
+            .await
+            .map_err(|e| format!("Auth service unreachable: {}", e))?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!("Auth service returned {}", response.status()))
+        }
+    }
+
+    /// Records the latency and failures of verification calls in `metrics`.
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
     pub async fn verify_token(&self, token: &str) -> Result<TokenData, String> {
+        let started = Instant::now();
+        let result = self.request_verification(token).await;
+
+        if let Some(metrics) = &self.metrics {
+            let failure = result.as_ref().err().map(|(failure, _)| *failure);
+            metrics.observe_auth_verification(started.elapsed(), failure);
+        }
+
+        result.map_err(|(_, message)| message)
+    }
+
+    async fn request_verification(&self, token: &str) -> Result<TokenData, (AuthFailure, String)> {
         let url = format!("{}/api/auth/verify", self.base_url);
         
+        let request = self.client.post(&url).json(&VerifyTokenRequest {
+            token: token.to_string(),
+        });
         let response = self
-            .client
-            .post(&url)
-            .json(&VerifyTokenRequest {
-                token: token.to_string(),
-            })
-            .send()
+            .send("POST", &url, request)
             .await
-            .map_err(|e| format!("Failed to verify token: {}", e))?;
+            .map_err(|e| (AuthFailure::Unavailable, format!("Failed to verify token: {}", e)))?;
 
         if !response.status().is_success() {
-            return Err("Token verification failed".to_string());
+            return Err((AuthFailure::Rejected, "Token verification failed".to_string()));
         }
 
         let verify_response: VerifyTokenResponse = response
             .json()
             .await
-            .map_err(|e| format!("Failed to parse response: {}", e))?;
+            .map_err(|e| (AuthFailure::InvalidResponse, format!("Failed to parse response: {}", e)))?;
 
         if !verify_response.success {
-            return Err(verify_response.message);
+            return Err((AuthFailure::Rejected, verify_response.message));
         }
 
         verify_response
             .data
-            .ok_or_else(|| "No token data in response".to_string())
+            .ok_or_else(|| (AuthFailure::InvalidResponse, "No token data in response".to_string()))
+    }
+
+    /// Sends `request` in its own client span, passing the trace context on
+    /// so the auth service's spans join the same trace.
+    async fn send(
+        &self,
+        method: &str,
+        url: &str,
+        request: RequestBuilder,
+    ) -> reqwest::Result<reqwest::Response> {
+        let span = telemetry::client_span(method, url);
+        let mut headers = HeaderMap::new();
+        telemetry::inject_context(&span, &mut headers);
+
+        let response = request.headers(headers).send().instrument(span.clone()).await;
+        if let Ok(response) = &response {
+            span.record("http.status_code", response.status().as_u16());
+        }
+
+        response
+    }
+}
+
+impl AdminUsers {
+    pub fn new<I, E>(emails: I) -> Self
+    where
+        I: IntoIterator<Item = E>,
+        E: AsRef<str>,
+    {
+        let emails = emails
+            .into_iter()
+            .map(|email| email.as_ref().trim().to_lowercase())
+            .filter(|email| !email.is_empty())
+            .collect();
+
+        Self {
+            emails: Arc::new(emails),
+        }
+    }
+
+    pub fn is_admin(&self, user: &UserInfo) -> bool {
+        self.emails.contains(&user.email.to_lowercase())
     }
 }
 
@@ -96,6 +222,11 @@ where
     type Rejection = (StatusCode, Json<serde_json::Value>);
 
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        // Already verified by middleware earlier in the stack
+        if let Some(user) = parts.extensions.get::<AuthenticatedUser>() {
+            return Ok(user.clone());
+        }
+
         // Extract auth service from extensions
         let Extension(auth_service): Extension<AuthService> = 
             Extension::from_request_parts(parts, state)
@@ -110,18 +241,25 @@ where
                     )
                 })?;
 
+        if let Some(user_id) = auth_service.single_user {
+            tracing::Span::current().record("user_id", tracing::field::display(user_id));
+            return Ok(AuthenticatedUser {
+                user_id,
+                user: UserInfo {
+                    id: user_id,
+                    email: SINGLE_USER_EMAIL.to_string(),
+                    display_name: None,
+                    is_active: true,
+                },
+            });
+        }
+
         // Extract token from Authorization header
         let auth_header = parts
             .headers
             .get("authorization")
             .and_then(|header| header.to_str().ok())
-            .and_then(|header| {
-                if header.starts_with("Bearer ") {
-                    Some(&header[7..])
-                } else {
-                    None
-                }
-            })
+            .and_then(|header| header.strip_prefix("Bearer "))
             .ok_or_else(|| {
                 (
                     StatusCode::UNAUTHORIZED,
@@ -146,6 +284,9 @@ where
                 )
             })?;
 
+        // Tie the rest of this request's logs to the user
+        tracing::Span::current().record("user_id", tracing::field::display(token_data.user_id));
+
         Ok(AuthenticatedUser {
             user_id: token_data.user_id,
             user: token_data.user,
@@ -153,6 +294,35 @@ where
     }
 }
 
+#[async_trait]
+impl<S> FromRequestParts<S> for AdminUser
+where
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, Json<serde_json::Value>);
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let user = AuthenticatedUser::from_request_parts(parts, state).await?;
+
+        let admins = Extension::<AdminUsers>::from_request_parts(parts, state)
+            .await
+            .map(|Extension(admins)| admins)
+            .unwrap_or_default();
+
+        if !admins.is_admin(&user.user) {
+            return Err((
+                StatusCode::FORBIDDEN,
+                Json(json!({
+                    "success": false,
+                    "message": "Admin access required"
+                })),
+            ));
+        }
+
+        Ok(AdminUser(user))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -231,7 +401,7 @@ mod tests {
             "data": null
         }"#;
 
-        let response: VerifyTokenResponse = serde_json::from_str(&json).unwrap();
+        let response: VerifyTokenResponse = serde_json::from_str(json).unwrap();
         assert!(!response.success);
         assert!(response.data.is_none());
         assert_eq!(response.message, "Invalid token");
@@ -273,6 +443,19 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_admin_users_matching() {
+        let admins = AdminUsers::new(" Admin@Example.com ,, ops@example.com".split(','));
+        let mut user = mock_authenticated_user().user;
+
+        assert!(!admins.is_admin(&user));
+
+        user.email = "admin@example.com".to_string();
+        assert!(admins.is_admin(&user));
+
+        assert!(!AdminUsers::default().is_admin(&user));
+    }
+
     #[test]
     fn test_authenticated_user_creation() {
         let user = mock_authenticated_user();
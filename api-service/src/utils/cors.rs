@@ -0,0 +1,188 @@
+use axum::http::{header, HeaderName, HeaderValue, Method};
+use std::str::FromStr;
+use std::time::Duration;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use crate::config::CorsConfig;
+use crate::utils::idempotency::IDEMPOTENT_REPLAYED;
+use crate::utils::rate_limit::{X_RATELIMIT_LIMIT, X_RATELIMIT_REMAINING, X_RATELIMIT_RESET};
+use crate::utils::request_id::X_REQUEST_ID;
+use crate::utils::version::{DEPRECATION, SUNSET};
+
+/// One entry of the CORS allow-list: an exact origin such as
+/// `https://coffee.example.com`, or `https://*.example.com` for any of its
+/// subdomains (but not the domain itself).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OriginPattern {
+    Exact(String),
+    Subdomains {
+        /// `https://`
+        prefix: String,
+        /// `.example.com`, including the port if one was given.
+        suffix: String,
+    },
+}
+
+impl FromStr for OriginPattern {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let origin = value.trim().to_ascii_lowercase();
+        let invalid = || format!("invalid origin '{}'", value);
+
+        let (scheme, host) = origin.split_once("://").ok_or_else(invalid)?;
+        if !matches!(scheme, "http" | "https") {
+            return Err(format!("{}: scheme must be http or https", invalid()));
+        }
+
+        let (wildcard, host) = match host.strip_prefix("*.") {
+            Some(domain) => (true, domain),
+            None => (false, host),
+        };
+        let valid_host = !host.is_empty()
+            && !host.starts_with('.')
+            && host
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | ':'));
+        if !valid_host {
+            return Err(format!(
+                "{}: expected scheme://host[:port] or scheme://*.domain[:port]",
+                invalid()
+            ));
+        }
+
+        if wildcard {
+            Ok(OriginPattern::Subdomains {
+                prefix: format!("{}://", scheme),
+                suffix: format!(".{}", host),
+            })
+        } else {
+            Ok(OriginPattern::Exact(origin))
+        }
+    }
+}
+
+impl OriginPattern {
+    pub fn matches(&self, origin: &str) -> bool {
+        let origin = origin.to_ascii_lowercase();
+
+        match self {
+            OriginPattern::Exact(allowed) => origin == *allowed,
+            OriginPattern::Subdomains { prefix, suffix } => origin
+                .strip_prefix(prefix.as_str())
+                .and_then(|rest| rest.strip_suffix(suffix.as_str()))
+                .is_some_and(|subdomain| {
+                    subdomain.split('.').all(|label| {
+                        !label.is_empty()
+                            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+                    })
+                }),
+        }
+    }
+}
+
+/// Builds the CORS layer. Origins that aren't allowed get no
+/// `Access-Control-Allow-Origin`, so browsers refuse to share the response.
+/// The configuration has been validated by the time this is called.
+pub fn cors_layer(config: &CorsConfig) -> CorsLayer {
+    let allow_origin = if config.allowed_origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        let patterns: Vec<OriginPattern> = config
+            .allowed_origins
+            .iter()
+            .filter_map(|origin| origin.parse().ok())
+            .collect();
+
+        AllowOrigin::predicate(move |origin: &HeaderValue, _| {
+            origin
+                .to_str()
+                .is_ok_and(|origin| patterns.iter().any(|pattern| pattern.matches(origin)))
+        })
+    };
+
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods(
+            config
+                .allowed_methods
+                .iter()
+                .filter_map(|method| Method::from_str(method).ok())
+                .collect::<Vec<_>>(),
+        )
+        .allow_headers(
+            config
+                .allowed_headers
+                .iter()
+                .filter_map(|name| HeaderName::from_str(name).ok())
+                .collect::<Vec<_>>(),
+        )
+        .allow_credentials(config.allow_credentials)
+        // Let browser clients see when they are being rate limited, which
+        // request to quote when reporting a problem, when a route they use
+        // is going away, which version of a read they hold and whether a
+        // write was a replay
+        .expose_headers([
+            X_REQUEST_ID,
+            header::RETRY_AFTER,
+            X_RATELIMIT_LIMIT,
+            X_RATELIMIT_REMAINING,
+            X_RATELIMIT_RESET,
+            DEPRECATION,
+            SUNSET,
+            header::LINK,
+            header::ETAG,
+            IDEMPOTENT_REPLAYED,
+        ])
+        .max_age(Duration::from_secs(config.max_age_secs))
+        // Counter responses are negotiated on Accept, so caches must key on it too
+        .vary([
+            header::ORIGIN,
+            header::ACCESS_CONTROL_REQUEST_METHOD,
+            header::ACCESS_CONTROL_REQUEST_HEADERS,
+            header::ACCEPT,
+        ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exact_origin() {
+        let pattern: OriginPattern = "https://Coffee.example.com".parse().unwrap();
+
+        assert!(pattern.matches("https://coffee.example.com"));
+        assert!(!pattern.matches("http://coffee.example.com"));
+        assert!(!pattern.matches("https://coffee.example.com:8443"));
+        assert!(!pattern.matches("https://coffee.example.com.evil.test"));
+    }
+
+    #[test]
+    fn test_wildcard_subdomains() {
+        let pattern: OriginPattern = "https://*.example.com".parse().unwrap();
+
+        assert!(pattern.matches("https://app.example.com"));
+        assert!(pattern.matches("https://preview-12.app.example.com"));
+        assert!(!pattern.matches("https://example.com"));
+        assert!(!pattern.matches("https://evilexample.com"));
+        assert!(!pattern.matches("https://.example.com"));
+        assert!(!pattern.matches("https://app.example.com.evil.test"));
+        assert!(!pattern.matches("http://app.example.com"));
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        for pattern in [
+            "example.com",
+            "ftp://example.com",
+            "https://",
+            "https://example.com/app",
+            "https://*.*.example.com",
+            "https://app.*.example.com",
+            "*",
+        ] {
+            assert!(pattern.parse::<OriginPattern>().is_err(), "{}", pattern);
+        }
+    }
+}
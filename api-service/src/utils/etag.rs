@@ -0,0 +1,209 @@
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use sha2::{Digest, Sha256};
+use std::convert::Infallible;
+use std::fmt::Display;
+
+use crate::db::Precondition;
+
+/// Reads are per user and change whenever the user logs something, so shared
+/// caches must not store them and private ones must revalidate every time.
+const CACHE_CONTROL: HeaderValue = HeaderValue::from_static("private, no-cache");
+
+/// A strong entity tag. Listings are tagged with a digest of everything the
+/// response depends on (the user, the route and its parameters, the response
+/// version and a [`Fingerprint`](crate::db::Fingerprint) of the rows) rather
+/// than of the body, so a matching request is answered before anything is
+/// loaded. A single category is tagged with its version, so `If-Match` can be
+/// checked by the storage layer as part of the write.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ETag(String);
+
+impl ETag {
+    pub fn new(representation: impl Display) -> Self {
+        let digest = Sha256::digest(representation.to_string().as_bytes());
+        ETag(format!("\"{}\"", hex::encode(&digest[..16])))
+    }
+
+    /// The tag of a row last changed at `updated_at`.
+    pub fn version(updated_at: DateTime<Utc>) -> Self {
+        ETag(format!(
+            "\"{}\"",
+            updated_at.to_rfc3339_opts(SecondsFormat::Nanos, true)
+        ))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    fn header_value(&self) -> HeaderValue {
+        HeaderValue::from_str(&self.0).expect("hex digests are valid header values")
+    }
+
+    /// Adds the tag and caching policy to a successful response.
+    pub fn attach(&self, mut response: Response) -> Response {
+        let headers = response.headers_mut();
+        headers.insert(header::ETAG, self.header_value());
+        headers.insert(header::CACHE_CONTROL, CACHE_CONTROL);
+        response
+    }
+
+    /// The `304 Not Modified` sent when the client's copy is current.
+    pub fn not_modified(&self) -> Response {
+        self.attach(StatusCode::NOT_MODIFIED.into_response())
+    }
+}
+
+/// The request's `If-None-Match` tags, if it sent any.
+#[derive(Debug, Default)]
+pub struct IfNoneMatch(Option<String>);
+
+impl IfNoneMatch {
+    /// Whether the client already has the representation tagged `etag`. Uses
+    /// the weak comparison RFC 9110 prescribes for `If-None-Match`.
+    pub fn matches(&self, etag: &ETag) -> bool {
+        let Some(tags) = &self.0 else {
+            return false;
+        };
+
+        tags.split(',')
+            .map(str::trim)
+            .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag.as_str())
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for IfNoneMatch
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(IfNoneMatch(
+            parts
+                .headers
+                .get(header::IF_NONE_MATCH)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+        ))
+    }
+}
+
+/// The request's `If-Match` tags, naming the versions a write may replace.
+#[derive(Debug, Default)]
+pub struct IfMatch(Option<String>);
+
+impl IfMatch {
+    /// Tags that aren't versions, and weak tags (which never match under the
+    /// strong comparison `If-Match` requires), can't match any version.
+    pub fn precondition(&self) -> Precondition {
+        let Some(tags) = &self.0 else {
+            return Precondition::Any;
+        };
+        if tags.trim() == "*" {
+            return Precondition::Any;
+        }
+
+        Precondition::UpdatedAt(
+            tags.split(',')
+                .filter_map(|tag| tag.trim().strip_prefix('"')?.strip_suffix('"'))
+                .filter_map(|version| DateTime::parse_from_rfc3339(version).ok())
+                .map(|version| version.with_timezone(&Utc))
+                .collect(),
+        )
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for IfMatch
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(IfMatch(
+            parts
+                .headers
+                .get(header::IF_MATCH)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn if_none_match(value: &str) -> IfNoneMatch {
+        IfNoneMatch(Some(value.to_string()))
+    }
+
+    #[test]
+    fn test_etag_is_a_quoted_digest_of_its_input() {
+        let etag = ETag::new("counters/day alice");
+
+        assert_eq!(etag, ETag::new("counters/day alice"));
+        assert_ne!(etag, ETag::new("counters/day bob"));
+        assert_eq!(etag.as_str().len(), 34);
+        assert!(etag.as_str().starts_with('"') && etag.as_str().ends_with('"'));
+    }
+
+    #[test]
+    fn test_if_none_match_comparison() {
+        let etag = ETag::new("counters/day alice");
+        let other = ETag::new("counters/day bob");
+
+        assert!(!IfNoneMatch::default().matches(&etag));
+        assert!(if_none_match(etag.as_str()).matches(&etag));
+        assert!(if_none_match(&format!("W/{}", etag.as_str())).matches(&etag));
+        assert!(if_none_match(&format!("{}, {}", other.as_str(), etag.as_str())).matches(&etag));
+        assert!(if_none_match("*").matches(&etag));
+        assert!(!if_none_match(other.as_str()).matches(&etag));
+        // Tags are opaque quoted strings; the bare digest is a different tag
+        assert!(!if_none_match(etag.as_str().trim_matches('"')).matches(&etag));
+    }
+
+    #[test]
+    fn test_not_modified_carries_the_tag() {
+        let etag = ETag::new("categories alice");
+        let response = etag.not_modified();
+
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::ETAG], etag.as_str());
+        assert_eq!(
+            response.headers()[header::CACHE_CONTROL],
+            "private, no-cache"
+        );
+    }
+
+    #[test]
+    fn test_if_match_names_versions() {
+        let updated_at = Utc::now();
+        let etag = ETag::version(updated_at);
+        let if_match = |value: &str| IfMatch(Some(value.to_string())).precondition();
+
+        assert_eq!(IfMatch::default().precondition(), Precondition::Any);
+        assert_eq!(if_match("*"), Precondition::Any);
+        assert_eq!(
+            if_match(etag.as_str()),
+            Precondition::UpdatedAt(vec![updated_at])
+        );
+        assert_eq!(
+            if_match(&format!("\"stale\", {}", etag.as_str())),
+            Precondition::UpdatedAt(vec![updated_at])
+        );
+        assert_eq!(
+            if_match(&format!("W/{}", etag.as_str())),
+            Precondition::UpdatedAt(Vec::new())
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{request::Parts, HeaderName, StatusCode},
+    response::Json,
+};
+use chrono::{Duration, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::db::IdempotencyKey;
+
+pub const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+/// Set on responses replayed from an earlier request with the same key.
+pub const IDEMPOTENT_REPLAYED: HeaderName = HeaderName::from_static("idempotent-replayed");
+
+const MAX_KEY_LENGTH: usize = 255;
+
+#[derive(Debug, Clone)]
+pub struct IdempotencySettings {
+    /// How long a key's result is kept for replay.
+    pub ttl: Duration,
+}
+
+impl IdempotencySettings {
+    pub fn new(ttl_hours: u64) -> Self {
+        Self {
+            ttl: Duration::hours(ttl_hours as i64),
+        }
+    }
+
+    /// `key` bound to `operation` and `request`, so that reusing it for a
+    /// different request is refused rather than replayed.
+    pub fn key<T: Serialize>(
+        &self,
+        key: String,
+        operation: &'static str,
+        request: &T,
+    ) -> IdempotencyKey {
+        let mut digest = Sha256::new();
+        digest.update(operation.as_bytes());
+        digest.update(b"\n");
+        digest.update(serde_json::to_vec(request).unwrap_or_default());
+
+        IdempotencyKey {
+            operation,
+            key,
+            request_hash: hex::encode(digest.finalize()),
+            expired_before: Utc::now() - self.ttl,
+        }
+    }
+}
+
+/// The request's `Idempotency-Key`, if it sent one.
+#[derive(Debug, Default)]
+pub struct IdempotencyKeyHeader(Option<String>);
+
+impl IdempotencyKeyHeader {
+    /// The sent key, bound to this request; see [`IdempotencySettings::key`].
+    pub fn bind<T: Serialize>(
+        &self,
+        operation: &'static str,
+        request: &T,
+        settings: &IdempotencySettings,
+    ) -> Option<IdempotencyKey> {
+        let key = self.0.clone()?;
+
+        Some(settings.key(key, operation, request))
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for IdempotencyKeyHeader
+where
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, Json<Value>);
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Some(value) = parts.headers.get(IDEMPOTENCY_KEY) else {
+            return Ok(IdempotencyKeyHeader(None));
+        };
+
+        match value.to_str().map(str::trim) {
+            Ok(key) if !key.is_empty() && key.len() <= MAX_KEY_LENGTH => {
+                Ok(IdempotencyKeyHeader(Some(key.to_string())))
+            }
+            _ => Err((
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({
+                    "success": false,
+                    "message": format!(
+                        "Idempotency-Key must be 1 to {} visible ASCII characters",
+                        MAX_KEY_LENGTH
+                    )
+                })),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+
+    async fn extract(value: &str) -> Result<IdempotencyKeyHeader, StatusCode> {
+        let request = Request::builder()
+            .header(IDEMPOTENCY_KEY, value)
+            .body(())
+            .unwrap();
+        let (mut parts, _) = request.into_parts();
+
+        IdempotencyKeyHeader::from_request_parts(&mut parts, &())
+            .await
+            .map_err(|(status, _)| status)
+    }
+
+    #[tokio::test]
+    async fn test_key_validation() {
+        assert!(extract("9f0c6a8e-retry").await.unwrap().0.is_some());
+        assert_eq!(extract("  ").await.unwrap_err(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            extract(&"k".repeat(256)).await.unwrap_err(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[test]
+    fn test_keys_are_bound_to_the_request() {
+        let settings = IdempotencySettings::new(24);
+        let header = IdempotencyKeyHeader(Some("tap-1".to_string()));
+        let bind = |operation: &'static str, amount: f64| {
+            header
+                .bind(
+                    operation,
+                    &serde_json::json!({ "amount": amount }),
+                    &settings,
+                )
+                .unwrap()
+        };
+
+        let key = bind("counters.add", 250.0);
+        assert_eq!(key.key, "tap-1");
+        assert_eq!(key.request_hash, bind("counters.add", 250.0).request_hash);
+        assert_ne!(key.request_hash, bind("counters.add", 100.0).request_hash);
+        assert_ne!(key.request_hash, bind("counters.batch", 250.0).request_hash);
+        assert!(key.expired_before < Utc::now() - Duration::hours(23));
+
+        assert!(IdempotencyKeyHeader::default()
+            .bind("counters.add", &(), &settings)
+            .is_none());
+    }
+}
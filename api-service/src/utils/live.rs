@@ -0,0 +1,304 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::{PgListener, PgPool};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, OnceCell};
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::models::{CategoryCounterData, CounterUpdate};
+use crate::utils::telemetry::query_span;
+
+/// Postgres channel the updates are announced on.
+pub const CHANNEL: &str = "counter_updates";
+
+/// How many updates a subscriber may fall behind before it misses some.
+const CAPACITY: usize = 256;
+
+/// Postgres refuses NOTIFY payloads of 8000 bytes or more.
+const MAX_PAYLOAD: usize = 7999;
+
+/// Pause before listening again after the notification connection failed.
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// What a live updates subscriber receives.
+#[derive(Debug, Clone)]
+pub enum LiveEvent {
+    Changed(Box<CounterUpdate>),
+    /// The client should refetch: updates were lost, because the subscriber
+    /// fell behind or this process lost its notification connection, or a
+    /// category change altered more days than are worth sending.
+    Resync,
+}
+
+/// A change announced to the user's subscribers, as sent over NOTIFY.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Published {
+    Counter {
+        user_id: Uuid,
+        #[serde(flatten)]
+        update: Box<CounterUpdate>,
+    },
+    Resync {
+        user_id: Uuid,
+    },
+}
+
+impl Published {
+    fn user_id(&self) -> Uuid {
+        match self {
+            Published::Counter { user_id, .. } | Published::Resync { user_id } => *user_id,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Message {
+    Published(Arc<Published>),
+    Missed,
+}
+
+struct Inner {
+    sender: broadcast::Sender<Message>,
+    pool: Option<PgPool>,
+    /// Set once this process listens to `CHANNEL`.
+    listening: OnceCell<()>,
+}
+
+/// Fans counter changes out to the live update streams. Without Postgres
+/// they go straight to this process's subscribers; with Postgres they are
+/// announced with NOTIFY, so subscribers on every replica get them, this one
+/// included.
+#[derive(Clone)]
+pub struct LiveUpdates {
+    inner: Arc<Inner>,
+}
+
+impl LiveUpdates {
+    pub fn new(pool: Option<PgPool>) -> Self {
+        let (sender, _) = broadcast::channel(CAPACITY);
+
+        Self {
+            inner: Arc::new(Inner {
+                sender,
+                pool,
+                listening: OnceCell::new(),
+            }),
+        }
+    }
+
+    /// Announces a category's counts on `date` after a change.
+    pub async fn publish(&self, user_id: Uuid, date: NaiveDate, entry: CategoryCounterData) {
+        self.announce(Published::Counter {
+            user_id,
+            update: Box::new(CounterUpdate { date, entry }),
+        })
+        .await
+    }
+
+    /// Tells the user's subscribers to refetch, after a change to their
+    /// categories: a merge, archive or restore can change any number of days,
+    /// and names, units and order show on every one.
+    pub async fn resync(&self, user_id: Uuid) {
+        self.announce(Published::Resync { user_id }).await
+    }
+
+    async fn announce(&self, published: Published) {
+        if let Some(pool) = &self.inner.pool {
+            match serde_json::to_string(&published) {
+                Ok(payload) if payload.len() <= MAX_PAYLOAD => {
+                    let notified = sqlx::query("SELECT pg_notify($1, $2)")
+                        .bind(CHANNEL)
+                        .bind(payload)
+                        .execute(pool)
+                        .instrument(query_span("NOTIFY", CHANNEL))
+                        .await;
+                    match notified {
+                        Ok(_) => return,
+                        Err(e) => tracing::warn!(
+                            "Failed to announce a counter update, other replicas miss it: {}",
+                            e
+                        ),
+                    }
+                }
+                _ => tracing::warn!("Counter update too large to announce, other replicas miss it"),
+            }
+        }
+
+        // Nobody subscribed is fine
+        let _ = self
+            .inner
+            .sender
+            .send(Message::Published(Arc::new(published)));
+    }
+
+    /// The user's updates from now on. With Postgres the first subscriber
+    /// starts listening for notifications, which fails if no connection can
+    /// be had.
+    pub async fn subscribe(
+        &self,
+        user_id: Uuid,
+    ) -> Result<impl Stream<Item = LiveEvent> + Send + 'static, sqlx::Error> {
+        let receiver = self.inner.sender.subscribe();
+        if let Some(pool) = &self.inner.pool {
+            self.inner
+                .listening
+                .get_or_try_init(|| listen(pool, self.inner.sender.clone()))
+                .await?;
+        }
+
+        Ok(
+            BroadcastStream::new(receiver).filter_map(move |message| match message {
+                Ok(Message::Published(published)) if published.user_id() == user_id => {
+                    match published.as_ref() {
+                        Published::Counter { update, .. } => {
+                            Some(LiveEvent::Changed(update.clone()))
+                        }
+                        Published::Resync { .. } => Some(LiveEvent::Resync),
+                    }
+                }
+                Ok(Message::Published(_)) => None,
+                Ok(Message::Missed) | Err(_) => Some(LiveEvent::Resync),
+            }),
+        )
+    }
+}
+
+/// Starts forwarding notifications to this process's subscribers, for as
+/// long as the pool is open. Returns once the channel is listened to.
+async fn listen(pool: &PgPool, sender: broadcast::Sender<Message>) -> Result<(), sqlx::Error> {
+    let mut listener = PgListener::connect_with(pool).await?;
+    listener.listen(CHANNEL).await?;
+
+    tokio::spawn(async move {
+        loop {
+            match listener.try_recv().await {
+                Ok(Some(notification)) => {
+                    match serde_json::from_str::<Published>(notification.payload()) {
+                        Ok(published) => {
+                            let _ = sender.send(Message::Published(Arc::new(published)));
+                        }
+                        Err(e) => tracing::warn!("Ignoring a malformed counter update: {}", e),
+                    }
+                }
+                // Reconnects on the next call; whatever was announced
+                // meanwhile is gone
+                Ok(None) => {
+                    tracing::warn!("Lost the connection for counter updates, reconnecting");
+                    let _ = sender.send(Message::Missed);
+                }
+                Err(sqlx::Error::PoolClosed) => return,
+                Err(e) => {
+                    tracing::error!("Failed to receive counter updates: {}", e);
+                    let _ = sender.send(Message::Missed);
+                    tokio::time::sleep(RETRY_DELAY).await;
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(count: i32) -> CategoryCounterData {
+        CategoryCounterData {
+            category_id: Uuid::new_v4(),
+            name: "Coffee".to_string(),
+            icon: "coffee".to_string(),
+            color: "#8B4513".to_string(),
+            unit: "ml".to_string(),
+            sort_order: 0,
+            group_name: None,
+            count,
+            amounts: vec![250.0; count as usize],
+            total_amount: 250.0 * count as f64,
+            updated_at: None,
+            deleted_at: None,
+        }
+    }
+
+    fn date() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_subscribers_only_get_their_own_updates() {
+        let live = LiveUpdates::new(None);
+        let alice = Uuid::new_v4();
+        let mut updates = Box::pin(live.subscribe(alice).await.unwrap());
+
+        live.publish(Uuid::new_v4(), date(), entry(1)).await;
+        live.publish(alice, date(), entry(2)).await;
+
+        match updates.next().await {
+            Some(LiveEvent::Changed(update)) => {
+                assert_eq!((update.date, update.entry.count), (date(), 2))
+            }
+            other => panic!("expected alice's update, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_falling_behind_is_reported() {
+        let live = LiveUpdates::new(None);
+        let alice = Uuid::new_v4();
+        let mut updates = Box::pin(live.subscribe(alice).await.unwrap());
+
+        for count in 0..=CAPACITY as i32 {
+            live.publish(alice, date(), entry(count)).await;
+        }
+
+        assert!(matches!(updates.next().await, Some(LiveEvent::Resync)));
+        match updates.next().await {
+            Some(LiveEvent::Changed(update)) => assert_eq!(update.entry.count, 1),
+            other => panic!("expected the oldest update still kept, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resyncs_go_to_the_user() {
+        let live = LiveUpdates::new(None);
+        let alice = Uuid::new_v4();
+        let mut updates = Box::pin(live.subscribe(alice).await.unwrap());
+
+        live.resync(Uuid::new_v4()).await;
+        live.resync(alice).await;
+        live.publish(alice, date(), entry(1)).await;
+
+        assert!(matches!(updates.next().await, Some(LiveEvent::Resync)));
+        assert!(matches!(updates.next().await, Some(LiveEvent::Changed(_))));
+    }
+
+    #[test]
+    fn test_notifications_round_trip() {
+        let published = Published::Counter {
+            user_id: Uuid::new_v4(),
+            update: Box::new(CounterUpdate {
+                date: date(),
+                entry: entry(1),
+            }),
+        };
+        let payload = serde_json::to_value(&published).unwrap();
+        assert_eq!(payload["event"], "counter");
+        assert_eq!(payload["date"], "2024-01-01");
+        let parsed: Published = serde_json::from_value(payload).unwrap();
+        assert_eq!(parsed.user_id(), published.user_id());
+
+        let resync = serde_json::to_string(&Published::Resync {
+            user_id: Uuid::nil(),
+        })
+        .unwrap();
+        assert!(matches!(
+            serde_json::from_str(&resync).unwrap(),
+            Published::Resync { .. }
+        ));
+    }
+}
@@ -0,0 +1,275 @@
+use axum::{
+    extract::{MatchedPath, State},
+    http::Request,
+    middleware::Next,
+    response::Response,
+};
+use prometheus::{
+    CounterVec, Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::utils::units;
+
+/// Prefix of every metric name.
+const NAMESPACE: &str = "coffee_counter";
+
+/// Auth calls should be far quicker than whole requests, so their buckets start lower.
+const AUTH_BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
+/// Why a token verification failed, as reported in `auth_verification_failures_total`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthFailure {
+    /// The auth service could not be reached or timed out.
+    Unavailable,
+    /// The auth service refused the token.
+    Rejected,
+    /// The auth service answered with something we couldn't use.
+    InvalidResponse,
+}
+
+impl AuthFailure {
+    fn label(self) -> &'static str {
+        match self {
+            AuthFailure::Unavailable => "unavailable",
+            AuthFailure::Rejected => "rejected",
+            AuthFailure::InvalidResponse => "invalid_response",
+        }
+    }
+}
+
+struct PoolGauges {
+    pool: PgPool,
+    connections: IntGaugeVec,
+}
+
+struct Inner {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_duration: HistogramVec,
+    auth_duration: HistogramVec,
+    auth_failures: IntCounterVec,
+    entries: IntCounterVec,
+    entry_amounts: CounterVec,
+    pool: Option<PoolGauges>,
+}
+
+/// The service's Prometheus metrics. Each application gets its own registry,
+/// rendered by `GET /metrics`.
+#[derive(Clone)]
+pub struct Metrics {
+    inner: Arc<Inner>,
+}
+
+impl Metrics {
+    /// Pool gauges are only registered when there is a Postgres pool;
+    /// `max_connections` is the configured pool size.
+    pub fn new(pool: Option<PgPool>, max_connections: u32) -> Self {
+        let registry = Registry::new_custom(Some(NAMESPACE.to_string()), None)
+            .expect("metrics namespace is valid");
+
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests by route and status"),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let http_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "HTTP request latency by route and status",
+            ),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let auth_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "auth_verification_duration_seconds",
+                "Latency of token verification calls to the auth service",
+            )
+            .buckets(AUTH_BUCKETS.to_vec()),
+            &["outcome"],
+        )
+        .unwrap();
+        let auth_failures = IntCounterVec::new(
+            Opts::new(
+                "auth_verification_failures_total",
+                "Failed token verifications by reason",
+            ),
+            &["reason"],
+        )
+        .unwrap();
+        let entries = IntCounterVec::new(
+            Opts::new(
+                "counter_entries_total",
+                "Counter entries logged by category unit",
+            ),
+            &["unit"],
+        )
+        .unwrap();
+        let entry_amounts = CounterVec::new(
+            Opts::new(
+                "counter_entry_amount_total",
+                "Sum of the amounts logged by category unit",
+            ),
+            &["unit"],
+        )
+        .unwrap();
+
+        registry.register(Box::new(http_requests.clone())).unwrap();
+        registry.register(Box::new(http_duration.clone())).unwrap();
+        registry.register(Box::new(auth_duration.clone())).unwrap();
+        registry.register(Box::new(auth_failures.clone())).unwrap();
+        registry.register(Box::new(entries.clone())).unwrap();
+        registry.register(Box::new(entry_amounts.clone())).unwrap();
+
+        let pool = pool.map(|pool| {
+            let connections = IntGaugeVec::new(
+                Opts::new("db_pool_connections", "Database pool connections by state"),
+                &["state"],
+            )
+            .unwrap();
+            let max =
+                IntGauge::new("db_pool_max_connections", "Configured database pool size").unwrap();
+            max.set(i64::from(max_connections));
+
+            registry.register(Box::new(connections.clone())).unwrap();
+            registry.register(Box::new(max)).unwrap();
+
+            PoolGauges { pool, connections }
+        });
+
+        Self {
+            inner: Arc::new(Inner {
+                registry,
+                http_requests,
+                http_duration,
+                auth_duration,
+                auth_failures,
+                entries,
+                entry_amounts,
+                pool,
+            }),
+        }
+    }
+
+    pub fn observe_request(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        let status = status.to_string();
+        let labels = [method, route, status.as_str()];
+
+        self.inner.http_requests.with_label_values(&labels).inc();
+        self.inner
+            .http_duration
+            .with_label_values(&labels)
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn observe_auth_verification(&self, elapsed: Duration, failure: Option<AuthFailure>) {
+        let outcome = if failure.is_some() {
+            "failure"
+        } else {
+            "success"
+        };
+        self.inner
+            .auth_duration
+            .with_label_values(&[outcome])
+            .observe(elapsed.as_secs_f64());
+
+        if let Some(failure) = failure {
+            self.inner
+                .auth_failures
+                .with_label_values(&[failure.label()])
+                .inc();
+        }
+    }
+
+    /// Units are free text, so anything but the units we know is counted as
+    /// `other` to keep the number of series bounded.
+    pub fn entry_logged(&self, unit: &str, amount: f64) {
+        let unit = units::canonical(unit).unwrap_or("other");
+
+        self.inner.entries.with_label_values(&[unit]).inc();
+        self.inner
+            .entry_amounts
+            .with_label_values(&[unit])
+            .inc_by(amount);
+    }
+
+    /// Everything in the Prometheus text format, with the pool gauges read
+    /// at the time of the scrape.
+    pub fn render(&self) -> Result<String, prometheus::Error> {
+        if let Some(gauges) = &self.inner.pool {
+            let size = i64::from(gauges.pool.size());
+            let idle = gauges.pool.num_idle() as i64;
+            gauges.connections.with_label_values(&["idle"]).set(idle);
+            gauges
+                .connections
+                .with_label_values(&["in_use"])
+                .set(size - idle);
+        }
+
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.inner.registry.gather(), &mut buffer)?;
+
+        String::from_utf8(buffer).map_err(|error| prometheus::Error::Msg(error.to_string()))
+    }
+}
+
+/// Middleware counting requests and their latency. Routes are labelled by
+/// their pattern (`/api/categories/:id`) rather than the actual path.
+pub async fn track_requests<B>(
+    State(metrics): State<Metrics>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let started = Instant::now();
+    let method = request.method().clone();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    let response = next.run(request).await;
+
+    metrics.observe_request(
+        method.as_str(),
+        &route,
+        response.status().as_u16(),
+        started.elapsed(),
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_includes_recorded_values() {
+        let metrics = Metrics::new(None, 20);
+        metrics.observe_request("GET", "/api/counters", 200, Duration::from_millis(5));
+        metrics.entry_logged(" ML ", 250.0);
+        metrics.entry_logged("shots", 1.0);
+        metrics.observe_auth_verification(Duration::from_millis(2), Some(AuthFailure::Rejected));
+
+        let text = metrics.render().unwrap();
+
+        assert!(text.contains(
+            r#"coffee_counter_http_requests_total{method="GET",route="/api/counters",status="200"} 1"#
+        ));
+        assert!(text.contains(r#"coffee_counter_counter_entries_total{unit="ml"} 1"#));
+        assert!(text.contains(r#"coffee_counter_counter_entries_total{unit="other"} 1"#));
+        assert!(text.contains(r#"coffee_counter_counter_entry_amount_total{unit="ml"} 250"#));
+        assert!(text
+            .contains(r#"coffee_counter_auth_verification_failures_total{reason="rejected"} 1"#));
+        assert!(text.contains(
+            r#"coffee_counter_auth_verification_duration_seconds_count{outcome="failure"} 1"#
+        ));
+        assert!(!text.contains("db_pool"));
+    }
+}
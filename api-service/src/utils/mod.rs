@@ -1 +1,13 @@
+pub mod audit;
 pub mod auth;
+pub mod cors;
+pub mod etag;
+pub mod idempotency;
+pub mod live;
+pub mod metrics;
+pub mod rate_limit;
+pub mod request_id;
+pub mod telemetry;
+pub mod trash;
+pub mod units;
+pub mod version;
@@ -0,0 +1,283 @@
+use axum::{
+    extract::{FromRequestParts, State},
+    http::{HeaderMap, HeaderName, HeaderValue, Method, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use serde_json::json;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::config::{RateLimitConfig, RateLimitRule};
+use crate::utils::auth::AuthenticatedUser;
+use crate::utils::version::unversioned;
+
+pub const X_RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("x-ratelimit-limit");
+pub const X_RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("x-ratelimit-remaining");
+pub const X_RATELIMIT_RESET: HeaderName = HeaderName::from_static("x-ratelimit-reset");
+
+/// A rule with its methods parsed; rules are validated with the configuration.
+struct Rule {
+    methods: Vec<Method>,
+    path_prefix: String,
+    limit: u32,
+    window: Duration,
+}
+
+impl Rule {
+    fn matches(&self, method: &Method, path: &str) -> bool {
+        let path_matches = match path.strip_prefix(self.path_prefix.trim_end_matches('/')) {
+            Some(rest) => rest.is_empty() || rest.starts_with('/'),
+            None => false,
+        };
+
+        path_matches && (self.methods.is_empty() || self.methods.contains(method))
+    }
+}
+
+struct Window {
+    started: Instant,
+    count: u32,
+}
+
+struct Windows {
+    /// Keyed by user and the index of the rule the request fell under.
+    entries: HashMap<(Uuid, usize), Window>,
+    last_sweep: Instant,
+}
+
+/// Outcome of counting one request against its window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Time until the window restarts.
+    pub reset: Duration,
+}
+
+impl Decision {
+    fn reset_secs(&self) -> u64 {
+        let secs = self.reset.as_secs() + u64::from(self.reset.subsec_nanos() > 0);
+        secs.max(1)
+    }
+
+    fn apply_headers(&self, headers: &mut HeaderMap) {
+        headers.insert(X_RATELIMIT_LIMIT, HeaderValue::from(self.limit));
+        headers.insert(X_RATELIMIT_REMAINING, HeaderValue::from(self.remaining));
+        headers.insert(X_RATELIMIT_RESET, HeaderValue::from(self.reset_secs()));
+    }
+}
+
+/// Fixed-window request counts per user and route class. Each rule is its
+/// own class, so heavy reading doesn't use up a user's write allowance.
+/// Counts live in this process only; each replica limits independently.
+#[derive(Clone)]
+pub struct RateLimiter {
+    rules: Arc<Vec<Rule>>,
+    windows: Arc<Mutex<Windows>>,
+}
+
+impl RateLimiter {
+    pub fn new(rules: &[RateLimitRule]) -> Self {
+        let rules = rules
+            .iter()
+            .map(|rule| Rule {
+                methods: rule
+                    .methods
+                    .iter()
+                    .filter_map(|method| Method::from_str(&method.to_ascii_uppercase()).ok())
+                    .collect(),
+                path_prefix: rule.path_prefix.clone(),
+                limit: rule.requests,
+                window: Duration::from_secs(rule.window_secs),
+            })
+            .collect();
+
+        Self {
+            rules: Arc::new(rules),
+            windows: Arc::new(Mutex::new(Windows {
+                entries: HashMap::new(),
+                last_sweep: Instant::now(),
+            })),
+        }
+    }
+
+    pub fn from_config(config: &RateLimitConfig) -> Self {
+        Self::new(&config.rules)
+    }
+
+    /// The first rule matching the request, if any; unmatched requests are not
+    /// limited. Versioned paths match as their unversioned alias.
+    pub fn classify(&self, method: &Method, path: &str) -> Option<usize> {
+        let path = unversioned(path);
+        self.rules
+            .iter()
+            .position(|rule| rule.matches(method, &path))
+    }
+
+    pub fn check(&self, user_id: Uuid, rule: usize) -> Decision {
+        self.check_at(user_id, rule, Instant::now())
+    }
+
+    fn check_at(&self, user_id: Uuid, index: usize, now: Instant) -> Decision {
+        let rule = &self.rules[index];
+        let mut windows = self.windows.lock().unwrap();
+
+        // Forget users whose windows have all run out, at most once per longest window
+        let longest = self
+            .rules
+            .iter()
+            .map(|rule| rule.window)
+            .max()
+            .unwrap_or_default();
+        if now.duration_since(windows.last_sweep) >= longest {
+            let rules = &self.rules;
+            windows.entries.retain(|(_, index), window| {
+                now.duration_since(window.started) < rules[*index].window
+            });
+            windows.last_sweep = now;
+        }
+
+        let window = windows.entries.entry((user_id, index)).or_insert(Window {
+            started: now,
+            count: 0,
+        });
+        if now.duration_since(window.started) >= rule.window {
+            *window = Window {
+                started: now,
+                count: 0,
+            };
+        }
+
+        let reset = rule
+            .window
+            .saturating_sub(now.duration_since(window.started));
+        let allowed = window.count < rule.limit;
+        if allowed {
+            window.count += 1;
+        }
+
+        Decision {
+            allowed,
+            limit: rule.limit,
+            remaining: rule.limit - window.count,
+            reset,
+        }
+    }
+}
+
+/// Middleware limiting authenticated requests by user and route class.
+/// It authenticates the request itself and leaves the user in the request
+/// extensions for the handler's extractor. Requests that fail authentication
+/// get the extractor's rejection straight away rather than being verified
+/// a second time by the handler.
+pub async fn rate_limit<B: Send>(
+    State(limiter): State<RateLimiter>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let Some(rule) = limiter.classify(request.method(), request.uri().path()) else {
+        return next.run(request).await;
+    };
+
+    let (mut parts, body) = request.into_parts();
+    let user = match AuthenticatedUser::from_request_parts(&mut parts, &()).await {
+        Ok(user) => user,
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    let decision = limiter.check(user.user_id, rule);
+    if !decision.allowed {
+        tracing::warn!(
+            "Rate limit exceeded for user {} on {}",
+            user.user_id,
+            parts.uri.path()
+        );
+
+        let mut response = (
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(json!({
+                "success": false,
+                "message": "Too many requests, please slow down"
+            })),
+        )
+            .into_response();
+        decision.apply_headers(response.headers_mut());
+        response.headers_mut().insert(
+            axum::http::header::RETRY_AFTER,
+            HeaderValue::from(decision.reset_secs()),
+        );
+        return response;
+    }
+
+    parts.extensions.insert(user);
+    let mut response = next.run(Request::from_parts(parts, body)).await;
+    decision.apply_headers(response.headers_mut());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(methods: &[&str], path_prefix: &str, requests: u32) -> RateLimitRule {
+        RateLimitRule {
+            name: path_prefix.to_string(),
+            methods: methods.iter().map(|method| method.to_string()).collect(),
+            path_prefix: path_prefix.to_string(),
+            requests,
+            window_secs: 60,
+        }
+    }
+
+    #[test]
+    fn test_first_matching_rule_classifies() {
+        let limiter =
+            RateLimiter::new(&[rule(&["POST"], "/api/counters", 10), rule(&[], "/api", 100)]);
+
+        assert_eq!(limiter.classify(&Method::POST, "/api/counters"), Some(0));
+        assert_eq!(limiter.classify(&Method::POST, "/api/v1/counters"), Some(0));
+        assert_eq!(limiter.classify(&Method::GET, "/api/counters"), Some(1));
+        assert_eq!(limiter.classify(&Method::POST, "/api/countersx"), Some(1));
+        assert_eq!(limiter.classify(&Method::GET, "/apix"), None);
+        assert_eq!(limiter.classify(&Method::GET, "/health"), None);
+    }
+
+    #[test]
+    fn test_window_counts_per_user_and_resets() {
+        let limiter = RateLimiter::new(&[rule(&[], "/api", 2)]);
+        let alice = Uuid::new_v4();
+        let bob = Uuid::new_v4();
+        let start = Instant::now();
+
+        assert_eq!(limiter.check_at(alice, 0, start).remaining, 1);
+        assert_eq!(limiter.check_at(alice, 0, start).remaining, 0);
+
+        let blocked = limiter.check_at(alice, 0, start + Duration::from_millis(20_500));
+        assert!(!blocked.allowed);
+        assert_eq!(blocked.reset_secs(), 40);
+
+        assert!(limiter.check_at(bob, 0, start).allowed);
+
+        let next_window = limiter.check_at(alice, 0, start + Duration::from_secs(60));
+        assert!(next_window.allowed);
+        assert_eq!(next_window.remaining, 1);
+    }
+
+    #[test]
+    fn test_expired_windows_are_swept() {
+        let limiter = RateLimiter::new(&[rule(&[], "/api", 5)]);
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            limiter.check_at(Uuid::new_v4(), 0, start);
+        }
+        limiter.check_at(Uuid::new_v4(), 0, start + Duration::from_secs(61));
+
+        assert_eq!(limiter.windows.lock().unwrap().entries.len(), 1);
+    }
+}
@@ -0,0 +1,54 @@
+use axum::{
+    body::{self, Full},
+    http::{header, HeaderName, Request},
+    middleware::Next,
+    response::Response,
+};
+use serde_json::Value;
+use tower_http::request_id::RequestId;
+
+pub const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+pub(crate) fn request_id<B>(request: &Request<B>) -> Option<&str> {
+    request
+        .extensions()
+        .get::<RequestId>()
+        .and_then(|id| id.header_value().to_str().ok())
+}
+
+/// Middleware adding the request ID to JSON error bodies, so a user
+/// reporting a failure can quote it without digging through headers.
+pub async fn echo_request_id<B>(request: Request<B>, next: Next<B>) -> Response {
+    let id = request_id(&request).map(str::to_string);
+    let response = next.run(request).await;
+
+    let is_error = response.status().is_client_error() || response.status().is_server_error();
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    let Some(id) = id.filter(|_| is_error && is_json) else {
+        return response;
+    };
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match hyper::body::to_bytes(body).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::error!("Failed to read error response body: {}", e);
+            return Response::from_parts(parts, body::boxed(Full::default()));
+        }
+    };
+
+    let bytes = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(Value::Object(mut object)) => {
+            object.insert("request_id".to_string(), Value::String(id));
+            parts.headers.remove(header::CONTENT_LENGTH);
+            serde_json::to_vec(&object).map(Into::into).unwrap_or(bytes)
+        }
+        _ => bytes,
+    };
+
+    Response::from_parts(parts, body::boxed(Full::from(bytes)))
+}
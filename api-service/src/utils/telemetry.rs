@@ -0,0 +1,177 @@
+use axum::{
+    extract::MatchedPath,
+    http::{HeaderMap, Request, Response},
+};
+use opentelemetry::{global, KeyValue};
+use opentelemetry_http::{HeaderExtractor, HeaderInjector};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{
+    propagation::TraceContextPropagator,
+    trace::{self, Sampler, Tracer},
+    Resource,
+};
+use std::time::Duration;
+use tower_http::trace::{DefaultOnResponse, OnResponse};
+use tracing::{field, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::config::TelemetryConfig;
+use crate::utils::request_id::request_id;
+
+/// Starts exporting spans over OTLP/HTTP when a collector is configured,
+/// and makes W3C trace context the propagation format. Returns `None`
+/// (and leaves propagation off) otherwise. Needs a Tokio runtime.
+pub fn init_tracer(config: &TelemetryConfig) -> anyhow::Result<Option<Tracer>> {
+    let Some(endpoint) = &config.otlp_endpoint else {
+        return Ok(None);
+    };
+
+    // Follow the caller's sampling decision; sample our own roots by ratio
+    let sampler = Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(config.sample_ratio)));
+    let resource = Resource::new([
+        KeyValue::new("service.name", config.service_name.clone()),
+        KeyValue::new("service.version", env!("CARGO_PKG_VERSION")),
+    ]);
+
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .http()
+                .with_endpoint(endpoint),
+        )
+        .with_trace_config(
+            trace::config()
+                .with_sampler(sampler)
+                .with_resource(resource),
+        )
+        .install_batch(opentelemetry_sdk::runtime::Tokio)?;
+
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    Ok(Some(tracer))
+}
+
+/// Exports the spans still buffered; call before the process exits.
+pub fn shutdown_tracer() {
+    global::shutdown_tracer_provider();
+}
+
+/// The span every request's logs are recorded in, continuing the caller's
+/// trace when it sent a `traceparent`. `user_id` is filled in once the
+/// request has been authenticated, the status once it has been answered.
+pub fn make_span<B>(request: &Request<B>) -> Span {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str())
+        .unwrap_or("unmatched");
+
+    let span = tracing::info_span!(
+        "request",
+        otel.name = %format_args!("{} {}", request.method(), route),
+        otel.kind = "server",
+        otel.status_code = field::Empty,
+        method = %request.method(),
+        uri = %request.uri(),
+        route,
+        request_id = request_id(request).unwrap_or("-"),
+        user_id = field::Empty,
+        http.status_code = field::Empty,
+    );
+
+    let parent = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(request.headers()))
+    });
+    span.set_parent(parent);
+
+    span
+}
+
+/// Records the status on the request span, besides the usual trace event.
+pub fn on_response<B>(response: &Response<B>, latency: Duration, span: &Span) {
+    let status = response.status();
+    span.record("http.status_code", status.as_u16());
+    if status.is_server_error() {
+        span.record("otel.status_code", "ERROR");
+    }
+
+    DefaultOnResponse::default().on_response(response, latency, span);
+}
+
+/// Span for a call to another service, to be passed to `inject_context`.
+pub fn client_span(method: &str, url: &str) -> Span {
+    tracing::info_span!(
+        "http.client",
+        otel.name = %format_args!("{} {}", method, url),
+        otel.kind = "client",
+        http.method = method,
+        http.url = url,
+        http.status_code = field::Empty,
+    )
+}
+
+/// Adds `span`'s trace context to outgoing request headers, so the other
+/// service's spans join the same trace.
+pub fn inject_context(span: &Span, headers: &mut HeaderMap) {
+    let context = span.context();
+
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, &mut HeaderInjector(headers))
+    });
+}
+
+/// Span for one Postgres statement, named the OpenTelemetry way
+/// (`SELECT categories`).
+pub fn query_span(operation: &'static str, table: &'static str) -> Span {
+    tracing::info_span!(
+        "db.query",
+        otel.name = %format_args!("{} {}", operation, table),
+        otel.kind = "client",
+        db.system = "postgresql",
+        db.operation = operation,
+        db.sql.table = table,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use opentelemetry::trace::TracerProvider as _;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_trace_context_is_continued_and_propagated() {
+        let provider = opentelemetry_sdk::trace::TracerProvider::builder().build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        global::set_text_map_propagator(TraceContextPropagator::new());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+            let request = Request::builder()
+                .uri("/api/counters")
+                .header(
+                    "traceparent",
+                    format!("00-{}-00f067aa0ba902b7-01", trace_id),
+                )
+                .body(Body::empty())
+                .unwrap();
+
+            let span = make_span(&request);
+            let _entered = span.enter();
+            let outgoing = client_span("POST", "http://auth/api/auth/verify");
+            let mut headers = HeaderMap::new();
+            inject_context(&outgoing, &mut headers);
+
+            let traceparent = headers["traceparent"].to_str().unwrap();
+            assert!(
+                traceparent.starts_with(&format!("00-{}-", trace_id)),
+                "{}",
+                traceparent
+            );
+            assert!(!traceparent.contains("00f067aa0ba902b7"));
+        });
+    }
+}
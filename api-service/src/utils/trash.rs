@@ -0,0 +1,104 @@
+use sqlx::PgPool;
+use std::time::Duration;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::db::DynTrashRepository;
+use crate::utils::telemetry::query_span;
+
+#[derive(Debug, Clone)]
+pub struct TrashSettings {
+    /// Archived categories older than this many days are purged; 0 keeps them forever.
+    pub retention_days: i64,
+    pub purge_interval: Duration,
+}
+
+impl TrashSettings {
+    pub fn new(retention_days: i64) -> Self {
+        Self {
+            retention_days,
+            purge_interval: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+/// Permanently deletes categories that have been in the trash longer than the
+/// retention period, together with their counter history. Each purge is
+/// written to the audit log as a system action, with the category's counter
+/// history under `counter_data` in its before data.
+///
+/// Goes one user at a time: like every writer, each user's purge takes the
+/// lock ordering their change sequence (see the `0006_change_feed`
+/// migration) before any row locks, so it can't deadlock with a concurrent
+/// restore.
+pub async fn purge_expired(pool: &PgPool, retention_days: i64) -> Result<u64, sqlx::Error> {
+    let users: Vec<Uuid> = sqlx::query_scalar(
+        "SELECT DISTINCT user_id FROM categories WHERE deleted_at < NOW() - make_interval(days => $1)",
+    )
+    .bind(retention_days as i32)
+    .fetch_all(pool)
+    .instrument(query_span("SELECT", "categories"))
+    .await?;
+
+    let mut purged = 0;
+    for user_id in users {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query("SELECT lock_change_seq($1)")
+            .bind(user_id)
+            .execute(&mut tx)
+            .instrument(query_span("SELECT", "change_seq"))
+            .await?;
+
+        // Checked again under the lock, as a category may have been restored.
+        // The statement's snapshot predates the cascade, so the counter rows
+        // it removes are still there to record.
+        let result = sqlx::query(
+            r#"
+            WITH purged AS (
+                DELETE FROM categories
+                WHERE user_id = $1 AND deleted_at < NOW() - make_interval(days => $2)
+                RETURNING *
+            )
+            INSERT INTO audit_log (user_id, actor_id, action, entity_type, entity_id, before_data)
+            SELECT user_id, $3, 'category.purge', 'category', id,
+                to_jsonb(purged) || jsonb_build_object('counter_data', COALESCE(
+                    (SELECT jsonb_agg(to_jsonb(cd) ORDER BY cd.date)
+                     FROM counter_data cd WHERE cd.category_id = purged.id),
+                    '[]'::jsonb
+                ))
+            FROM purged
+            "#,
+        )
+        .bind(user_id)
+        .bind(retention_days as i32)
+        .bind(Uuid::nil())
+        .execute(&mut tx)
+        .instrument(query_span("DELETE", "categories"))
+        .await?;
+
+        tx.commit().await?;
+        purged += result.rows_affected();
+    }
+
+    Ok(purged)
+}
+
+pub fn spawn_purge_task(trash: DynTrashRepository, settings: TrashSettings) {
+    if settings.retention_days <= 0 {
+        tracing::info!("Category trash purge disabled");
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(settings.purge_interval);
+        loop {
+            interval.tick().await;
+            match trash.purge_trash(settings.retention_days).await {
+                Ok(0) => {}
+                Ok(purged) => tracing::info!("Purged {} archived categories", purged),
+                Err(e) => tracing::error!("Failed to purge archived categories: {}", e),
+            }
+        }
+    });
+}
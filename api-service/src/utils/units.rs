@@ -0,0 +1,80 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Dimension {
+    Volume,
+    Mass,
+}
+
+/// Standard spelling of a unit, its dimension and its size expressed in the
+/// dimension's base unit.
+fn lookup(unit: &str) -> Option<(&'static str, Dimension, f64)> {
+    let unit = match unit.trim().to_lowercase().as_str() {
+        "ml" => ("ml", Dimension::Volume, 1.0),
+        "cl" => ("cl", Dimension::Volume, 10.0),
+        "dl" => ("dl", Dimension::Volume, 100.0),
+        "l" => ("l", Dimension::Volume, 1000.0),
+        "fl oz" | "floz" => ("fl oz", Dimension::Volume, 29.5735),
+        "cup" | "cups" => ("cup", Dimension::Volume, 236.588),
+        "mg" => ("mg", Dimension::Mass, 0.001),
+        "g" => ("g", Dimension::Mass, 1.0),
+        "kg" => ("kg", Dimension::Mass, 1000.0),
+        "oz" => ("oz", Dimension::Mass, 28.3495),
+        "lb" | "lbs" => ("lb", Dimension::Mass, 453.592),
+        _ => return None,
+    };
+
+    Some(unit)
+}
+
+/// The standard spelling of a unit we know how to convert, e.g. `cup` for
+/// ` Cups`; `None` for anything else.
+pub fn canonical(unit: &str) -> Option<&'static str> {
+    lookup(unit).map(|(name, _, _)| name)
+}
+
+/// Factor that converts an amount in `from` into `to`, if the two units are
+/// the same or measure the same dimension.
+pub fn conversion_factor(from: &str, to: &str) -> Option<f64> {
+    if from.trim().eq_ignore_ascii_case(to.trim()) {
+        return Some(1.0);
+    }
+
+    let (_, from_dimension, from_size) = lookup(from)?;
+    let (_, to_dimension, to_size) = lookup(to)?;
+
+    if from_dimension != to_dimension {
+        return None;
+    }
+
+    Some(from_size / to_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_unit_converts_one_to_one() {
+        assert_eq!(conversion_factor("ml", "ml"), Some(1.0));
+        assert_eq!(conversion_factor("Shots", "shots"), Some(1.0));
+    }
+
+    #[test]
+    fn test_converts_within_dimension() {
+        assert_eq!(conversion_factor("l", "ml"), Some(1000.0));
+        assert_eq!(conversion_factor("mg", "g"), Some(0.001));
+        assert_eq!(conversion_factor(" KG ", "g"), Some(1000.0));
+    }
+
+    #[test]
+    fn test_rejects_incompatible_units() {
+        assert_eq!(conversion_factor("ml", "g"), None);
+        assert_eq!(conversion_factor("cups", "shots"), None);
+    }
+
+    #[test]
+    fn test_canonical_spelling() {
+        assert_eq!(canonical(" Cups"), Some("cup"));
+        assert_eq!(canonical("FLOZ"), Some("fl oz"));
+        assert_eq!(canonical("shots"), None);
+    }
+}
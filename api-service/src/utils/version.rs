@@ -0,0 +1,203 @@
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, OriginalUri, State},
+    http::{header, request::Parts, HeaderName, HeaderValue, Request},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::convert::Infallible;
+
+pub const V1_MEDIA_TYPE: &str = "application/vnd.coffee-counter.v1+json";
+pub const V2_MEDIA_TYPE: &str = "application/vnd.coffee-counter.v2+json";
+
+pub const DEPRECATION: HeaderName = HeaderName::from_static("deprecation");
+pub const SUNSET: HeaderName = HeaderName::from_static("sunset");
+
+/// When the unversioned routes were deprecated (2026-10-19, the release
+/// that introduced `/api/v1`), as an RFC 9745 `Deprecation` date.
+const LEGACY_DEPRECATED_AT: &str = "@1792368000";
+
+/// Response shape of a request. Routes under `/api/v1` and `/api/v2` fix it
+/// through an `ApiVersion` extension; the unversioned aliases negotiate it on
+/// the `Accept` header, where plain `application/json` means v1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ApiVersion {
+    #[default]
+    V1,
+    V2,
+}
+
+impl ApiVersion {
+    pub fn from_accept(accept: &str) -> Self {
+        let requested = accept
+            .split(',')
+            .filter_map(|range| range.split(';').next())
+            .map(|media_type| media_type.trim().to_ascii_lowercase());
+
+        for media_type in requested {
+            if media_type == V2_MEDIA_TYPE {
+                return ApiVersion::V2;
+            }
+            if media_type == V1_MEDIA_TYPE {
+                return ApiVersion::V1;
+            }
+        }
+
+        ApiVersion::V1
+    }
+
+    pub fn media_type(self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "application/json",
+            ApiVersion::V2 => V2_MEDIA_TYPE,
+        }
+    }
+
+    /// Serializes `body` as JSON labelled with this version's media type.
+    pub fn respond<T: Serialize>(self, body: T) -> Response {
+        let mut response = Json(body).into_response();
+        let headers = response.headers_mut();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(self.media_type()),
+        );
+        headers.insert(header::VARY, HeaderValue::from_static("Accept"));
+        response
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ApiVersion
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(version) = parts.extensions.get::<ApiVersion>() {
+            return Ok(*version);
+        }
+
+        Ok(parts
+            .headers
+            .get(header::ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .map(ApiVersion::from_accept)
+            .unwrap_or_default())
+    }
+}
+
+/// `path` with any `/api/v<N>` prefix replaced by `/api`, so the versioned
+/// routes and their unversioned aliases can be treated alike.
+pub fn unversioned(path: &str) -> Cow<'_, str> {
+    let Some(rest) = path.strip_prefix("/api/v") else {
+        return Cow::Borrowed(path);
+    };
+    let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let rest = &rest[digits..];
+
+    if digits > 0 && (rest.is_empty() || rest.starts_with('/')) {
+        Cow::Owned(format!("/api{}", rest))
+    } else {
+        Cow::Borrowed(path)
+    }
+}
+
+/// Headers marking a response as coming from a deprecated route.
+#[derive(Clone)]
+pub struct Deprecation {
+    sunset: Option<HeaderValue>,
+}
+
+impl Deprecation {
+    /// `sunset` is the day (UTC) the routes are planned to be removed.
+    pub fn new(sunset: Option<NaiveDate>) -> Self {
+        let sunset = sunset.map(|date| {
+            let http_date = date.format("%a, %d %b %Y 00:00:00 GMT").to_string();
+            HeaderValue::from_str(&http_date).expect("formatted dates are valid header values")
+        });
+
+        Self { sunset }
+    }
+}
+
+/// Middleware for the unversioned `/api/...` aliases: adds `Deprecation`,
+/// `Sunset` when one is planned, and a `Link` to the `/api/v1` successor.
+pub async fn deprecated<B>(
+    State(deprecation): State<Deprecation>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    // Nested routers see the path without their prefix
+    let uri = match request.extensions().get::<OriginalUri>() {
+        Some(OriginalUri(uri)) => uri.clone(),
+        None => request.uri().clone(),
+    };
+    let successor = match uri.path().strip_prefix("/api") {
+        Some(rest) => format!("/api/v1{}", rest),
+        None => uri.path().to_string(),
+    };
+
+    let mut response = next.run(request).await;
+
+    let headers = response.headers_mut();
+    headers.insert(DEPRECATION, HeaderValue::from_static(LEGACY_DEPRECATED_AT));
+    if let Some(sunset) = &deprecation.sunset {
+        headers.insert(SUNSET, sunset.clone());
+    }
+    if let Ok(link) = HeaderValue::from_str(&format!("<{}>; rel=\"successor-version\"", successor)) {
+        headers.insert(header::LINK, link);
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_json_is_v1() {
+        assert_eq!(ApiVersion::from_accept("application/json"), ApiVersion::V1);
+        assert_eq!(ApiVersion::from_accept("*/*"), ApiVersion::V1);
+        assert_eq!(ApiVersion::from_accept(""), ApiVersion::V1);
+    }
+
+    #[test]
+    fn test_vendor_media_type_selects_version() {
+        assert_eq!(
+            ApiVersion::from_accept("application/vnd.coffee-counter.v2+json"),
+            ApiVersion::V2
+        );
+        assert_eq!(
+            ApiVersion::from_accept("text/html, Application/VND.coffee-counter.v2+json; q=0.9"),
+            ApiVersion::V2
+        );
+        assert_eq!(
+            ApiVersion::from_accept("application/vnd.coffee-counter.v1+json"),
+            ApiVersion::V1
+        );
+    }
+
+    #[test]
+    fn test_respond_sets_media_type() {
+        let response = ApiVersion::V2.respond(serde_json::json!({ "ok": true }));
+
+        assert_eq!(response.headers()[header::CONTENT_TYPE], V2_MEDIA_TYPE);
+        assert_eq!(response.headers()[header::VARY], "Accept");
+    }
+
+    #[test]
+    fn test_unversioned_strips_version_prefix() {
+        assert_eq!(unversioned("/api/v1/counters"), "/api/counters");
+        assert_eq!(unversioned("/api/v2"), "/api");
+        assert_eq!(unversioned("/api/v12/categories/1"), "/api/categories/1");
+        assert_eq!(unversioned("/api/counters"), "/api/counters");
+        assert_eq!(unversioned("/api/v/counters"), "/api/v/counters");
+        assert_eq!(unversioned("/api/videos"), "/api/videos");
+        assert_eq!(unversioned("/health"), "/health");
+    }
+}
@@ -1,4 +1,6 @@
 // Authentication and authorization tests
+// Mock fixtures kept as written; the lints below only flag their style
+#![allow(unused_variables, clippy::const_is_empty, clippy::len_zero)]
 
 use serde_json::json;
 use uuid::Uuid;
@@ -0,0 +1,193 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{header, Method, Request, StatusCode};
+use chrono::{Duration, Utc};
+use common::{backend_tests, TestApp, TestUser};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+async fn batch(app: &TestApp, user: &TestUser, operations: &[Value]) -> (StatusCode, Value) {
+    app.send(
+        Method::POST,
+        "/api/v1/counters/batch",
+        Some(user),
+        Some(json!({ "operations": operations })),
+    )
+    .await
+}
+
+/// Returns the user the batches were sent as, for backend-specific checks.
+async fn check_batches_apply_each_operation(app: &TestApp) -> TestUser {
+    let alice = app.create_user("alice").await;
+    let coffee = app.create_category(&alice, "Coffee", "ml").await;
+    let water = app.create_category(&alice, "Water", "ml").await;
+    let now = Utc::now();
+    let yesterday = now - Duration::days(1);
+    let date = yesterday.date_naive().to_string();
+
+    // Sent out of order: the edit was made after the add it corrects
+    let operations = [
+        json!({
+            "id": Uuid::new_v4(), "recorded_at": yesterday + Duration::minutes(5),
+            "op": "edit", "category_id": coffee["id"], "date": date, "amounts": [100.0, 150.0]
+        }),
+        json!({
+            "id": Uuid::new_v4(), "recorded_at": yesterday,
+            "op": "add", "category_id": coffee["id"], "amount": 250.0
+        }),
+        json!({
+            "id": Uuid::new_v4(), "recorded_at": now,
+            "op": "add", "category_id": water["id"], "amount": 500.0, "notes": "sparkling"
+        }),
+        json!({
+            "id": Uuid::new_v4(), "recorded_at": now,
+            "op": "add", "category_id": Uuid::new_v4(), "amount": 250.0
+        }),
+    ];
+
+    let (status, body) = batch(app, &alice, &operations).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let results = body["data"]["results"].as_array().unwrap();
+    assert_eq!(results.len(), 4);
+    for (result, operation) in results.iter().zip(&operations) {
+        assert_eq!(result["id"], operation["id"]);
+    }
+    assert_eq!(results[0]["data"]["amounts"], json!([100.0, 150.0]));
+    assert_eq!(results[1]["data"]["count"], 1);
+    assert_eq!(results[2]["success"], true);
+    assert_eq!(results[3]["success"], false);
+    assert_eq!(results[3]["status"], 404);
+
+    let days = body["data"]["days"].as_array().unwrap();
+    assert_eq!(days.len(), 2);
+    assert_eq!(days[0]["date"], date);
+    assert_eq!(days[0]["categories"][0]["total_amount"], 250.0);
+    assert_eq!(days[1]["categories"][1]["count"], 1);
+
+    // Resending the batch doesn't apply anything twice
+    let (_, resent) = batch(app, &alice, &operations).await;
+    let replays = resent["data"]["results"].as_array().unwrap();
+    assert!(replays[..3].iter().all(|result| result["replayed"] == true));
+    assert_eq!(replays[0]["data"], results[0]["data"]);
+    assert_eq!(replays[3]["status"], 404);
+    assert_eq!(resent["data"]["days"], body["data"]["days"]);
+
+    // An edit based on the add's version comes too late
+    let edited_at = results[0]["data"]["updated_at"].clone();
+    let stale = json!({
+        "id": Uuid::new_v4(), "recorded_at": now, "op": "delete",
+        "category_id": coffee["id"], "date": date, "if_updated_at": results[1]["data"]["updated_at"]
+    });
+    let current = json!({
+        "id": Uuid::new_v4(), "recorded_at": now, "op": "delete",
+        "category_id": coffee["id"], "date": date, "if_updated_at": edited_at
+    });
+    let (_, body) = batch(app, &alice, &[stale, current]).await;
+    let results = body["data"]["results"].as_array().unwrap();
+    assert_eq!(results[0]["status"], 412);
+    assert_eq!(results[0]["data"]["updated_at"], edited_at);
+    assert_eq!(results[1]["success"], true);
+    assert_eq!(body["data"]["days"][0]["categories"][0]["count"], 0);
+
+    alice
+}
+
+#[tokio::test]
+async fn test_postgres_batches_apply_each_operation() {
+    let Some(app) = TestApp::postgres().await else {
+        return;
+    };
+    let alice = check_batches_apply_each_operation(&app).await;
+
+    let (_, body) = app
+        .send(Method::GET, "/api/v1/audit", Some(&alice), None)
+        .await;
+    let actions: Vec<&str> = body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|entry| entry["action"].as_str())
+        .filter(|action| action.starts_with("counter."))
+        .collect();
+    assert_eq!(
+        actions,
+        [
+            "counter.delete",
+            "counter.add",
+            "counter.set",
+            "counter.add"
+        ]
+    );
+}
+
+/// Operation ids and `Idempotency-Key` headers are separate namespaces, even
+/// for keys that look like how batch operations used to be stored.
+async fn check_operation_ids_dont_collide_with_header_keys(app: &TestApp) {
+    let alice = app.create_user("alice").await;
+    let coffee = app.create_category(&alice, "Coffee", "ml").await;
+    let id = Uuid::new_v4();
+
+    for key in [id.to_string(), format!("batch {}", id)] {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/api/v1/counters")
+            .header(header::AUTHORIZATION, format!("Bearer {}", alice.token))
+            .header(header::CONTENT_TYPE, "application/json")
+            .header("idempotency-key", key)
+            .body(Body::from(
+                json!({ "category_id": coffee["id"], "amount": 250.0 }).to_string(),
+            ))
+            .unwrap();
+        let response = app.request(request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get("idempotent-replayed").is_none());
+    }
+
+    let add = json!({
+        "id": id, "recorded_at": Utc::now(), "op": "add",
+        "category_id": coffee["id"], "amount": 100.0
+    });
+    let (status, body) = batch(app, &alice, &[add]).await;
+    assert_eq!(status, StatusCode::OK);
+    let result = &body["data"]["results"][0];
+    assert_eq!(result["success"], true, "{}", result);
+    assert_eq!(result["replayed"], false);
+    assert_eq!(result["data"]["amounts"], json!([250.0, 250.0, 100.0]));
+}
+
+async fn check_batch_validation(app: &TestApp) {
+    let alice = app.create_user("alice").await;
+    let coffee = app.create_category(&alice, "Coffee", "ml").await;
+
+    let (status, _) = batch(app, &alice, &[]).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let add = |amount: f64| {
+        json!({
+            "id": Uuid::new_v4(), "recorded_at": Utc::now(),
+            "op": "add", "category_id": coffee["id"], "amount": amount
+        })
+    };
+    let (status, _) = batch(app, &alice, &vec![add(250.0); 101]).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Invalid operations fail on their own
+    let edit = json!({
+        "id": Uuid::new_v4(), "recorded_at": Utc::now(), "op": "edit",
+        "category_id": coffee["id"], "date": Utc::now().date_naive(), "amounts": [250.0, 0.0]
+    });
+    let (status, body) = batch(app, &alice, &[add(0.0), edit, add(250.0)]).await;
+    assert_eq!(status, StatusCode::OK);
+    let results = body["data"]["results"].as_array().unwrap();
+    assert_eq!(results[0]["status"], 400);
+    assert!(results[0]["errors"]["amount"].is_array());
+    assert_eq!(results[1]["status"], 400);
+    assert_eq!(results[2]["data"]["count"], 1);
+}
+
+backend_tests!(
+    check_batches_apply_each_operation,
+    check_operation_ids_dont_collide_with_header_keys,
+    check_batch_validation,
+);
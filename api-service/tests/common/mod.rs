@@ -0,0 +1,448 @@
+// Shared harness for the integration tests: the real application, backed by
+// in-memory storage, an in-memory SQLite database or a throwaway Postgres
+// schema, behind a stub auth service.
+#![allow(dead_code)]
+
+use axum::{
+    body::{Body, BoxBody},
+    extract::Extension,
+    http::{header, HeaderMap, Method, Request, Response, StatusCode},
+    response::Json,
+    routing::{get, post},
+    Router,
+};
+use coffee_counter_api::app::{app, Storage};
+use coffee_counter_api::config::Config;
+use coffee_counter_api::db::{prepare_schema, MigrationMode};
+#[cfg(feature = "sqlite")]
+use coffee_counter_api::db::SqliteRepository;
+use coffee_counter_api::handlers::health::Readiness;
+use coffee_counter_api::utils::auth::{
+    TokenData, UserInfo, VerifyTokenRequest, VerifyTokenResponse,
+};
+use serde_json::{json, Value};
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::{Connection, PgConnection, PgPool};
+use std::collections::HashMap;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::{Arc, Once, RwLock};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+/// Users created with this name can read the admin endpoints.
+pub const ADMIN: &str = "admin";
+
+/// Connections in each Postgres test's pool.
+pub const POOL_SIZE: u32 = 5;
+
+/// Generates a module of tests for each `check` function, which takes a
+/// `&TestApp`: one per storage backend. The Postgres test is skipped, with a
+/// notice, when `TEST_DATABASE_URL` is not set; the SQLite one is built with
+/// `--features sqlite`.
+#[allow(unused_macros)]
+macro_rules! backend_tests {
+    ($($check:ident),* $(,)?) => {$(
+        mod $check {
+            #[tokio::test]
+            async fn memory() {
+                super::$check(&$crate::common::TestApp::memory().await).await;
+            }
+
+            #[tokio::test]
+            async fn postgres() {
+                let Some(app) = $crate::common::TestApp::postgres().await else {
+                    return;
+                };
+                super::$check(&app).await;
+            }
+
+            #[cfg(feature = "sqlite")]
+            #[tokio::test]
+            async fn sqlite() {
+                super::$check(&$crate::common::TestApp::sqlite().await).await;
+            }
+        }
+    )*};
+}
+#[allow(unused_imports)]
+pub(crate) use backend_tests;
+
+pub struct TestUser {
+    pub id: Uuid,
+    pub email: String,
+    pub token: String,
+}
+
+type Tokens = Arc<RwLock<HashMap<String, (Uuid, String)>>>;
+type Traceparents = Arc<RwLock<Vec<String>>>;
+
+/// Stand-in for the auth service: accepts the tokens it has been told about.
+#[derive(Clone)]
+pub struct StubAuth {
+    addr: SocketAddr,
+    tokens: Tokens,
+    traceparents: Traceparents,
+}
+
+impl StubAuth {
+    pub async fn spawn() -> Self {
+        let tokens = Tokens::default();
+        let traceparents = Traceparents::default();
+        let router = Router::new()
+            .route(
+                "/health",
+                get(|| async { Json(json!({ "status": "healthy" })) }),
+            )
+            .route("/api/auth/verify", post(verify))
+            .layer(Extension(tokens.clone()))
+            .layer(Extension(traceparents.clone()));
+
+        let server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+            .serve(router.into_make_service());
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        Self {
+            addr,
+            tokens,
+            traceparents,
+        }
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    pub fn register(&self, token: &str, user_id: Uuid, email: &str) {
+        self.tokens
+            .write()
+            .unwrap()
+            .insert(token.to_string(), (user_id, email.to_string()));
+    }
+
+    /// The `traceparent` headers sent with verification requests so far.
+    pub fn traceparents(&self) -> Vec<String> {
+        self.traceparents.read().unwrap().clone()
+    }
+}
+
+async fn verify(
+    Extension(tokens): Extension<Tokens>,
+    Extension(traceparents): Extension<Traceparents>,
+    headers: HeaderMap,
+    Json(request): Json<VerifyTokenRequest>,
+) -> (StatusCode, Json<VerifyTokenResponse>) {
+    if let Some(traceparent) = headers.get("traceparent") {
+        let traceparent = traceparent.to_str().unwrap_or_default().to_string();
+        traceparents.write().unwrap().push(traceparent);
+    }
+
+    match tokens.read().unwrap().get(&request.token) {
+        Some((id, email)) => (
+            StatusCode::OK,
+            Json(VerifyTokenResponse {
+                success: true,
+                message: "Token is valid".to_string(),
+                data: Some(TokenData {
+                    user_id: *id,
+                    user: UserInfo {
+                        id: *id,
+                        email: email.clone(),
+                        display_name: None,
+                        is_active: true,
+                    },
+                }),
+            }),
+        ),
+        None => (
+            StatusCode::UNAUTHORIZED,
+            Json(VerifyTokenResponse {
+                success: false,
+                message: "Invalid token".to_string(),
+                data: None,
+            }),
+        ),
+    }
+}
+
+/// A schema created for one test and dropped with it.
+struct TestSchema {
+    database_url: String,
+    name: String,
+}
+
+impl Drop for TestSchema {
+    fn drop(&mut self) {
+        let database_url = self.database_url.clone();
+        let name = self.name.clone();
+        let statement = format!("DROP SCHEMA IF EXISTS {} CASCADE", self.name);
+
+        // Drop can't await, and the test's own runtime may be shutting down
+        let _ = std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            runtime.block_on(async {
+                if let Ok(mut conn) = PgConnection::connect(&database_url).await {
+                    // The test's pool can't roll back a connection released
+                    // by its last request while this blocks its runtime, and
+                    // the locks that connection holds would block the drop
+                    let _ = sqlx::query(
+                        "SELECT pg_terminate_backend(pid) FROM pg_stat_activity WHERE application_name = $1",
+                    )
+                    .bind(&name)
+                    .execute(&mut conn)
+                    .await;
+                    let _ = sqlx::query(&statement).execute(&mut conn).await;
+                }
+            });
+        })
+        .join();
+    }
+}
+
+pub struct TestApp {
+    pub router: Router,
+    pub pool: Option<PgPool>,
+    pub auth: StubAuth,
+    /// Cleared by `main` on shutdown; tests can do the same.
+    pub readiness: Readiness,
+    schema: Option<TestSchema>,
+}
+
+impl TestApp {
+    /// The application on in-memory storage.
+    pub async fn memory() -> Self {
+        Self::memory_with(|_| {}).await
+    }
+
+    /// In-memory storage with adjustments to the default test configuration.
+    pub async fn memory_with(configure: impl FnOnce(&mut Config)) -> Self {
+        Self::spawn(Storage::memory(), None, configure).await
+    }
+
+    /// The application on a fresh, migrated in-memory SQLite database.
+    #[cfg(feature = "sqlite")]
+    pub async fn sqlite() -> Self {
+        let repository = SqliteRepository::connect("sqlite::memory:").await.unwrap();
+        repository.prepare_schema(MigrationMode::Run).await.unwrap();
+
+        Self::spawn(Storage::sqlite(Arc::new(repository)), None, |_| {}).await
+    }
+
+    /// The application on a fresh, fully migrated schema in the database at
+    /// `TEST_DATABASE_URL`. Returns `None` (and the test should return early)
+    /// when that variable is not set, unless `REQUIRE_TEST_DATABASE` is, as
+    /// in CI, where a missing database fails the test instead.
+    pub async fn postgres() -> Option<Self> {
+        let Ok(database_url) = std::env::var("TEST_DATABASE_URL") else {
+            assert!(
+                std::env::var_os("REQUIRE_TEST_DATABASE").is_none(),
+                "REQUIRE_TEST_DATABASE is set but TEST_DATABASE_URL is not"
+            );
+            skip_notice();
+            return None;
+        };
+
+        let name = format!("test_{}", Uuid::new_v4().simple());
+        let mut conn = PgConnection::connect(&database_url)
+            .await
+            .expect("TEST_DATABASE_URL should be reachable");
+
+        // Keep the extension out of the test schema so dropping it leaves
+        // uuid_generate_v4 in place; concurrent tests may race to create it.
+        let _ = sqlx::query(r#"CREATE EXTENSION IF NOT EXISTS "uuid-ossp" WITH SCHEMA public"#)
+            .execute(&mut conn)
+            .await;
+        sqlx::query(&format!("CREATE SCHEMA {}", name))
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        let schema = TestSchema {
+            database_url: database_url.clone(),
+            name,
+        };
+
+        let options = PgConnectOptions::from_str(&database_url)
+            .unwrap()
+            .options([("search_path", format!("{},public", schema.name))])
+            .application_name(&schema.name);
+        let pool = PgPoolOptions::new()
+            .max_connections(POOL_SIZE)
+            .connect_with(options)
+            .await
+            .unwrap();
+        prepare_schema(&pool, MigrationMode::Run).await.unwrap();
+
+        Some(
+            Self::spawn(Storage::postgres(pool), Some(schema), |config| {
+                config.storage.max_connections = POOL_SIZE
+            })
+            .await,
+        )
+    }
+
+    async fn spawn(
+        storage: Storage,
+        schema: Option<TestSchema>,
+        configure: impl FnOnce(&mut Config),
+    ) -> Self {
+        let auth = StubAuth::spawn().await;
+        let pool = storage.pool.clone();
+        let config = Self::config(&auth, configure);
+
+        let readiness = Readiness::new();
+        let router = app(&config, storage, readiness.clone());
+
+        Self {
+            router,
+            pool,
+            auth,
+            readiness,
+            schema,
+        }
+    }
+
+    fn config(auth: &StubAuth, configure: impl FnOnce(&mut Config)) -> Config {
+        let mut config = Config::default();
+        config.auth.service_url = auth.url();
+        config.auth.admin_emails = vec![format!("{}@example.com", ADMIN)];
+        configure(&mut config);
+
+        config
+    }
+
+    /// Another instance of a Postgres application on the same schema and
+    /// auth stub, as a second replica would be. The schema stays with `self`.
+    pub fn replica(&self) -> Self {
+        let pool = self
+            .pool
+            .clone()
+            .expect("replicas share a Postgres database");
+        let config = Self::config(&self.auth, |config| {
+            config.storage.max_connections = POOL_SIZE
+        });
+
+        let readiness = Readiness::new();
+        let router = app(&config, Storage::postgres(pool.clone()), readiness.clone());
+
+        Self {
+            router,
+            pool: Some(pool),
+            auth: self.auth.clone(),
+            readiness,
+            schema: None,
+        }
+    }
+
+    /// Registers a user with the auth stub and, on Postgres, in `users`.
+    pub async fn create_user(&self, name: &str) -> TestUser {
+        let user = TestUser {
+            id: Uuid::new_v4(),
+            email: format!("{}@example.com", name),
+            token: format!("{}-{}", name, Uuid::new_v4().simple()),
+        };
+
+        if let Some(pool) = &self.pool {
+            sqlx::query(
+                "INSERT INTO users (id, email, password_hash, display_name) VALUES ($1, $2, '', $3)",
+            )
+            .bind(user.id)
+            .bind(&user.email)
+            .bind(name)
+            .execute(pool)
+            .await
+            .unwrap();
+        }
+
+        self.auth.register(&user.token, user.id, &user.email);
+        user
+    }
+
+    pub async fn request(&self, request: Request<Body>) -> Response<BoxBody> {
+        self.router.clone().oneshot(request).await.unwrap()
+    }
+
+    /// Sends a JSON request and returns the status with the decoded body
+    /// (`Value::Null` when it isn't JSON).
+    pub async fn send(
+        &self,
+        method: Method,
+        uri: &str,
+        user: Option<&TestUser>,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(user) = user {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", user.token));
+        }
+        let request = match body {
+            Some(body) => request
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string())),
+            None => request.body(Body::empty()),
+        }
+        .unwrap();
+
+        let response = self.request(request).await;
+        let status = response.status();
+
+        (status, json_body(response).await)
+    }
+
+    pub async fn create_category(&self, user: &TestUser, name: &str, unit: &str) -> Value {
+        let (status, body) = self
+            .send(
+                Method::POST,
+                "/api/categories",
+                Some(user),
+                Some(json!({
+                    "name": name,
+                    "icon": "coffee",
+                    "color": "#8B4513",
+                    "unit": unit,
+                    "default_amount": 250.0
+                })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+
+        body["data"].clone()
+    }
+
+    /// Records one serving for today.
+    pub async fn add_entry(&self, user: &TestUser, category: &Value, amount: f64) -> Value {
+        let (status, body) = self
+            .send(
+                Method::POST,
+                "/api/counters",
+                Some(user),
+                Some(json!({ "category_id": category["id"], "amount": amount })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+
+        body["data"].clone()
+    }
+}
+
+/// Tells whoever runs the tests, once per test binary, that the Postgres
+/// tests passed without running. Written to stderr directly, as the test
+/// harness swallows `eprintln!` from passing tests.
+fn skip_notice() {
+    static NOTICE: Once = Once::new();
+    NOTICE.call_once(|| {
+        let _ = writeln!(
+            std::io::stderr(),
+            "note: TEST_DATABASE_URL is not set; Postgres tests in this binary are skipped"
+        );
+    });
+}
+
+pub async fn json_body(response: Response<BoxBody>) -> Value {
+    let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    serde_json::from_slice(&bytes).unwrap_or(Value::Null)
+}
@@ -0,0 +1,122 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, Method, Request, StatusCode},
+};
+use common::TestApp;
+
+async fn cors_app() -> TestApp {
+    TestApp::memory_with(|config| {
+        config.cors.allowed_origins = vec![
+            "https://coffee.example.com".to_string(),
+            "https://*.preview.example.com".to_string(),
+        ];
+        config.cors.allow_credentials = true;
+        config.cors.max_age_secs = 600;
+    })
+    .await
+}
+
+async fn preflight(app: &TestApp, origin: &str, method: &str) -> HeaderMap {
+    let response = app
+        .request(
+            Request::builder()
+                .method(Method::OPTIONS)
+                .uri("/api/categories")
+                .header(header::ORIGIN, origin)
+                .header(header::ACCESS_CONTROL_REQUEST_METHOD, method)
+                .header(
+                    header::ACCESS_CONTROL_REQUEST_HEADERS,
+                    "authorization,content-type",
+                )
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+
+    response.headers().clone()
+}
+
+async fn get_from(app: &TestApp, origin: &str) -> HeaderMap {
+    let response = app
+        .request(
+            Request::builder()
+                .uri("/health")
+                .header(header::ORIGIN, origin)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    response.headers().clone()
+}
+
+#[tokio::test]
+async fn test_allowed_origin_preflight() {
+    let app = cors_app().await;
+
+    let headers = preflight(&app, "https://coffee.example.com", "PATCH").await;
+
+    assert_eq!(
+        headers[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+        "https://coffee.example.com"
+    );
+    assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+    assert_eq!(headers[header::ACCESS_CONTROL_MAX_AGE], "600");
+    let methods = headers[header::ACCESS_CONTROL_ALLOW_METHODS]
+        .to_str()
+        .unwrap();
+    assert!(
+        methods.contains("PATCH") && methods.contains("OPTIONS"),
+        "{}",
+        methods
+    );
+}
+
+#[tokio::test]
+async fn test_wildcard_subdomain_origin_is_allowed() {
+    let app = cors_app().await;
+
+    let headers = get_from(&app, "https://pr-42.preview.example.com").await;
+    assert_eq!(
+        headers[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+        "https://pr-42.preview.example.com"
+    );
+}
+
+#[tokio::test]
+async fn test_disallowed_origins_are_rejected() {
+    let app = cors_app().await;
+
+    for origin in [
+        "https://evil.test",
+        "http://coffee.example.com",
+        "https://preview.example.com",
+        "https://coffee.example.com.evil.test",
+        "https://pr-42.preview.example.com.evil.test",
+    ] {
+        let headers = preflight(&app, origin, "DELETE").await;
+        assert!(
+            !headers.contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN),
+            "{}",
+            origin
+        );
+
+        let headers = get_from(&app, origin).await;
+        assert!(
+            !headers.contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN),
+            "{}",
+            origin
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_no_origins_are_allowed_by_default() {
+    let app = TestApp::memory().await;
+
+    let headers = preflight(&app, "http://localhost:3000", "GET").await;
+    assert!(!headers.contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+}
@@ -1,5 +1,7 @@
 // Database integration tests
 // These tests would require a test database setup
+// Mock fixtures kept as written; the lint below only flags their style
+#![allow(unused_variables)]
 
 use chrono::NaiveDate;
 use serde_json::json;
@@ -0,0 +1,318 @@
+mod common;
+
+use axum::{
+    body::{Body, BoxBody},
+    http::{header, Method, Request, Response, StatusCode},
+};
+use common::{backend_tests, json_body, TestApp, TestUser};
+
+fn get(uri: &str, user: &TestUser, if_none_match: Option<&str>) -> Request<Body> {
+    let mut request = Request::builder()
+        .uri(uri)
+        .header(header::AUTHORIZATION, format!("Bearer {}", user.token));
+    if let Some(etag) = if_none_match {
+        request = request.header(header::IF_NONE_MATCH, etag);
+    }
+
+    request.body(Body::empty()).unwrap()
+}
+
+fn etag(response: &Response<BoxBody>) -> String {
+    response.headers()[header::ETAG]
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+/// The current tag of `uri`, checking that it is served in full.
+async fn current_etag(app: &TestApp, uri: &str, user: &TestUser) -> String {
+    let response = app.request(get(uri, user, None)).await;
+    assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+    etag(&response)
+}
+
+async fn check_unchanged_reads_are_not_modified(app: &TestApp) {
+    let alice = app.create_user("alice").await;
+    let coffee = app.create_category(&alice, "Coffee", "ml").await;
+    app.add_entry(&alice, &coffee, 250.0).await;
+    let today = chrono::Utc::now().date_naive();
+
+    for uri in [
+        "/api/v1/counters".to_string(),
+        format!("/api/v1/counters/{}", today),
+        format!(
+            "/api/v2/counters/range?start_date={}&end_date={}",
+            today, today
+        ),
+        "/api/v1/categories".to_string(),
+        "/api/v1/categories/trash".to_string(),
+    ] {
+        let response = app.request(get(&uri, &alice, None)).await;
+        assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+        assert_eq!(
+            response.headers()[header::CACHE_CONTROL],
+            "private, no-cache"
+        );
+        let tag = etag(&response);
+
+        let response = app.request(get(&uri, &alice, Some(&tag))).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED, "{}", uri);
+        assert_eq!(etag(&response), tag, "{}", uri);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert!(body.is_empty(), "{}", uri);
+
+        // Any other tag gets the full response
+        let response = app.request(get(&uri, &alice, Some("\"stale\""))).await;
+        assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+    }
+}
+
+async fn check_writes_change_the_etag(app: &TestApp) {
+    let alice = app.create_user("alice").await;
+    let coffee = app.create_category(&alice, "Coffee", "ml").await;
+
+    let counters = current_etag(app, "/api/v1/counters", &alice).await;
+    let categories = current_etag(app, "/api/v1/categories", &alice).await;
+    let trash = current_etag(app, "/api/v1/categories/trash", &alice).await;
+
+    app.add_entry(&alice, &coffee, 250.0).await;
+    let after_entry = current_etag(app, "/api/v1/counters", &alice).await;
+    assert_ne!(after_entry, counters);
+    // Entries don't show up in the category list
+    assert_eq!(
+        current_etag(app, "/api/v1/categories", &alice).await,
+        categories
+    );
+
+    let (status, _) = app
+        .send(
+            Method::PUT,
+            &format!("/api/v1/categories/{}", coffee["id"].as_str().unwrap()),
+            Some(&alice),
+            Some(serde_json::json!({ "name": "Espresso" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_ne!(
+        current_etag(app, "/api/v1/categories", &alice).await,
+        categories
+    );
+    // The day lists categories by name, so a rename changes it too
+    assert_ne!(
+        current_etag(app, "/api/v1/counters", &alice).await,
+        after_entry
+    );
+
+    let (status, _) = app
+        .send(
+            Method::DELETE,
+            &format!("/api/v1/categories/{}", coffee["id"].as_str().unwrap()),
+            Some(&alice),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_ne!(
+        current_etag(app, "/api/v1/categories/trash", &alice).await,
+        trash
+    );
+}
+
+async fn check_etags_are_per_user_and_version(app: &TestApp) {
+    let alice = app.create_user("alice").await;
+    let bob = app.create_user("bob").await;
+
+    // Neither has any data, but their responses are still their own
+    let alices = current_etag(app, "/api/v1/counters", &alice).await;
+    let bobs = current_etag(app, "/api/v1/counters", &bob).await;
+    assert_ne!(alices, bobs);
+    let response = app
+        .request(get("/api/v1/counters", &bob, Some(&alices)))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // v1 and v2 bodies differ, so must their tags
+    let v2 = current_etag(app, "/api/v2/counters", &alice).await;
+    assert_ne!(alices, v2);
+    let response = app
+        .request(get("/api/v2/counters", &alice, Some(&alices)))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+async fn check_etags_follow_writes(app: &TestApp) {
+    let alice = app.create_user("alice").await;
+    let coffee = app.create_category(&alice, "Coffee", "ml").await;
+    let today = chrono::Utc::now().date_naive();
+    let range = format!(
+        "/api/v1/counters/range?start_date={}&end_date={}",
+        today, today
+    );
+
+    let day = current_etag(app, "/api/v1/counters", &alice).await;
+    let response = app
+        .request(get("/api/v1/counters", &alice, Some(&day)))
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    let before = current_etag(app, &range, &alice).await;
+
+    app.add_entry(&alice, &coffee, 250.0).await;
+    let after_one = current_etag(app, &range, &alice).await;
+    assert_ne!(after_one, before);
+    app.add_entry(&alice, &coffee, 250.0).await;
+    assert_ne!(current_etag(app, &range, &alice).await, after_one);
+
+    let response = app
+        .request(get("/api/v1/counters", &alice, Some(&day)))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+/// Sends a write with an optional `If-Match`, returning the response.
+async fn write(
+    app: &TestApp,
+    method: Method,
+    uri: &str,
+    user: &TestUser,
+    if_match: Option<&str>,
+    body: Option<serde_json::Value>,
+) -> Response<BoxBody> {
+    let mut request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::AUTHORIZATION, format!("Bearer {}", user.token))
+        .header(header::CONTENT_TYPE, "application/json");
+    if let Some(etag) = if_match {
+        request = request.header(header::IF_MATCH, etag);
+    }
+    let body = body.map(|body| body.to_string()).unwrap_or_default();
+
+    app.request(request.body(Body::from(body)).unwrap()).await
+}
+
+async fn check_if_match_guards_category_writes(app: &TestApp) {
+    let alice = app.create_user("alice").await;
+    let coffee = app.create_category(&alice, "Coffee", "ml").await;
+    let uri = format!("/api/v1/categories/{}", coffee["id"].as_str().unwrap());
+    let rename = |name: &str| Some(serde_json::json!({ "name": name }));
+
+    let read = current_etag(app, &uri, &alice).await;
+
+    // Another device renames it first
+    let response = write(
+        app,
+        Method::PUT,
+        &uri,
+        &alice,
+        Some(&read),
+        rename("Espresso"),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let current = etag(&response);
+    assert_ne!(current, read);
+    assert_eq!(current_etag(app, &uri, &alice).await, current);
+
+    // Writes based on the old version are refused with the current state
+    let response = write(app, Method::PUT, &uri, &alice, Some(&read), rename("Latte")).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    let body = json_body(response).await;
+    assert_eq!(body["success"], false);
+    assert_eq!(body["data"]["name"], "Espresso");
+    let response = write(app, Method::DELETE, &uri, &alice, Some(&read), None).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+
+    let response = write(app, Method::DELETE, &uri, &alice, Some(&current), None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(json_body(response).await["data"]["name"], "Espresso");
+
+    // A restore hands back the version to write against next
+    let restore = format!("{}/restore", uri);
+    let response = write(app, Method::POST, &restore, &alice, None, None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let restored = etag(&response);
+    assert_eq!(current_etag(app, &uri, &alice).await, restored);
+    let response = write(app, Method::PUT, &uri, &alice, Some(&restored), rename("Latte")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+async fn check_writes_without_if_match_are_unconditional(app: &TestApp) {
+    let alice = app.create_user("alice").await;
+    let coffee = app.create_category(&alice, "Coffee", "ml").await;
+    let uri = format!("/api/v1/categories/{}", coffee["id"].as_str().unwrap());
+
+    for name in ["Espresso", "Latte"] {
+        let body = Some(serde_json::json!({ "name": name }));
+        let response = write(app, Method::PUT, &uri, &alice, None, body).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let response = write(app, Method::DELETE, &uri, &alice, Some("*"), None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+async fn check_counter_entries_carry_their_version(app: &TestApp) {
+    let alice = app.create_user("alice").await;
+    let coffee = app.create_category(&alice, "Coffee", "ml").await;
+    app.create_category(&alice, "Water", "ml").await;
+
+    let added = app.add_entry(&alice, &coffee, 250.0).await;
+    assert!(added["updated_at"].is_string());
+
+    let (_, body) = app
+        .send(Method::GET, "/api/v2/counters", Some(&alice), None)
+        .await;
+    let categories = body["data"]["categories"].as_array().unwrap();
+    assert_eq!(categories[0]["updated_at"], added["updated_at"]);
+    assert!(categories[1].get("updated_at").is_none());
+}
+
+async fn check_if_match_guards_counter_writes(app: &TestApp) {
+    let alice = app.create_user("alice").await;
+    let coffee = app.create_category(&alice, "Coffee", "ml").await;
+    let added = app.add_entry(&alice, &coffee, 250.0).await;
+    let read = format!("\"{}\"", added["updated_at"].as_str().unwrap());
+    let today = chrono::Utc::now().date_naive();
+    let uri = format!("/api/v1/counters/{}", today);
+    let edit = |amounts: &[f64]| {
+        Some(serde_json::json!({ "category_id": coffee["id"], "amounts": amounts }))
+    };
+
+    // Another device edits the day first
+    let response = write(app, Method::PUT, &uri, &alice, Some(&read), edit(&[100.0])).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let current = etag(&response);
+    assert_ne!(current, read);
+    assert_eq!(json_body(response).await["data"]["amounts"], serde_json::json!([100.0]));
+
+    // Writes based on the old version are refused with the current entry
+    let response = write(app, Method::PUT, &uri, &alice, Some(&read), edit(&[300.0])).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    let body = json_body(response).await;
+    assert_eq!(body["success"], false);
+    assert_eq!(body["data"]["amounts"], serde_json::json!([100.0]));
+
+    let delete = format!("{}?category_id={}", uri, coffee["id"].as_str().unwrap());
+    let response = write(app, Method::DELETE, &delete, &alice, Some(&read), None).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+
+    let response = write(app, Method::DELETE, &delete, &alice, Some(&current), None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(json_body(response).await["data"]["count"], 0);
+
+    // Nothing is logged any more, so no version matches
+    let response = write(app, Method::PUT, &uri, &alice, Some(&current), edit(&[250.0])).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    let response = write(app, Method::PUT, &uri, &alice, None, edit(&[250.0])).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+backend_tests!(
+    check_unchanged_reads_are_not_modified,
+    check_writes_change_the_etag,
+    check_etags_are_per_user_and_version,
+    check_etags_follow_writes,
+    check_if_match_guards_category_writes,
+    check_writes_without_if_match_are_unconditional,
+    check_counter_entries_carry_their_version,
+    check_if_match_guards_counter_writes,
+);
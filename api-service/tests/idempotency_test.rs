@@ -0,0 +1,118 @@
+mod common;
+
+use axum::{
+    body::{Body, BoxBody},
+    http::{header, Request, Response, StatusCode},
+};
+use common::{backend_tests, json_body, TestApp, TestUser};
+use serde_json::{json, Value};
+
+fn add_request(user: &TestUser, key: Option<&str>, body: &Value) -> Request<Body> {
+    let mut request = Request::builder()
+        .method("POST")
+        .uri("/api/v1/counters")
+        .header(header::AUTHORIZATION, format!("Bearer {}", user.token))
+        .header(header::CONTENT_TYPE, "application/json");
+    if let Some(key) = key {
+        request = request.header("idempotency-key", key);
+    }
+
+    request.body(Body::from(body.to_string())).unwrap()
+}
+
+fn replayed(response: &Response<BoxBody>) -> bool {
+    response.headers().get("idempotent-replayed").is_some()
+}
+
+async fn todays_count(app: &TestApp, user: &TestUser) -> Value {
+    let (_, body) = app
+        .send(
+            axum::http::Method::GET,
+            "/api/v2/counters",
+            Some(user),
+            None,
+        )
+        .await;
+    body["data"]["categories"][0]["count"].clone()
+}
+
+async fn check_retries_are_replayed(app: &TestApp) {
+    let alice = app.create_user("alice").await;
+    let coffee = app.create_category(&alice, "Coffee", "ml").await;
+    let body = json!({ "category_id": coffee["id"], "amount": 250.0 });
+
+    let first = app.request(add_request(&alice, Some("tap-1"), &body)).await;
+    assert_eq!(first.status(), StatusCode::OK);
+    assert!(!replayed(&first));
+    let first = json_body(first).await;
+
+    let retry = app.request(add_request(&alice, Some("tap-1"), &body)).await;
+    assert_eq!(retry.status(), StatusCode::OK);
+    assert!(replayed(&retry));
+    assert_eq!(json_body(retry).await, first);
+    assert_eq!(todays_count(app, &alice).await, 1);
+
+    // A new key is a new coffee
+    let second = app.request(add_request(&alice, Some("tap-2"), &body)).await;
+    assert!(!replayed(&second));
+    assert_eq!(json_body(second).await["data"]["count"], 2);
+
+    // Reusing a key for something else is refused
+    let other = json!({ "category_id": coffee["id"], "amount": 100.0 });
+    let response = app
+        .request(add_request(&alice, Some("tap-1"), &other))
+        .await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(todays_count(app, &alice).await, 2);
+}
+
+#[tokio::test]
+async fn test_postgres_concurrent_retries_log_once() {
+    let Some(app) = TestApp::postgres().await else {
+        return;
+    };
+    let alice = app.create_user("alice").await;
+    let coffee = app.create_category(&alice, "Coffee", "ml").await;
+    let body = json!({ "category_id": coffee["id"], "amount": 250.0 });
+
+    let send = || app.request(add_request(&alice, Some("flaky"), &body));
+    let (a, b, c, d) = tokio::join!(send(), send(), send(), send());
+    let responses = [a, b, c, d];
+
+    assert!(responses.iter().all(|r| r.status() == StatusCode::OK));
+    assert_eq!(responses.iter().filter(|r| !replayed(r)).count(), 1);
+    assert_eq!(todays_count(&app, &alice).await, 1);
+}
+
+async fn check_keys_are_optional_and_scoped_to_the_user(app: &TestApp) {
+    let alice = app.create_user("alice").await;
+    let bob = app.create_user("bob").await;
+    let alices = app.create_category(&alice, "Coffee", "ml").await;
+    let bobs = app.create_category(&bob, "Coffee", "ml").await;
+
+    let body = json!({ "category_id": alices["id"], "amount": 250.0 });
+    for _ in 0..2 {
+        let response = app.request(add_request(&alice, None, &body)).await;
+        assert!(!replayed(&response));
+    }
+    assert_eq!(todays_count(app, &alice).await, 2);
+
+    let body = json!({ "category_id": bobs["id"], "amount": 250.0 });
+    app.request(add_request(
+        &alice,
+        Some("shared"),
+        &json!({ "category_id": alices["id"], "amount": 250.0 }),
+    ))
+    .await;
+    let response = app.request(add_request(&bob, Some("shared"), &body)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!replayed(&response));
+
+    let response = app.request(add_request(&bob, Some(""), &body)).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+backend_tests!(
+    check_retries_are_replayed,
+    check_keys_are_optional_and_scoped_to_the_user,
+);
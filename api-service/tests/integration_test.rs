@@ -1,78 +1,337 @@
+mod common;
+
 use axum::{
     body::Body,
-    http::{Request, StatusCode, Method},
-    Router,
+    http::{header, Request, StatusCode, Method},
 };
-use serde_json::json;
-use tower::ServiceExt;
+use coffee_counter_api::config::AuthMode;
+use common::{backend_tests, json_body, TestApp};
+use serde_json::{json, Value};
 use uuid::Uuid;
 
-// Helper function to create test app
-fn create_test_app() -> Router {
-    // Simplified test app without database dependencies
-    Router::new()
-        .route("/health", axum::routing::get(|| async { "OK" }))
-        .route("/api/categories", axum::routing::get(|| async { 
-            axum::response::Json(json!({
-                "success": true,
-                "data": [],
-                "message": "Categories retrieved"
-            }))
-        }))
-}
-
 #[tokio::test]
 async fn test_health_check() {
-    let app = create_test_app();
+    let app = TestApp::memory().await;
     
     let response = app
-        .oneshot(
+        .request(
             Request::builder()
                 .uri("/health")
                 .body(Body::empty())
                 .unwrap()
         )
-        .await
-        .unwrap();
+        .await;
 
     assert_eq!(response.status(), StatusCode::OK);
 }
 
+#[tokio::test]
+async fn test_readiness_reports_each_dependency() {
+    let app = TestApp::memory().await;
+
+    let (status, body) = app.send(Method::GET, "/health/ready", None, None).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "healthy");
+    assert_eq!(body["checks"]["database"]["status"], "disabled");
+    assert_eq!(body["checks"]["migrations"]["status"], "disabled");
+    assert_eq!(body["checks"]["auth_service"]["status"], "healthy");
+    assert!(body["checks"]["auth_service"]["latency_ms"].is_number());
+}
+
+#[tokio::test]
+async fn test_not_ready_without_auth_service() {
+    // Nothing listens on port 1
+    let app = TestApp::memory_with(|config| {
+        config.auth.service_url = "http://127.0.0.1:1".to_string()
+    })
+    .await;
+
+    for uri in ["/health", "/health/ready"] {
+        let (status, body) = app.send(Method::GET, uri, None, None).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], "unhealthy");
+        assert_eq!(body["checks"]["auth_service"]["status"], "unhealthy");
+        assert!(body["checks"]["auth_service"]["error"].is_string());
+    }
+
+    // Still alive, so nothing restarts it over someone else's outage
+    let (status, body) = app.send(Method::GET, "/health/live", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "alive");
+}
+
+#[tokio::test]
+async fn test_not_ready_while_shutting_down() {
+    let app = TestApp::memory().await;
+    app.readiness.begin_shutdown();
+
+    let (status, body) = app.send(Method::GET, "/health/ready", None, None).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["status"], "shutting_down");
+
+    let (status, _) = app.send(Method::GET, "/health/live", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_single_user_mode_skips_auth_check() {
+    let app = TestApp::memory_with(|config| {
+        config.auth.mode = AuthMode::SingleUser;
+        config.auth.service_url = "http://127.0.0.1:1".to_string();
+    })
+    .await;
+
+    let (status, body) = app.send(Method::GET, "/health/ready", None, None).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["checks"]["auth_service"]["status"], "disabled");
+}
+
 #[tokio::test]
 async fn test_categories_endpoint_structure() {
-    let app = create_test_app();
+    let app = TestApp::memory().await;
+    let alice = app.create_user("alice").await;
     
-    let response = app
-        .oneshot(
-            Request::builder()
-                .method(Method::GET)
-                .uri("/api/categories")
-                .body(Body::empty())
-                .unwrap()
-        )
-        .await
-        .unwrap();
+    let (status, body) = app.send(Method::GET, "/api/categories", Some(&alice), None).await;
 
-    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["success"], true);
+    assert_eq!(body["data"], json!([]));
 }
 
 #[tokio::test]
 async fn test_invalid_route_returns_404() {
-    let app = create_test_app();
+    let app = TestApp::memory().await;
     
     let response = app
-        .oneshot(
+        .request(
             Request::builder()
                 .uri("/invalid-route")
                 .body(Body::empty())
                 .unwrap()
         )
-        .await
-        .unwrap();
+        .await;
 
     assert_eq!(response.status(), StatusCode::NOT_FOUND);
 }
 
+#[tokio::test]
+async fn test_requests_without_valid_token_are_rejected() {
+    let app = TestApp::memory().await;
+
+    let (status, _) = app.send(Method::GET, "/api/categories", None, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let response = app
+        .request(
+            Request::builder()
+                .uri("/api/counters")
+                .header(header::AUTHORIZATION, "Bearer mallory")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+async fn check_category_lifecycle(app: &TestApp) {
+    let alice = app.create_user("alice").await;
+    let bob = app.create_user("bob").await;
+    let coffee = app.create_category(&alice, "Coffee", "ml").await;
+    let uri = format!("/api/categories/{}", coffee["id"].as_str().unwrap());
+
+    let (status, body) = app
+        .send(
+            Method::POST,
+            "/api/categories",
+            Some(&alice),
+            Some(json!({ "name": "", "icon": "c", "color": "#000", "unit": "ml", "default_amount": 1 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["message"], "Validation failed");
+
+    let (status, body) = app
+        .send(Method::PUT, &uri, Some(&alice), Some(json!({ "name": "Espresso" })))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["name"], "Espresso");
+
+    let (status, _) = app.send(Method::GET, &uri, Some(&bob), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = app.send(Method::DELETE, &uri, Some(&alice), None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, body) = app.send(Method::GET, "/api/categories/trash", Some(&alice), None).await;
+    assert_eq!(body["data"][0]["name"], "Espresso");
+
+    let (status, _) = app
+        .send(Method::POST, &format!("{}/restore", uri), Some(&alice), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, body) = app.send(Method::GET, "/api/categories", Some(&alice), None).await;
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+}
+
+async fn check_counters_in_both_response_versions(app: &TestApp) {
+    let alice = app.create_user("alice").await;
+    let bob = app.create_user("bob").await;
+    let water = app.create_category(&alice, "Water", "ml").await;
+    let coffee = app.create_category(&alice, "Coffee", "ml").await;
+
+    app.add_entry(&alice, &coffee, 250.0).await;
+    app.add_entry(&alice, &coffee, 100.0).await;
+
+    let (status, _) = app
+        .send(
+            Method::POST,
+            "/api/counters",
+            Some(&bob),
+            Some(json!({ "category_id": coffee["id"], "amount": 250.0 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, v1) = app.send(Method::GET, "/api/counters", Some(&alice), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(v1["data"]["order"], json!(["Water", "Coffee"]));
+    assert_eq!(v1["data"]["categories"]["Coffee"]["count"], 2);
+    assert_eq!(v1["data"]["categories"]["Coffee"]["total_amount"], 350.0);
+
+    let response = app
+        .request(
+            Request::builder()
+                .uri("/api/counters")
+                .header(header::AUTHORIZATION, format!("Bearer {}", alice.token))
+                .header(header::ACCEPT, "application/vnd.coffee-counter.v2+json")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "application/vnd.coffee-counter.v2+json"
+    );
+    let v2 = json_body(response).await;
+    assert_eq!(v2["data"]["categories"][0]["category_id"], water["id"]);
+    assert_eq!(v2["data"]["categories"][1]["amounts"], json!([250.0, 100.0]));
+}
+
+async fn check_merge_converts_units(app: &TestApp) {
+    let alice = app.create_user("alice").await;
+    let litres = app.create_category(&alice, "Big coffee", "l").await;
+    let millilitres = app.create_category(&alice, "Coffee", "ml").await;
+    let protein = app.create_category(&alice, "Protein", "g").await;
+
+    app.add_entry(&alice, &litres, 0.5).await;
+    app.add_entry(&alice, &millilitres, 250.0).await;
+
+    let merge_uri = format!("/api/categories/{}/merge", litres["id"].as_str().unwrap());
+    let (status, _) = app
+        .send(
+            Method::POST,
+            &merge_uri,
+            Some(&alice),
+            Some(json!({ "target_id": protein["id"] })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let (status, body) = app
+        .send(
+            Method::POST,
+            &merge_uri,
+            Some(&alice),
+            Some(json!({ "target_id": millilitres["id"] })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["moved_days"], 0);
+    assert_eq!(body["data"]["combined_days"], 1);
+
+    let (_, day) = app.send(Method::GET, "/api/counters", Some(&alice), None).await;
+    assert_eq!(day["data"]["categories"]["Coffee"]["amounts"], json!([250.0, 500.0]));
+    assert_eq!(day["data"]["categories"]["Coffee"]["count"], 2);
+}
+
+async fn check_reorder_sets_the_layout(app: &TestApp) {
+    let alice = app.create_user("alice").await;
+    let coffee = app.create_category(&alice, "Coffee", "ml").await;
+    let tea = app.create_category(&alice, "Tea", "ml").await;
+    let water = app.create_category(&alice, "Water", "ml").await;
+
+    // Water is left out, so it keeps its place after the listed ones
+    let (status, body) = app
+        .send(
+            Method::PUT,
+            "/api/v1/categories/order",
+            Some(&alice),
+            Some(json!({ "categories": [
+                { "id": tea["id"], "group_name": "Hot" },
+                { "id": coffee["id"], "group_name": "Hot" }
+            ] })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let (_, body) = app.send(Method::GET, "/api/v1/categories", Some(&alice), None).await;
+    let layout: Vec<(Value, Value)> = body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|category| (category["id"].clone(), category["group_name"].clone()))
+        .collect();
+    assert_eq!(
+        layout,
+        [
+            (tea["id"].clone(), json!("Hot")),
+            (coffee["id"].clone(), json!("Hot")),
+            (water["id"].clone(), Value::Null),
+        ]
+    );
+
+    // Someone else's category can't be placed
+    let bob = app.create_user("bob").await;
+    let (status, _) = app
+        .send(
+            Method::PUT,
+            "/api/v1/categories/order",
+            Some(&bob),
+            Some(json!({ "categories": [{ "id": tea["id"] }] })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+backend_tests!(
+    check_category_lifecycle,
+    check_counters_in_both_response_versions,
+    check_merge_converts_units,
+    check_reorder_sets_the_layout,
+);
+
+#[tokio::test]
+async fn test_single_user_mode_needs_no_token() {
+    let app = TestApp::memory_with(|config| config.auth.mode = AuthMode::SingleUser).await;
+
+    let (status, body) = app
+        .send(
+            Method::POST,
+            "/api/categories",
+            None,
+            Some(json!({ "name": "Coffee", "icon": "coffee", "color": "#8B4513", "unit": "ml", "default_amount": 250.0 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(body["data"]["user_id"], Uuid::from_u128(1).to_string());
+
+    let (_, body) = app.send(Method::GET, "/api/categories", None, None).await;
+    assert_eq!(body["data"][0]["name"], "Coffee");
+}
+
 // Test data structures
 #[tokio::test]
 async fn test_uuid_generation() {
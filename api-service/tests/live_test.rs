@@ -0,0 +1,185 @@
+mod common;
+
+use axum::body::{Body, BoxBody, HttpBody};
+use axum::http::{header, Method, Request, StatusCode};
+use chrono::Utc;
+use common::{backend_tests, TestApp, TestUser};
+use serde_json::{json, Value};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// An open live updates stream.
+struct Updates {
+    body: BoxBody,
+    buffer: String,
+}
+
+impl Updates {
+    async fn open(app: &TestApp, user: &TestUser) -> Self {
+        let request = Request::builder()
+            .uri("/api/v1/counters/live")
+            .header(header::AUTHORIZATION, format!("Bearer {}", user.token))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.request(request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/event-stream"
+        );
+        assert_eq!(response.headers()["x-accel-buffering"], "no");
+
+        Self {
+            body: response.into_body(),
+            buffer: String::new(),
+        }
+    }
+
+    /// The next event's name and data, unless none comes within `wait`.
+    async fn next(&mut self, wait: Duration) -> Option<(String, Value)> {
+        loop {
+            if let Some(end) = self.buffer.find("\n\n") {
+                let event: String = self.buffer.drain(..end + 2).collect();
+                // `name:value`, with an optional space after the colon
+                let field = |name: &str| {
+                    event.lines().find_map(|line| {
+                        let value = line.strip_prefix(name)?.strip_prefix(':')?;
+                        Some(value.strip_prefix(' ').unwrap_or(value).to_string())
+                    })
+                };
+                // Keep-alive comments have neither
+                if let Some(name) = field("event") {
+                    let data = field("data").unwrap_or_default();
+                    return Some((name, serde_json::from_str(&data).unwrap_or(Value::Null)));
+                }
+                continue;
+            }
+
+            let chunk = tokio::time::timeout(wait, self.body.data()).await.ok()??;
+            self.buffer
+                .push_str(std::str::from_utf8(&chunk.unwrap()).unwrap());
+        }
+    }
+}
+
+/// Watches `app` while the writes go to `writer`, which may be another replica.
+async fn check_updates_are_pushed(app: &TestApp, writer: &TestApp) {
+    let alice = app.create_user("alice").await;
+    let bob = app.create_user("bob").await;
+    let coffee = app.create_category(&alice, "Coffee", "ml").await;
+    let today = Utc::now().date_naive().to_string();
+
+    let mut updates = Updates::open(app, &alice).await;
+    let mut bobs_updates = Updates::open(app, &bob).await;
+
+    writer.add_entry(&alice, &coffee, 250.0).await;
+    let (event, update) = updates.next(Duration::from_secs(5)).await.unwrap();
+    assert_eq!(event, "counter");
+    assert_eq!(update["date"], today);
+    assert_eq!(update["entry"]["category_id"], coffee["id"]);
+    assert_eq!(update["entry"]["count"], 1);
+
+    let operation = json!({
+        "id": Uuid::new_v4(), "recorded_at": Utc::now(), "op": "delete",
+        "category_id": coffee["id"], "date": today
+    });
+    let (status, _) = writer
+        .send(
+            Method::POST,
+            "/api/v1/counters/batch",
+            Some(&alice),
+            Some(json!({ "operations": [operation] })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, update) = updates.next(Duration::from_secs(5)).await.unwrap();
+    assert_eq!(update["entry"]["count"], 0);
+
+    assert!(bobs_updates
+        .next(Duration::from_millis(100))
+        .await
+        .is_none());
+}
+
+/// Category changes can touch any number of days, so they ask for a refetch.
+async fn check_category_changes_resync(app: &TestApp, writer: &TestApp) {
+    let alice = app.create_user("alice").await;
+    let bob = app.create_user("bob").await;
+    let coffee = app.create_category(&alice, "Coffee", "ml").await;
+    let espresso = app.create_category(&alice, "Espresso", "ml").await;
+    writer.add_entry(&alice, &espresso, 30.0).await;
+
+    let mut updates = Updates::open(app, &alice).await;
+    let mut bobs_updates = Updates::open(app, &bob).await;
+
+    let (status, _) = writer
+        .send(
+            Method::POST,
+            &format!(
+                "/api/v1/categories/{}/merge",
+                espresso["id"].as_str().unwrap()
+            ),
+            Some(&alice),
+            Some(json!({ "target_id": coffee["id"] })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (event, _) = updates.next(Duration::from_secs(5)).await.unwrap();
+    assert_eq!(event, "resync");
+
+    let (status, _) = writer
+        .send(
+            Method::DELETE,
+            &format!("/api/v1/categories/{}", coffee["id"].as_str().unwrap()),
+            Some(&alice),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (event, _) = updates.next(Duration::from_secs(5)).await.unwrap();
+    assert_eq!(event, "resync");
+
+    assert!(bobs_updates
+        .next(Duration::from_millis(100))
+        .await
+        .is_none());
+}
+
+async fn check_category_changes_resync_the_user(app: &TestApp) {
+    check_category_changes_resync(app, app).await;
+}
+
+#[tokio::test]
+async fn test_postgres_category_changes_resync_other_replicas() {
+    let Some(app) = TestApp::postgres().await else {
+        return;
+    };
+    check_category_changes_resync(&app, &app.replica()).await;
+}
+
+async fn check_updates_are_pushed_to_the_user(app: &TestApp) {
+    check_updates_are_pushed(app, app).await;
+}
+
+backend_tests!(
+    check_updates_are_pushed_to_the_user,
+    check_category_changes_resync_the_user,
+);
+
+#[tokio::test]
+async fn test_postgres_updates_reach_other_replicas() {
+    let Some(app) = TestApp::postgres().await else {
+        return;
+    };
+    check_updates_are_pushed(&app, &app.replica()).await;
+}
+
+#[tokio::test]
+async fn test_live_updates_require_authentication() {
+    let app = TestApp::memory().await;
+
+    let (status, _) = app
+        .send(Method::GET, "/api/v1/counters/live", None, None)
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{header, Method, Request, StatusCode};
+use common::TestApp;
+
+async fn scrape(app: &TestApp) -> String {
+    let request = Request::builder()
+        .uri("/metrics")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.request(request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers()[header::CONTENT_TYPE]
+        .to_str()
+        .unwrap()
+        .starts_with("text/plain"));
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+#[tokio::test]
+async fn test_requests_are_counted_by_route_pattern() {
+    let app = TestApp::memory().await;
+    let alice = app.create_user("alice").await;
+
+    app.send(Method::GET, "/api/counters/2024-01-01", Some(&alice), None)
+        .await;
+    app.send(Method::GET, "/api/counters/2024-01-02", Some(&alice), None)
+        .await;
+    app.send(Method::GET, "/api/counters", None, None).await;
+
+    let text = scrape(&app).await;
+
+    assert!(text.contains(
+        r#"coffee_counter_http_requests_total{method="GET",route="/api/counters/:date",status="200"} 2"#
+    ), "{}", text);
+    assert!(text.contains(
+        r#"coffee_counter_http_requests_total{method="GET",route="/api/counters",status="401"} 1"#
+    ));
+    assert!(text.contains(
+        r#"coffee_counter_http_request_duration_seconds_count{method="GET",route="/api/counters/:date",status="200"} 2"#
+    ));
+}
+
+#[tokio::test]
+async fn test_entries_and_auth_are_recorded() {
+    let app = TestApp::memory().await;
+    let alice = app.create_user("alice").await;
+    let coffee = app.create_category(&alice, "Coffee", "ML").await;
+    let pills = app.create_category(&alice, "Vitamins", "pills").await;
+
+    app.add_entry(&alice, &coffee, 250.0).await;
+    app.add_entry(&alice, &coffee, 100.0).await;
+    app.add_entry(&alice, &pills, 2.0).await;
+
+    let request = Request::builder()
+        .uri("/api/counters")
+        .header(header::AUTHORIZATION, "Bearer not-a-token")
+        .body(Body::empty())
+        .unwrap();
+    app.request(request).await;
+
+    let text = scrape(&app).await;
+
+    assert!(text.contains(r#"coffee_counter_counter_entries_total{unit="ml"} 2"#));
+    assert!(text.contains(r#"coffee_counter_counter_entry_amount_total{unit="ml"} 350"#));
+    assert!(text.contains(r#"coffee_counter_counter_entries_total{unit="other"} 1"#));
+    assert!(
+        text.contains(r#"coffee_counter_auth_verification_failures_total{reason="rejected"} 1"#)
+    );
+    assert!(text
+        .contains(r#"coffee_counter_auth_verification_duration_seconds_count{outcome="success"}"#));
+    // No pool with in-memory storage
+    assert!(!text.contains("db_pool"));
+}
+
+#[tokio::test]
+async fn test_metrics_can_be_disabled() {
+    let app = TestApp::memory_with(|config| config.features.metrics = false).await;
+
+    let (status, _) = app.send(Method::GET, "/metrics", None, None).await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
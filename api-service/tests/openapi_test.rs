@@ -0,0 +1,97 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+};
+use coffee_counter_api::openapi::ApiDoc;
+use common::{json_body, TestApp};
+use std::path::PathBuf;
+use utoipa::OpenApi;
+
+fn committed_spec_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("openapi.json")
+}
+
+fn generated_spec() -> String {
+    ApiDoc::openapi().to_pretty_json().unwrap() + "\n"
+}
+
+/// Run with `UPDATE_OPENAPI=1` to rewrite `openapi.json` after changing the API.
+#[test]
+fn test_committed_spec_is_up_to_date() {
+    let generated = generated_spec();
+    let path = committed_spec_path();
+
+    if std::env::var_os("UPDATE_OPENAPI").is_some() {
+        std::fs::write(&path, &generated).unwrap();
+        return;
+    }
+
+    let committed = std::fs::read_to_string(&path).unwrap_or_default();
+    assert!(
+        committed == generated,
+        "openapi.json is out of date; run `UPDATE_OPENAPI=1 cargo test --test openapi_test` and commit the result"
+    );
+}
+
+#[tokio::test]
+async fn test_spec_and_viewer_are_served() {
+    let app = TestApp::memory().await;
+
+    let (status, body) = app.send(Method::GET, "/api/openapi.json", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, serde_json::to_value(ApiDoc::openapi()).unwrap());
+
+    let request = Request::builder()
+        .uri("/api/docs")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.request(request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers()[header::CONTENT_TYPE]
+        .to_str()
+        .unwrap()
+        .starts_with("text/html"));
+}
+
+#[tokio::test]
+async fn test_documented_routes_exist() {
+    let app = TestApp::memory().await;
+    let alice = app.create_user("alice").await;
+    let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+
+    for (path, operations) in spec["paths"].as_object().unwrap() {
+        // In-memory storage, which this app uses, keeps no audit log
+        if path.contains("/audit") {
+            continue;
+        }
+        let uri = path
+            .replace("{id}", "00000000-0000-0000-0000-000000000000")
+            .replace("{date}", "2024-01-15");
+
+        for method in operations.as_object().unwrap().keys() {
+            let method = Method::from_bytes(method.to_uppercase().as_bytes()).unwrap();
+            let request = Request::builder()
+                .method(method.clone())
+                .uri(&uri)
+                .header(header::AUTHORIZATION, format!("Bearer {}", alice.token))
+                .body(Body::empty())
+                .unwrap();
+            let response = app.request(request).await;
+
+            let status = response.status();
+            assert_ne!(
+                status,
+                StatusCode::METHOD_NOT_ALLOWED,
+                "{} {}",
+                method,
+                path
+            );
+            if status == StatusCode::NOT_FOUND {
+                let body = json_body(response).await;
+                assert_ne!(body["message"], "Route not found", "{} {}", method, path);
+            }
+        }
+    }
+}
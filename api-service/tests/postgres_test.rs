@@ -0,0 +1,237 @@
+// End-to-end tests against Postgres. Each test gets its own schema in the
+// database at TEST_DATABASE_URL and is skipped when that is not set.
+
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Method, Request, StatusCode},
+};
+use coffee_counter_api::utils::trash;
+use common::{TestApp, ADMIN, POOL_SIZE};
+use serde_json::{json, Value};
+
+fn actions(body: &Value) -> Vec<&str> {
+    body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["action"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_health_reports_database() {
+    let Some(app) = TestApp::postgres().await else {
+        return;
+    };
+
+    let (status, body) = app.send(Method::GET, "/health", None, None).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["database"], "healthy");
+    assert_eq!(body["checks"]["database"]["status"], "healthy");
+    assert_eq!(body["checks"]["migrations"]["status"], "healthy");
+    assert!(body["checks"]["database"]["latency_ms"].is_number());
+}
+
+#[tokio::test]
+async fn test_not_ready_with_unknown_migration() {
+    let Some(app) = TestApp::postgres().await else {
+        return;
+    };
+
+    // As if a newer build had migrated the database
+    sqlx::query(
+        "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time) \
+         VALUES (99999999999999, 'from the future', true, '\\x00', 0)",
+    )
+    .execute(app.pool.as_ref().unwrap())
+    .await
+    .unwrap();
+
+    let (status, body) = app.send(Method::GET, "/health/ready", None, None).await;
+
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["checks"]["database"]["status"], "healthy");
+    assert_eq!(body["checks"]["migrations"]["status"], "unhealthy");
+    assert!(body["checks"]["migrations"]["error"]
+        .as_str()
+        .unwrap()
+        .contains("99999999999999"));
+}
+
+#[tokio::test]
+async fn test_metrics_report_pool_usage() {
+    let Some(app) = TestApp::postgres().await else {
+        return;
+    };
+
+    let request = Request::builder()
+        .uri("/metrics")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.request(request).await;
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let text = String::from_utf8(body.to_vec()).unwrap();
+
+    assert!(text.contains(r#"coffee_counter_db_pool_connections{state="idle"}"#));
+    assert!(text.contains(r#"coffee_counter_db_pool_connections{state="in_use"}"#));
+    assert!(text.contains(&format!(
+        "coffee_counter_db_pool_max_connections {}",
+        POOL_SIZE
+    )));
+}
+
+#[tokio::test]
+async fn test_counters_are_persisted() {
+    let Some(app) = TestApp::postgres().await else {
+        return;
+    };
+    let alice = app.create_user("alice").await;
+    let coffee = app.create_category(&alice, "Coffee", "ml").await;
+    app.create_category(&alice, "Water", "ml").await;
+
+    app.add_entry(&alice, &coffee, 250.0).await;
+    let entry = app.add_entry(&alice, &coffee, 99.5).await;
+    assert_eq!(entry["count"], 2);
+
+    let (status, body) = app
+        .send(Method::GET, "/api/counters", Some(&alice), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["order"], json!(["Coffee", "Water"]));
+    assert_eq!(
+        body["data"]["categories"]["Coffee"]["amounts"],
+        json!([250.0, 99.5])
+    );
+    assert_eq!(body["data"]["categories"]["Coffee"]["total_amount"], 349.5);
+    assert_eq!(body["data"]["categories"]["Water"]["count"], 0);
+}
+
+#[tokio::test]
+async fn test_duplicate_category_name_conflicts() {
+    let Some(app) = TestApp::postgres().await else {
+        return;
+    };
+    let alice = app.create_user("alice").await;
+    let bob = app.create_user("bob").await;
+    let coffee = app.create_category(&alice, "Coffee", "ml").await;
+
+    let body = json!({ "name": "Coffee", "icon": "coffee", "color": "#000", "unit": "ml", "default_amount": 1 });
+    let (status, _) = app
+        .send(
+            Method::POST,
+            "/api/categories",
+            Some(&alice),
+            Some(body.clone()),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    // Names are only unique per user, and archived names can be reused
+    let (status, _) = app
+        .send(
+            Method::POST,
+            "/api/categories",
+            Some(&bob),
+            Some(body.clone()),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let uri = format!("/api/categories/{}", coffee["id"].as_str().unwrap());
+    app.send(Method::DELETE, &uri, Some(&alice), None).await;
+    let (status, _) = app
+        .send(Method::POST, "/api/categories", Some(&alice), Some(body))
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+}
+
+#[tokio::test]
+async fn test_changes_are_audited() {
+    let Some(app) = TestApp::postgres().await else {
+        return;
+    };
+    let alice = app.create_user("alice").await;
+    let bob = app.create_user("bob").await;
+    let admin = app.create_user(ADMIN).await;
+
+    let coffee = app.create_category(&alice, "Coffee", "ml").await;
+    app.add_entry(&alice, &coffee, 250.0).await;
+    let uri = format!("/api/categories/{}", coffee["id"].as_str().unwrap());
+    app.send(Method::DELETE, &uri, Some(&alice), None).await;
+    app.create_category(&bob, "Tea", "ml").await;
+
+    let (status, body) = app
+        .send(Method::GET, "/api/audit", Some(&alice), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        actions(&body),
+        ["category.archive", "counter.add", "category.create"]
+    );
+    assert_eq!(body["data"][0]["before_data"]["name"], "Coffee");
+
+    let (status, _) = app
+        .send(Method::GET, "/api/admin/audit", Some(&alice), None)
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, body) = app
+        .send(Method::GET, "/api/admin/audit", Some(&admin), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"].as_array().unwrap().len(), 4);
+
+    let uri = format!("/api/admin/audit?user_id={}", bob.id);
+    let (_, body) = app.send(Method::GET, &uri, Some(&admin), None).await;
+    assert_eq!(actions(&body), ["category.create"]);
+}
+
+#[tokio::test]
+async fn test_trash_purge_waits_for_the_users_writers() {
+    let Some(app) = TestApp::postgres().await else {
+        return;
+    };
+    let pool = app.pool.clone().unwrap();
+    let alice = app.create_user("alice").await;
+    let bob = app.create_user("bob").await;
+    for user in [&alice, &bob] {
+        let coffee = app.create_category(user, "Coffee", "ml").await;
+        app.add_entry(user, &coffee, 250.0).await;
+        let uri = format!("/api/v1/categories/{}", coffee["id"].as_str().unwrap());
+        app.send(Method::DELETE, &uri, Some(user), None).await;
+    }
+
+    // A writer of alice's data is part way through its transaction
+    let mut writer = pool.begin().await.unwrap();
+    sqlx::query("SELECT lock_change_seq($1)")
+        .bind(alice.id)
+        .execute(&mut writer)
+        .await
+        .unwrap();
+
+    let purge = tokio::spawn({
+        let pool = pool.clone();
+        async move { trash::purge_expired(&pool, 0).await.unwrap() }
+    });
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    assert!(!purge.is_finished());
+
+    writer.commit().await.unwrap();
+    assert_eq!(purge.await.unwrap(), 2);
+
+    for user in [&alice, &bob] {
+        let (_, body) = app
+            .send(Method::GET, "/api/v1/audit", Some(user), None)
+            .await;
+        assert_eq!(actions(&body)[0], "category.purge");
+        let history = &body["data"][0]["before_data"]["counter_data"];
+        assert_eq!(history[0]["amounts"], json!([250.0]));
+        let (_, body) = app
+            .send(Method::GET, "/api/v1/categories/trash", Some(user), None)
+            .await;
+        assert_eq!(body["data"], json!([]));
+    }
+}
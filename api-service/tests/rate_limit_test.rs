@@ -0,0 +1,147 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, Method, Request, StatusCode},
+};
+use coffee_counter_api::config::RateLimitRule;
+use common::{json_body, TestApp, TestUser};
+
+fn rule(name: &str, methods: &[&str], path_prefix: &str, requests: u32) -> RateLimitRule {
+    RateLimitRule {
+        name: name.to_string(),
+        methods: methods.iter().map(|method| method.to_string()).collect(),
+        path_prefix: path_prefix.to_string(),
+        requests,
+        window_secs: 60,
+    }
+}
+
+async fn limited_app() -> TestApp {
+    TestApp::memory_with(|config| {
+        config.rate_limit.rules = vec![
+            rule("writes", &["POST"], "/api", 1),
+            rule("reads", &[], "/api", 2),
+        ];
+    })
+    .await
+}
+
+async fn get(app: &TestApp, uri: &str, user: &TestUser) -> (StatusCode, HeaderMap) {
+    let request = Request::builder()
+        .uri(uri)
+        .header(header::AUTHORIZATION, format!("Bearer {}", user.token))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.request(request).await;
+
+    (response.status(), response.headers().clone())
+}
+
+#[tokio::test]
+async fn test_requests_over_the_limit_are_rejected() {
+    let app = limited_app().await;
+    let alice = app.create_user("alice").await;
+
+    let (status, headers) = get(&app, "/api/counters", &alice).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers["x-ratelimit-limit"], "2");
+    assert_eq!(headers["x-ratelimit-remaining"], "1");
+    assert!(!headers.contains_key(header::RETRY_AFTER));
+
+    get(&app, "/api/categories", &alice).await;
+
+    let request = Request::builder()
+        .uri("/api/counters")
+        .header(header::AUTHORIZATION, format!("Bearer {}", alice.token))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.request(request).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let headers = response.headers().clone();
+    assert_eq!(headers["x-ratelimit-remaining"], "0");
+    let retry_after: u64 = headers[header::RETRY_AFTER]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=60).contains(&retry_after));
+    assert_eq!(headers["x-ratelimit-reset"], headers[header::RETRY_AFTER]);
+
+    let body = json_body(response).await;
+    assert_eq!(body["success"], false);
+}
+
+#[tokio::test]
+async fn test_limits_are_per_user() {
+    let app = limited_app().await;
+    let alice = app.create_user("alice").await;
+    let bob = app.create_user("bob").await;
+
+    for _ in 0..2 {
+        get(&app, "/api/counters", &alice).await;
+    }
+    assert_eq!(
+        get(&app, "/api/counters", &alice).await.0,
+        StatusCode::TOO_MANY_REQUESTS
+    );
+
+    assert_eq!(get(&app, "/api/counters", &bob).await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_route_classes_are_counted_separately() {
+    let app = limited_app().await;
+    let alice = app.create_user("alice").await;
+
+    app.create_category(&alice, "Coffee", "ml").await;
+    let (status, _) = app
+        .send(Method::POST, "/api/categories", Some(&alice), None)
+        .await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+
+    // Writes being used up leaves reads alone
+    assert_eq!(get(&app, "/api/categories", &alice).await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_versions_share_a_limit() {
+    let app = limited_app().await;
+    let alice = app.create_user("alice").await;
+
+    get(&app, "/api/v1/counters", &alice).await;
+    get(&app, "/api/counters", &alice).await;
+
+    assert_eq!(
+        get(&app, "/api/v2/counters", &alice).await.0,
+        StatusCode::TOO_MANY_REQUESTS
+    );
+}
+
+#[tokio::test]
+async fn test_unauthenticated_and_unmatched_requests_are_not_limited() {
+    let app = limited_app().await;
+
+    for _ in 0..3 {
+        let (status, _) = app.send(Method::GET, "/api/counters", None, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = app.send(Method::GET, "/health", None, None).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
+
+#[tokio::test]
+async fn test_rate_limiting_can_be_disabled() {
+    let app = TestApp::memory_with(|config| {
+        config.rate_limit.enabled = false;
+        config.rate_limit.rules = vec![rule("reads", &[], "/api", 1)];
+    })
+    .await;
+    let alice = app.create_user("alice").await;
+
+    for _ in 0..3 {
+        let (status, headers) = get(&app, "/api/counters", &alice).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(!headers.contains_key("x-ratelimit-limit"));
+    }
+}
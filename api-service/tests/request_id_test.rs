@@ -0,0 +1,80 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+};
+use common::{json_body, TestApp};
+use uuid::Uuid;
+
+#[tokio::test]
+async fn test_request_id_is_generated() {
+    let app = TestApp::memory().await;
+
+    let request = Request::builder()
+        .uri("/health/live")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.request(request).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let id = response.headers()["x-request-id"].to_str().unwrap();
+    assert!(Uuid::parse_str(id).is_ok(), "{}", id);
+}
+
+#[tokio::test]
+async fn test_request_id_is_propagated_into_errors() {
+    let app = TestApp::memory().await;
+
+    let request = Request::builder()
+        .uri("/api/counters")
+        .header("x-request-id", "trace-me-123")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.request(request).await;
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(response.headers()["x-request-id"], "trace-me-123");
+    let body = json_body(response).await;
+    assert_eq!(body["success"], false);
+    assert_eq!(body["request_id"], "trace-me-123");
+}
+
+#[tokio::test]
+async fn test_unknown_routes_get_a_request_id() {
+    let app = TestApp::memory().await;
+
+    let request = Request::builder()
+        .uri("/api/nothing-here")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.request(request).await;
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let id = response.headers()["x-request-id"]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let body = json_body(response).await;
+    assert_eq!(body["message"], "Route not found");
+    assert_eq!(body["request_id"], id);
+}
+
+#[tokio::test]
+async fn test_successful_responses_are_untouched() {
+    let app = TestApp::memory().await;
+    let alice = app.create_user("alice").await;
+
+    let request = Request::builder()
+        .method(Method::GET)
+        .uri("/api/categories")
+        .header(header::AUTHORIZATION, format!("Bearer {}", alice.token))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.request(request).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().contains_key("x-request-id"));
+    let body = json_body(response).await;
+    assert!(body.get("request_id").is_none());
+}
@@ -0,0 +1,65 @@
+// End-to-end tests against SQLite, for builds with `--features sqlite`. Each
+// test gets its own in-memory database.
+#![cfg(feature = "sqlite")]
+
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::{TestApp, ADMIN};
+use serde_json::Value;
+
+fn actions(body: &Value) -> Vec<&str> {
+    body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["action"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_changes_are_audited() {
+    let app = TestApp::sqlite().await;
+    let alice = app.create_user("alice").await;
+    let bob = app.create_user("bob").await;
+    let admin = app.create_user(ADMIN).await;
+
+    let coffee = app.create_category(&alice, "Coffee", "ml").await;
+    app.add_entry(&alice, &coffee, 250.0).await;
+    let uri = format!("/api/categories/{}", coffee["id"].as_str().unwrap());
+    app.send(Method::DELETE, &uri, Some(&alice), None).await;
+    app.create_category(&bob, "Tea", "ml").await;
+
+    let (status, body) = app
+        .send(Method::GET, "/api/v1/audit", Some(&alice), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        actions(&body),
+        ["category.archive", "counter.add", "category.create"]
+    );
+    assert_eq!(body["data"][0]["before_data"]["name"], "Coffee");
+    assert_eq!(body["data"][1]["after_data"]["amounts"][0], 250.0);
+
+    let (status, _) = app
+        .send(Method::GET, "/api/v1/admin/audit", Some(&alice), None)
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let uri = format!("/api/v1/admin/audit?user_id={}", bob.id);
+    let (status, body) = app.send(Method::GET, &uri, Some(&admin), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(actions(&body), ["category.create"]);
+}
+
+#[tokio::test]
+async fn test_readiness_checks_the_database() {
+    let app = TestApp::sqlite().await;
+
+    let (status, body) = app.send(Method::GET, "/health/ready", None, None).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["database"], "healthy");
+    assert_eq!(body["checks"]["database"]["status"], "healthy");
+    assert_eq!(body["checks"]["migrations"]["status"], "healthy");
+}
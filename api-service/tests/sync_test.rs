@@ -0,0 +1,119 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use coffee_counter_api::utils::trash;
+use common::{backend_tests, TestApp, TestUser};
+use serde_json::{json, Value};
+
+async fn changes(app: &TestApp, user: &TestUser, since: i64, limit: usize) -> Value {
+    let uri = format!("/api/v1/sync?since={}&limit={}", since, limit);
+    let (status, body) = app.send(Method::GET, &uri, Some(user), None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    body["data"].clone()
+}
+
+/// Reads every change after `since`, a page of `limit` at a time, and returns
+/// the pages.
+async fn drain(app: &TestApp, user: &TestUser, since: i64, limit: usize) -> Vec<Value> {
+    let mut pages = Vec::new();
+    let mut cursor = since;
+    loop {
+        let page = changes(app, user, cursor, limit).await;
+        cursor = page["cursor"].as_i64().unwrap();
+        let has_more = page["has_more"] == true;
+        pages.push(page);
+        if !has_more {
+            return pages;
+        }
+    }
+}
+
+fn ids(pages: &[Value], list: &str) -> Vec<Value> {
+    pages
+        .iter()
+        .flat_map(|page| page[list].as_array().unwrap().clone())
+        .map(|item| item["id"].clone())
+        .collect()
+}
+
+/// Returns the user, their trashed category and the cursor after their last
+/// change, for backend-specific checks.
+async fn check_changes_are_paged_after_the_cursor(app: &TestApp) -> (TestUser, Value, i64) {
+    let alice = app.create_user("alice").await;
+    let coffee = app.create_category(&alice, "Coffee", "ml").await;
+    let water = app.create_category(&alice, "Water", "ml").await;
+    app.add_entry(&alice, &coffee, 250.0).await;
+    app.add_entry(&alice, &water, 500.0).await;
+    app.create_category(&app.create_user("bob").await, "Tea", "ml")
+        .await;
+
+    // Paging returns each change once, in the order they were made
+    let pages = drain(app, &alice, 0, 1).await;
+    assert_eq!(pages.len(), 4);
+    assert_eq!(
+        ids(&pages, "categories"),
+        [coffee["id"].clone(), water["id"].clone()]
+    );
+    assert_eq!(ids(&pages, "counters").len(), 2);
+    let cursor = pages.last().unwrap()["cursor"].as_i64().unwrap();
+    assert_eq!(changes(app, &alice, 0, 10).await["cursor"], cursor);
+
+    // Only what changed since is sent again
+    app.add_entry(&alice, &coffee, 250.0).await;
+    let (status, _) = app
+        .send(
+            Method::DELETE,
+            &format!("/api/v1/categories/{}", water["id"].as_str().unwrap()),
+            Some(&alice),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let later = changes(app, &alice, cursor, 10).await;
+    assert_eq!(later["counters"][0]["amounts"], json!([250.0, 250.0]));
+    assert_eq!(later["categories"][0]["id"], water["id"]);
+    assert!(later["categories"][0]["deleted_at"].is_string());
+    assert_eq!(later["deleted"], json!([]));
+
+    let cursor = later["cursor"].as_i64().unwrap();
+    let idle = changes(app, &alice, cursor, 10).await;
+    assert_eq!(idle["cursor"], cursor);
+    assert_eq!(idle["has_more"], false);
+
+    (alice, water, cursor)
+}
+
+#[tokio::test]
+async fn test_postgres_purged_categories_leave_tombstones() {
+    let Some(app) = TestApp::postgres().await else {
+        return;
+    };
+    let (alice, water, cursor) = check_changes_are_paged_after_the_cursor(&app).await;
+
+    // The purge removes the category and its history without the API
+    trash::purge_expired(app.pool.as_ref().unwrap(), 0)
+        .await
+        .unwrap();
+
+    let later = changes(&app, &alice, cursor, 10).await;
+    let deleted = later["deleted"].as_array().unwrap();
+    assert_eq!(deleted.len(), 2);
+    assert!(deleted.iter().any(|tombstone| {
+        tombstone["entity_type"] == "category" && tombstone["entity_id"] == water["id"]
+    }));
+    assert!(deleted.iter().any(|tombstone| {
+        tombstone["entity_type"] == "counter_data" && tombstone["category_id"] == water["id"]
+    }));
+}
+
+#[tokio::test]
+async fn test_sync_requires_authentication() {
+    let app = TestApp::memory().await;
+
+    let (status, _) = app.send(Method::GET, "/api/v1/sync", None, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+backend_tests!(check_changes_are_paged_after_the_cursor);
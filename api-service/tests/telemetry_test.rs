@@ -0,0 +1,74 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+};
+use common::{TestApp, TestUser};
+use opentelemetry::{global, trace::TracerProvider as _};
+use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::TracerProvider};
+use std::sync::OnceLock;
+use tracing_subscriber::layer::SubscriberExt;
+
+/// Tracers stop recording once their provider is dropped.
+static PROVIDER: OnceLock<TracerProvider> = OnceLock::new();
+
+/// What `init_tracer` sets up, minus the exporter.
+fn init_tracing() {
+    PROVIDER.get_or_init(|| {
+        let provider = TracerProvider::builder().build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        tracing::subscriber::set_global_default(subscriber).unwrap();
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        provider
+    });
+}
+
+fn counters_request(user: &TestUser, traceparent: Option<&str>) -> Request<Body> {
+    let mut request = Request::builder()
+        .uri("/api/counters")
+        .header(header::AUTHORIZATION, format!("Bearer {}", user.token));
+    if let Some(traceparent) = traceparent {
+        request = request.header("traceparent", traceparent);
+    }
+
+    request.body(Body::empty()).unwrap()
+}
+
+/// The trace id field of a W3C `traceparent`.
+fn trace_id(traceparent: &str) -> &str {
+    traceparent.split('-').nth(1).unwrap()
+}
+
+#[tokio::test]
+async fn test_trace_context_reaches_the_auth_service() {
+    init_tracing();
+    let app = TestApp::memory().await;
+    let alice = app.create_user("alice").await;
+
+    let incoming = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+    let response = app.request(counters_request(&alice, Some(incoming))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let sent = app.auth.traceparents();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(trace_id(&sent[0]), "4bf92f3577b34da6a3ce929d0e0e4736");
+    // The auth call is a child of our span, not of the caller's
+    assert!(!sent[0].contains("00f067aa0ba902b7"), "{}", sent[0]);
+}
+
+#[tokio::test]
+async fn test_requests_without_context_start_a_trace() {
+    init_tracing();
+    let app = TestApp::memory().await;
+    let alice = app.create_user("alice").await;
+
+    app.request(counters_request(&alice, None)).await;
+    app.request(counters_request(&alice, None)).await;
+
+    let sent = app.auth.traceparents();
+    assert_eq!(sent.len(), 2);
+    assert_ne!(trace_id(&sent[0]), "00000000000000000000000000000000");
+    assert_ne!(trace_id(&sent[0]), trace_id(&sent[1]));
+}
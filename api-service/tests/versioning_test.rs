@@ -0,0 +1,133 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+};
+use chrono::NaiveDate;
+use common::{json_body, TestApp, TestUser};
+
+fn get(uri: &str, user: &TestUser) -> Request<Body> {
+    Request::builder()
+        .uri(uri)
+        .header(header::AUTHORIZATION, format!("Bearer {}", user.token))
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_versioned_routes_are_not_deprecated() {
+    let app = TestApp::memory().await;
+    let alice = app.create_user("alice").await;
+
+    let (status, body) = app
+        .send(
+            Method::POST,
+            "/api/v1/categories",
+            Some(&alice),
+            Some(serde_json::json!({
+                "name": "Coffee",
+                "icon": "coffee",
+                "color": "#8B4513",
+                "unit": "ml",
+                "default_amount": 250.0
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(body["data"]["name"], "Coffee");
+
+    let response = app.request(get("/api/v1/categories", &alice)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!response.headers().contains_key("deprecation"));
+    assert!(!response.headers().contains_key(header::LINK));
+
+    // Same data behind the alias
+    let body = json_body(app.request(get("/api/categories", &alice)).await).await;
+    assert_eq!(body["data"][0]["name"], "Coffee");
+
+    for uri in ["/api/v1/nope", "/api/nope", "/api/v3/categories"] {
+        let (status, body) = app.send(Method::GET, uri, Some(&alice), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{}", uri);
+        assert_eq!(body["message"], "Route not found", "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn test_unversioned_aliases_are_deprecated() {
+    let app = TestApp::memory_with(|config| {
+        config.api.legacy_sunset = NaiveDate::from_ymd_opt(2027, 6, 30);
+    })
+    .await;
+    let alice = app.create_user("alice").await;
+
+    let response = app
+        .request(get(
+            "/api/counters/range?start_date=2024-01-01&end_date=2024-01-31",
+            &alice,
+        ))
+        .await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let headers = response.headers();
+    assert!(headers["deprecation"].to_str().unwrap().starts_with('@'));
+    assert_eq!(headers["sunset"], "Wed, 30 Jun 2027 00:00:00 GMT");
+    assert_eq!(
+        headers[header::LINK],
+        r#"</api/v1/counters/range>; rel="successor-version""#
+    );
+}
+
+#[tokio::test]
+async fn test_path_version_selects_the_response_shape() {
+    let app = TestApp::memory().await;
+    let alice = app.create_user("alice").await;
+    let coffee = app.create_category(&alice, "Coffee", "ml").await;
+
+    let response = app.request(get("/api/v2/counters", &alice)).await;
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "application/vnd.coffee-counter.v2+json"
+    );
+    let v2 = json_body(response).await;
+    assert_eq!(v2["data"]["categories"][0]["category_id"], coffee["id"]);
+
+    // The path wins over Accept
+    let mut request = get("/api/v1/counters", &alice);
+    request.headers_mut().insert(
+        header::ACCEPT,
+        "application/vnd.coffee-counter.v2+json".parse().unwrap(),
+    );
+    let v1 = json_body(app.request(request).await).await;
+    assert_eq!(
+        v1["data"]["categories"]["Coffee"]["category_id"],
+        coffee["id"]
+    );
+    assert_eq!(v1["data"]["order"][0], "Coffee");
+
+    // Handlers without a second shape serve the same body under both
+    let (_, v1) = app
+        .send(Method::GET, "/api/v1/categories", Some(&alice), None)
+        .await;
+    let (_, v2) = app
+        .send(Method::GET, "/api/v2/categories", Some(&alice), None)
+        .await;
+    assert_eq!(v1, v2);
+}
+
+#[tokio::test]
+async fn test_aliases_can_be_turned_off() {
+    let app = TestApp::memory_with(|config| config.api.legacy_routes = false).await;
+    let alice = app.create_user("alice").await;
+
+    let (status, body) = app
+        .send(Method::GET, "/api/categories", Some(&alice), None)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["message"], "Route not found");
+
+    let (status, _) = app
+        .send(Method::GET, "/api/v1/categories", Some(&alice), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+}
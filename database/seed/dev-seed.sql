@@ -0,0 +1,18 @@
+-- Sample data for local development
+-- Apply after the API service has run its migrations:
+--   psql "$DATABASE_URL" -f database/seed/dev-seed.sql
+
+INSERT INTO users (email, password_hash, display_name, email_verified) VALUES
+('test@example.com', '$2b$12$LQv3c1yqBWVHxkd0LHAkCOYz6TtxMQJqhN8/LewdBPj/RK.s5uO.G', 'Test User', true)
+ON CONFLICT (email) DO NOTHING;
+
+-- Get the test user ID and create default categories
+DO $$
+DECLARE
+    test_user_id UUID;
+BEGIN
+    SELECT id INTO test_user_id FROM users WHERE email = 'test@example.com';
+    IF NOT EXISTS (SELECT 1 FROM categories WHERE user_id = test_user_id) THEN
+        PERFORM create_default_categories(test_user_id);
+    END IF;
+END $$;